@@ -0,0 +1,276 @@
+// Prefix of paths of the admin API enabled by --admin
+pub const PREFIX: &str = "/__gateway/admin/";
+
+type Flag = fn(&mut crate::Opt) -> &mut bool;
+
+// Flags the admin API toggles at runtime, keyed by their option names
+const FEATURES: &[(&str, Flag)] = &[
+    ("etags", |opt| &mut opt.etags),
+    ("security-headers", |opt| &mut opt.security_headers),
+    ("cloudfront-headers", |opt| &mut opt.cloudfront_headers),
+    ("strict-responses", |opt| &mut opt.strict_responses),
+    ("raw-fallback", |opt| &mut opt.raw_fallback),
+    ("label-charset", |opt| &mut opt.label_charset),
+    ("decompress-requests", |opt| &mut opt.decompress_requests),
+    ("enforce-response-limit", |opt| {
+        &mut opt.enforce_response_limit
+    }),
+    ("trust-proxy", |opt| &mut opt.trust_proxy),
+    ("alb-multi-value-headers", |opt| {
+        &mut opt.alb_multi_value_headers
+    }),
+];
+
+/// Respond to a request of the admin API
+///
+/// Requests need `Authorization: Bearer TOKEN` of `--admin-token`, or come from loopback
+/// addresses without it.
+///
+/// - `GET /__gateway/admin/routes` lists routes
+/// - `POST /__gateway/admin/routes/NAME/switch` switches routes of the name between their
+///   targets and `green` URLs, or to the side given by `?to=blue` or `?to=green`
+/// - `PUT /__gateway/admin/routes/NAME/target` changes the target of configured routes of the
+///   name to the URL of the body like `{"url": "http://localhost:9002"}`
+/// - `GET /__gateway/admin/features` and `PUT /__gateway/admin/features/NAME` with `true` or
+///   `false` show and toggle flags like `etags`
+/// - `GET /__gateway/admin/config` shows the options of the listener
+///
+/// Changes last until the options are reloaded.
+pub async fn handle(
+    current: &crate::Current,
+    gateway: &crate::Gateway,
+    remote_addr: std::net::SocketAddr,
+    request: hyper::Request<hyper::Body>,
+) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    if !authorized(&gateway.opt, remote_addr, &request) {
+        log::warn!("Rejected admin request from {}", remote_addr);
+        return respond(
+            hyper::StatusCode::UNAUTHORIZED,
+            serde_json::json!({ "message": "Unauthorized" }),
+        );
+    }
+    let (parts, body) = request.into_parts();
+    let path = &parts.uri.path()[PREFIX.len()..];
+    let segments: Vec<&str> = path.split('/').collect();
+    let method = &parts.method;
+    match (segments.as_slice(), method) {
+        (["routes"], &hyper::Method::GET) => {
+            let routes = gateway.routes.snapshot();
+            let routes: Vec<serde_json::Value> =
+                routes.iter().map(|route| route.describe()).collect();
+            respond(hyper::StatusCode::OK, routes.into())
+        }
+        (["routes", name, "switch"], &hyper::Method::POST) => {
+            let to = parts
+                .uri
+                .query()
+                .unwrap_or_default()
+                .split('&')
+                .find_map(|p| p.strip_prefix("to="));
+            let green = match to {
+                None => None,
+                Some("blue") => Some(false),
+                Some("green") => Some(true),
+                Some(to) => {
+                    return bad_request(format!("to must be blue or green but got {}", to));
+                }
+            };
+            switch(gateway, name, green)
+        }
+        (["routes", name, "target"], &hyper::Method::PUT) => {
+            let body = hyper::body::to_bytes(body).await?;
+            let url = match serde_json::from_slice::<serde_json::Value>(&body) {
+                Ok(serde_json::Value::Object(body)) => body
+                    .get("url")
+                    .and_then(|url| url.as_str())
+                    .map(|url| url.to_owned()),
+                _ => None,
+            };
+            let url = match url {
+                Some(url) => url,
+                None => {
+                    return bad_request(
+                        r#"body must be like {"url": "http://localhost:9002"}"#.to_owned(),
+                    )
+                }
+            };
+            match gateway.routes.set_target_url(name, &url) {
+                Ok(0) => not_found(format!("No configured route named {}", name)),
+                Ok(_) => {
+                    log::info!("Changed the target of route {} to {}", name, url);
+                    let routes = gateway.routes.snapshot();
+                    let routes: Vec<serde_json::Value> = routes
+                        .iter()
+                        .filter(|route| route.name.as_deref() == Some(*name))
+                        .map(|route| route.describe())
+                        .collect();
+                    respond(hyper::StatusCode::OK, routes.into())
+                }
+                Err(e) => bad_request(format!("{:#}", e)),
+            }
+        }
+        (["features"], &hyper::Method::GET) => {
+            respond(hyper::StatusCode::OK, features(&gateway.opt))
+        }
+        (["features", name], &hyper::Method::PUT) => {
+            let body = hyper::body::to_bytes(body).await?;
+            let enabled = match serde_json::from_slice(&body) {
+                Ok(serde_json::Value::Bool(enabled)) => enabled,
+                _ => return bad_request("body must be true or false".to_owned()),
+            };
+            let flag = match FEATURES.iter().find(|(feature, _)| feature == name) {
+                Some((_, flag)) => flag,
+                None => return not_found(format!("No feature named {}", name)),
+            };
+            let mut current = current.write().unwrap();
+            let mut opt = current.opt.clone();
+            *flag(&mut opt) = enabled;
+            let features = features(&opt);
+            // Requests in flight complete with the previous options
+            *current = std::sync::Arc::new(current.with_opt(opt));
+            log::info!("{} {}", if enabled { "Enabled" } else { "Disabled" }, name);
+            respond(hyper::StatusCode::OK, features)
+        }
+        (["config"], &hyper::Method::GET) => respond(hyper::StatusCode::OK, config(gateway)),
+        (["routes"], _)
+        | (["routes", _, "switch"], _)
+        | (["routes", _, "target"], _)
+        | (["features"], _)
+        | (["features", _], _)
+        | (["config"], _) => respond(
+            hyper::StatusCode::METHOD_NOT_ALLOWED,
+            serde_json::json!({ "message": "Method Not Allowed" }),
+        ),
+        _ => not_found("Not Found".to_owned()),
+    }
+}
+
+fn authorized(
+    opt: &crate::Opt,
+    remote_addr: std::net::SocketAddr,
+    request: &hyper::Request<hyper::Body>,
+) -> bool {
+    match &opt.admin_token {
+        Some(token) => request
+            .headers()
+            .get(hyper::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .is_some_and(|given| {
+                // Compare every byte so that the time doesn't tell the matching prefix
+                given.len() == token.len()
+                    && given
+                        .bytes()
+                        .zip(token.bytes())
+                        .fold(0, |acc, (a, b)| acc | (a ^ b))
+                        == 0
+            }),
+        None => remote_addr.ip().is_loopback(),
+    }
+}
+
+fn switch(
+    gateway: &crate::Gateway,
+    name: &str,
+    green: Option<bool>,
+) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    let routes = gateway.routes.snapshot();
+    let mut routes = routes
+        .iter()
+        .filter(|route| route.name.as_deref() == Some(name))
+        .peekable();
+    if routes.peek().is_none() {
+        return not_found(format!("No route named {}", name));
+    }
+    let mut switched = Vec::new();
+    for route in routes {
+        let active = match route.switch(green) {
+            Ok(active) => active,
+            Err(e) => return bad_request(format!("Can't switch {}: {}", name, e)),
+        };
+        let target_url = route.active_url().unwrap_or_default();
+        log::info!("Switched route {} to {}", name, target_url);
+        switched.push(serde_json::json!({
+            "name": name,
+            "active": if active { "green" } else { "blue" },
+            "targetUrl": target_url,
+        }));
+    }
+    respond(hyper::StatusCode::OK, serde_json::Value::Array(switched))
+}
+
+fn features(opt: &crate::Opt) -> serde_json::Value {
+    let mut opt = opt.clone();
+    let features: serde_json::Map<String, serde_json::Value> = FEATURES
+        .iter()
+        .map(|(name, flag)| ((*name).to_owned(), (*flag(&mut opt)).into()))
+        .collect();
+    features.into()
+}
+
+// Options of the listener, leaving out secrets like --admin-token
+fn config(gateway: &crate::Gateway) -> serde_json::Value {
+    let opt = &gateway.opt;
+    let pairs = |pairs: &[(String, String)]| -> serde_json::Value {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.clone(), serde_json::Value::from(v.as_str())))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    };
+    serde_json::json!({
+        "bind": opt.bind,
+        "targetUrl": opt.target_url,
+        "fallbackUrl": opt.fallback_url,
+        "payloadFormat": opt.payload_format.to_string(),
+        "stage": opt.stage,
+        "stripStagePrefix": opt.strip_stage_prefix,
+        "stageVariables": pairs(&opt.stage_variables),
+        "functionTargets": pairs(&opt.function_targets),
+        "functionAliases": pairs(&opt.function_aliases),
+        "resources": opt.resources.iter().map(|r| r.to_string()).collect::<Vec<_>>(),
+        "passthrough": opt.passthrough_resources.iter().map(|r| r.to_string()).collect::<Vec<_>>(),
+        "timeoutMillis": opt.timeout_millis,
+        "maxRequestBody": opt.max_request_body,
+        "cacheTtl": opt.cache_ttl,
+        "idempotencyTtl": opt.idempotency_ttl,
+        "cors": if crate::cors::is_enabled(opt) {
+            serde_json::json!({
+                "allowOrigins": opt.cors_allow_origins,
+                "allowMethods": opt.cors_allow_methods,
+                "allowHeaders": opt.cors_allow_headers,
+                "exposeHeaders": opt.cors_expose_headers,
+                "allowCredentials": opt.cors_allow_credentials,
+                "maxAge": opt.cors_max_age,
+            })
+        } else {
+            serde_json::Value::Null
+        },
+        "features": features(opt),
+        "routes": gateway.routes.snapshot().len(),
+    })
+}
+
+fn bad_request(message: String) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    respond(
+        hyper::StatusCode::BAD_REQUEST,
+        serde_json::json!({ "message": message }),
+    )
+}
+
+fn not_found(message: String) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    respond(
+        hyper::StatusCode::NOT_FOUND,
+        serde_json::json!({ "message": message }),
+    )
+}
+
+fn respond(
+    status: hyper::StatusCode,
+    body: serde_json::Value,
+) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    Ok(hyper::Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(hyper::Body::from(body.to_string()))?)
+}
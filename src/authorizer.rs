@@ -0,0 +1,178 @@
+/// Types of Lambda authorizers like REST APIs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    // Events have the token of the identity source only
+    Token,
+    // Events have the request like proxy events
+    Request,
+}
+impl std::str::FromStr for Type {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "token" => Ok(Self::Token),
+            "request" => Ok(Self::Request),
+            _ => Err(anyhow::anyhow!(
+                "authorizer type must be token or request: {}",
+                s
+            )),
+        }
+    }
+}
+
+/// Named context of authorizers in NAME=JSON form, whose JSON is a file or an inline object like
+/// `admin={"claims":{"scope":"admin"}}`
+pub fn parse_preset(s: &str) -> Result<(String, serde_json::Value), anyhow::Error> {
+    let (name, context) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("authorizer preset must be in NAME=JSON form: {}", s))?;
+    let context = if context.starts_with('{') {
+        serde_json::from_str(context)
+            .map_err(|e| anyhow::anyhow!("invalid authorizer preset {}: {}", name, e))?
+    } else {
+        crate::json::read_file(context)?
+    };
+    if !context.is_object() {
+        anyhow::bail!("authorizer preset {} must be an object", name);
+    }
+    Ok((name.to_owned(), context))
+}
+
+/// Outcome of the authorizer
+#[derive(Debug)]
+pub enum Decision {
+    // requestContext.authorizer of the event of the function
+    Allow(serde_json::Value),
+    // 401, which authorizers request by failing with "Unauthorized"
+    Unauthorized,
+    // 403 with the message
+    Deny(&'static str),
+}
+
+/// Event of the authorizer built from the proxy event of the request
+///
+/// `methodArn` of REST APIs and `routeArn` of HTTP APIs are the ARN of the method. HTTP API
+/// events are proxy events of payload format 2.0 with `identitySource`.
+pub fn event(
+    type_: Type,
+    payload_format: crate::payload::PayloadFormat,
+    proxy_event: &serde_json::Value,
+    identity: Option<&str>,
+    method_arn: &str,
+) -> serde_json::Value {
+    if type_ == Type::Token {
+        return serde_json::json!({
+            "type": "TOKEN",
+            "authorizationToken": identity,
+            "methodArn": method_arn,
+        });
+    }
+    let mut event = proxy_event.clone();
+    if let serde_json::Value::Object(event) = &mut event {
+        // Authorizers don't get bodies
+        event.remove("body");
+        event.remove("isBase64Encoded");
+        event.insert("type".to_owned(), "REQUEST".into());
+        if payload_format == crate::payload::PayloadFormat::V2 {
+            event.insert("routeArn".to_owned(), method_arn.into());
+            event.insert(
+                "identitySource".to_owned(),
+                identity.into_iter().collect::<Vec<_>>().into(),
+            );
+        } else {
+            event.insert("methodArn".to_owned(), method_arn.into());
+        }
+    }
+    event
+}
+
+/// Decide by the result of the authorizer, which is an IAM policy or, with simple responses of
+/// HTTP APIs, `{"isAuthorized": true, "context": {...}}`
+pub fn decide(
+    payload_format: crate::payload::PayloadFormat,
+    simple_responses: bool,
+    method_arn: &str,
+    function_error: bool,
+    result: &[u8],
+) -> Result<Decision, anyhow::Error> {
+    if let Some(e) = crate::payload::FunctionError::parse(function_error, result) {
+        if e.error_message == "Unauthorized" {
+            return Ok(Decision::Unauthorized);
+        }
+        anyhow::bail!("authorizer failed: {}", e.error_message);
+    }
+    let result: serde_json::Value = serde_json::from_slice(result)
+        .map_err(|e| anyhow::anyhow!("invalid result of the authorizer: {}", e))?;
+    let is_v2 = payload_format == crate::payload::PayloadFormat::V2;
+    let context = result.get("context").cloned().unwrap_or_default();
+    if simple_responses && is_v2 {
+        return match result.get("isAuthorized") {
+            Some(serde_json::Value::Bool(true)) => {
+                Ok(Decision::Allow(serde_json::json!({ "lambda": context })))
+            }
+            Some(serde_json::Value::Bool(false)) => Ok(Decision::Deny("Forbidden")),
+            _ => anyhow::bail!("authorizer result has no boolean isAuthorized"),
+        };
+    }
+
+    let statements = match &result["policyDocument"]["Statement"] {
+        serde_json::Value::Array(statements) => statements.iter().collect(),
+        serde_json::Value::Object(_) => vec![&result["policyDocument"]["Statement"]],
+        _ => anyhow::bail!("authorizer result has no policyDocument.Statement"),
+    };
+    let applies = |effect: &str| {
+        statements.iter().any(|statement| {
+            statement["Effect"].as_str() == Some(effect)
+                && matches_any(&statement["Action"], "execute-api:Invoke")
+                && matches_any(&statement["Resource"], method_arn)
+        })
+    };
+    if applies("Deny") {
+        return Ok(Decision::Deny(if is_v2 {
+            "Forbidden"
+        } else {
+            "User is not authorized to access this resource with an explicit deny"
+        }));
+    }
+    if !applies("Allow") {
+        return Ok(Decision::Deny(if is_v2 {
+            "Forbidden"
+        } else {
+            "User is not authorized to access this resource"
+        }));
+    }
+    if is_v2 {
+        return Ok(Decision::Allow(serde_json::json!({ "lambda": context })));
+    }
+    // REST APIs pass values of the context as strings
+    let mut authorizer: serde_json::Map<String, serde_json::Value> = context
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(k, v)| {
+            let v = match v {
+                serde_json::Value::String(v) => v.clone(),
+                v => v.to_string(),
+            };
+            (k.clone(), v.into())
+        })
+        .collect();
+    if let Some(principal_id) = result.get("principalId") {
+        authorizer.insert("principalId".to_owned(), principal_id.clone());
+    }
+    Ok(Decision::Allow(authorizer.into()))
+}
+
+// Whether the pattern or one of the patterns like arn:aws:execute-api:*:*:*/*/GET/* matches
+fn matches_any(patterns: &serde_json::Value, value: &str) -> bool {
+    let matches = |pattern: &serde_json::Value| {
+        pattern
+            .as_str()
+            .is_some_and(|pattern| crate::header_filter::glob_match(pattern, value))
+    };
+    match patterns {
+        serde_json::Value::Array(patterns) => patterns.iter().any(matches),
+        pattern => matches(pattern),
+    }
+}
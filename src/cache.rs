@@ -0,0 +1,124 @@
+/// In-memory response cache like stage caching of REST APIs
+///
+/// Keys consist of the host, the path and the values of the configured cache key headers and
+/// query string parameters of `GET` requests.
+#[derive(Debug)]
+pub struct Cache {
+    ttl: std::time::Duration,
+    key_headers: Vec<String>,
+    key_query_parameters: Vec<String>,
+    entries: std::sync::Mutex<std::collections::HashMap<String, Entry>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    expires_at: std::time::Instant,
+    status: hyper::StatusCode,
+    headers: hyper::HeaderMap,
+    body: bytes::Bytes,
+}
+
+impl Cache {
+    pub fn new(
+        ttl: std::time::Duration,
+        key_headers: &[String],
+        key_query_parameters: &[String],
+    ) -> Self {
+        Self {
+            ttl,
+            key_headers: key_headers.iter().map(|h| h.to_ascii_lowercase()).collect(),
+            key_query_parameters: key_query_parameters.to_vec(),
+            entries: std::sync::Mutex::new(std::collections::HashMap::new()),
+        }
+    }
+
+    /// Cache key of the request, or None when the request isn't cacheable
+    pub fn key<B>(&self, request: &hyper::Request<B>) -> Option<String> {
+        if request.method() != hyper::Method::GET {
+            return None;
+        }
+        // Routes can be chosen by hosts, whose responses differ for the same path
+        let host = request
+            .headers()
+            .get(hyper::header::HOST)
+            .and_then(|v| v.to_str().ok())
+            .or_else(|| request.uri().host())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let mut key = format!("{}{}", host, request.uri().path());
+        let query: Vec<(&str, &str)> = request
+            .uri()
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|p| !p.is_empty())
+            .map(|p| {
+                let mut kv = p.splitn(2, '=');
+                (kv.next().unwrap_or_default(), kv.next().unwrap_or_default())
+            })
+            .collect();
+        for name in &self.key_query_parameters {
+            let values: Vec<&str> = query
+                .iter()
+                .filter(|(k, _)| k == name)
+                .map(|(_, v)| *v)
+                .collect();
+            key.push_str(&format!("\n?{}={}", name, values.join(",")));
+        }
+        for name in &self.key_headers {
+            let values: Vec<String> = request
+                .headers()
+                .get_all(name.as_str())
+                .iter()
+                .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+                .collect();
+            key.push_str(&format!("\n{}: {}", name, values.join(",")));
+        }
+        Some(key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<hyper::Response<hyper::Body>> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some(entry) if entry.expires_at > std::time::Instant::now() => {
+                let mut response = hyper::Response::new(hyper::Body::from(entry.body.clone()));
+                *response.status_mut() = entry.status;
+                *response.headers_mut() = entry.headers.clone();
+                Some(response)
+            }
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Store the response, returning the response to send
+    pub async fn put(
+        &self,
+        key: String,
+        response: hyper::Response<hyper::Body>,
+    ) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+        let (parts, body) = response.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        let now = std::time::Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        // Sweep expired entries so that keys never requested again don't stay forever
+        entries.retain(|_, entry| entry.expires_at > now);
+        entries.insert(
+            key,
+            Entry {
+                expires_at: now + self.ttl,
+                status: parts.status,
+                headers: parts.headers.clone(),
+                body: body.clone(),
+            },
+        );
+        Ok(hyper::Response::from_parts(parts, hyper::Body::from(body)))
+    }
+
+    pub fn invalidate(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
@@ -0,0 +1,153 @@
+const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Print the routing table of each listener with problems like conflicting routes, listeners of the
+/// same address and targets refusing connections, returning whether there were no problems
+///
+/// Routes are the ones resolved from the options like `--sam-template` and `--openapi`, so the
+/// routes of Docker containers and Kubernetes aren't checked.
+pub async fn run(gateways: &[crate::Gateway]) -> bool {
+    let mut problems = Vec::new();
+    for (i, gateway) in gateways.iter().enumerate() {
+        let opt = &gateway.opt;
+        if let Some(j) = gateways[..i].iter().position(|g| g.opt.bind == opt.bind) {
+            problems.push(format!(
+                "listeners {} and {} bind the same address {}",
+                j + 1,
+                i + 1,
+                opt.bind
+            ));
+        }
+        let routes = gateway.routes.snapshot();
+        let mut rows: Vec<[String; 3]> = routes
+            .iter()
+            .map(|route| {
+                let description = route.describe();
+                let options: Vec<String> = description
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .filter(|(key, _)| *key != "pattern" && *key != "target")
+                    .map(|(key, value)| match value {
+                        serde_json::Value::String(value) => format!("{}={}", key, value),
+                        // Like {"url":"http://localhost:9002","weight":10.0} of canary
+                        value => format!("{}={}", key, value),
+                    })
+                    .collect();
+                [
+                    description["pattern"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_owned(),
+                    description["target"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_owned(),
+                    options.join(" "),
+                ]
+            })
+            .collect();
+        for resource in &opt.resources {
+            rows.push([resource.to_string(), opt.target_url.clone(), String::new()]);
+        }
+        for resource in &opt.passthrough_resources {
+            rows.push([
+                resource.to_string(),
+                opt.target_url.clone(),
+                "passthrough".to_owned(),
+            ]);
+        }
+        rows.push([
+            "(default)".to_owned(),
+            opt.target_url.clone(),
+            String::new(),
+        ]);
+        if let Some(fallback_url) = &opt.fallback_url {
+            rows.push(["(fallback)".to_owned(), fallback_url.clone(), String::new()]);
+        }
+
+        println!("Listener {}", opt.bind);
+        let width = |column: usize| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or_default()
+                .max(7)
+        };
+        let (pattern_width, target_width) = (width(0), width(1));
+        println!(
+            "  {:pattern_width$}  {:target_width$}  OPTIONS",
+            "PATTERN",
+            "TARGET",
+            pattern_width = pattern_width,
+            target_width = target_width
+        );
+        for [pattern, target, options] in &rows {
+            let line = format!(
+                "  {:pattern_width$}  {:target_width$}  {}",
+                pattern,
+                target,
+                options,
+                pattern_width = pattern_width,
+                target_width = target_width
+            );
+            println!("{}", line.trim_end());
+        }
+        println!();
+
+        for (i, j) in crate::route::conflicts(&routes) {
+            problems.push(format!(
+                "route {} of {} is never matched since route {} matches the same requests",
+                rows[j][0], opt.bind, rows[i][0],
+            ));
+        }
+
+        let mut urls: Vec<&str> = routes.iter().flat_map(|route| route.urls()).collect();
+        urls.push(&opt.target_url);
+        urls.extend(opt.fallback_url.as_deref());
+        // Templates are filled by functions of requests
+        urls.retain(|url| !url.contains('{'));
+        urls.sort_unstable();
+        urls.dedup();
+        let probes = urls.iter().map(|url| async move {
+            probe(gateway, url)
+                .await
+                .err()
+                .map(|e| format!("target {} of {} is unreachable: {:#}", url, opt.bind, e))
+        });
+        problems.extend(
+            futures::future::join_all(probes)
+                .await
+                .into_iter()
+                .flatten(),
+        );
+    }
+
+    for problem in &problems {
+        println!("error: {}", problem);
+    }
+    if problems.is_empty() {
+        println!("No problems found");
+    }
+    problems.is_empty()
+}
+
+// Connect to the host and the port of the URL, resolving srv:// URLs
+async fn probe(gateway: &crate::Gateway, url: &str) -> Result<(), anyhow::Error> {
+    let resolved = gateway.srv.resolve(url).await?;
+    let parsed = reqwest::Url::parse(&resolved)?;
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| anyhow::anyhow!("no host"))?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = parsed
+        .port_or_known_default()
+        .ok_or_else(|| anyhow::anyhow!("no port"))?;
+    tokio::time::timeout(
+        CONNECT_TIMEOUT,
+        tokio::net::TcpStream::connect((host, port)),
+    )
+    .await
+    .map_err(|_| anyhow::anyhow!("connection timed out"))??;
+    Ok(())
+}
@@ -0,0 +1,41 @@
+// Headers which CloudFront adds to requests of edge-optimized APIs
+const DEFAULT_HEADERS: &[(&str, &str)] = &[
+    ("cloudfront-forwarded-proto", "https"),
+    ("cloudfront-is-desktop-viewer", "true"),
+    ("cloudfront-is-mobile-viewer", "false"),
+    ("cloudfront-is-smarttv-viewer", "false"),
+    ("cloudfront-is-tablet-viewer", "false"),
+    ("cloudfront-viewer-country", "US"),
+    (
+        "via",
+        "2.0 0123456789abcdef0123456789abcdef.cloudfront.net (CloudFront)",
+    ),
+];
+
+/// Add CloudFront headers missing in the request
+///
+/// `overrides` replace the default values or add extra headers.
+pub fn inject(
+    request: &mut crate::payload::Request,
+    overrides: &[(String, String)],
+    cf_id: uuid::Uuid,
+) {
+    let mut headers: Vec<(String, String)> = DEFAULT_HEADERS
+        .iter()
+        .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+        .collect();
+    for (k, v) in overrides {
+        let k = k.to_ascii_lowercase();
+        match headers.iter_mut().find(|(name, _)| *name == k) {
+            Some(header) => header.1 = v.clone(),
+            None => headers.push((k, v.clone())),
+        }
+    }
+    headers.push(("x-amz-cf-id".to_owned(), base64::encode(cf_id.as_bytes())));
+    for (k, v) in headers {
+        // Headers sent by the client win so that each request can simulate different viewers
+        if !request.headers.iter().any(|(name, _)| *name == k) {
+            request.headers.push((k, v));
+        }
+    }
+}
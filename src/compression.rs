@@ -0,0 +1,181 @@
+use std::io::Read as _;
+use std::io::Write as _;
+
+// Supported content codings in order of preference when clients accept them equally
+const ENCODINGS: &[&str] = &["br", "gzip", "deflate"];
+
+/// Compress the response body with the coding preferred by `Accept-Encoding`
+///
+/// Like `minimumCompressionSize` of REST APIs, bodies smaller than `minimum_size` bytes are sent
+/// as they are. So are bodies already encoded by the function.
+pub async fn compress(
+    response: hyper::Response<hyper::Body>,
+    accept_encoding: &str,
+    minimum_size: usize,
+) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    if response
+        .headers()
+        .contains_key(hyper::header::CONTENT_ENCODING)
+    {
+        return Ok(response);
+    }
+    let encoding = match negotiate(accept_encoding) {
+        Some(encoding) => encoding,
+        None => return Ok(response),
+    };
+    let (mut parts, body) = response.into_parts();
+    let body = hyper::body::to_bytes(body).await?;
+    if body.is_empty() || body.len() < minimum_size {
+        return Ok(hyper::Response::from_parts(parts, hyper::Body::from(body)));
+    }
+
+    let compressed = match encoding {
+        "br" => {
+            let mut compressed = Vec::new();
+            {
+                let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, 5, 22);
+                writer.write_all(&body)?;
+            }
+            compressed
+        }
+        "gzip" => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&body)?;
+            encoder.finish()?
+        }
+        // deflate of HTTP is the zlib format
+        _ => {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&body)?;
+            encoder.finish()?
+        }
+    };
+    log::debug!(
+        "Compressed response body with {} from {} to {} bytes",
+        encoding,
+        body.len(),
+        compressed.len()
+    );
+    parts.headers.remove(hyper::header::CONTENT_LENGTH);
+    parts.headers.insert(
+        hyper::header::CONTENT_ENCODING,
+        hyper::header::HeaderValue::from_static(encoding),
+    );
+    parts.headers.append(
+        hyper::header::VARY,
+        hyper::header::HeaderValue::from_static("Accept-Encoding"),
+    );
+    Ok(hyper::Response::from_parts(
+        parts,
+        hyper::Body::from(compressed),
+    ))
+}
+
+// The supported coding with the highest q-value, where `*` covers codings not listed
+fn negotiate(accept_encoding: &str) -> Option<&'static str> {
+    let qualities: Vec<(String, f32)> = accept_encoding
+        .split(',')
+        .map(|item| {
+            let mut params = item.split(';');
+            let coding = params
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase();
+            let q = params
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.trim().parse().ok())
+                .unwrap_or(1.0);
+            (coding, q)
+        })
+        .collect();
+    let quality = |encoding: &str| {
+        qualities
+            .iter()
+            .find(|(coding, _)| coding == encoding)
+            .or_else(|| qualities.iter().find(|(coding, _)| coding == "*"))
+            .map_or(0.0, |(_, q)| *q)
+    };
+
+    let mut best = None;
+    let mut best_quality = 0.0;
+    for encoding in ENCODINGS {
+        let q = quality(encoding);
+        if q > best_quality {
+            best = Some(*encoding);
+            best_quality = q;
+        }
+    }
+    best
+}
+
+/// Decode a request body sent with `Content-Encoding`, which functions behind API Gateway never see
+///
+/// Bodies in unsupported codings are left as they are, and decoded bodies exceeding
+/// `max_body_size` bytes fail with `BodyTooLarge`.
+pub fn decompress_request(
+    request: &mut crate::payload::Request,
+    max_body_size: usize,
+) -> Result<(), anyhow::Error> {
+    let content_encoding = match request.header(&hyper::header::CONTENT_ENCODING) {
+        Some(content_encoding) => content_encoding.to_ascii_lowercase(),
+        None => return Ok(()),
+    };
+    let codings: Vec<&str> = content_encoding
+        .split(',')
+        .map(|coding| coding.trim())
+        .filter(|coding| !coding.is_empty() && *coding != "identity")
+        .collect();
+    if !codings
+        .iter()
+        .all(|coding| matches!(*coding, "br" | "gzip" | "x-gzip" | "deflate"))
+    {
+        log::warn!(
+            "Leaving request body in unsupported Content-Encoding {} as is",
+            content_encoding
+        );
+        return Ok(());
+    }
+
+    // Codings are listed in the order they were applied
+    let mut body = request.body.to_vec();
+    for coding in codings.iter().rev() {
+        let mut decoded = Vec::new();
+        // Read a byte over the limit to tell bodies exceeding it, so that small bombs can't
+        // exhaust memory
+        let limit = max_body_size as u64 + 1;
+        match *coding {
+            "br" => brotli::Decompressor::new(&body[..], 4096)
+                .take(limit)
+                .read_to_end(&mut decoded),
+            "gzip" | "x-gzip" => flate2::read::MultiGzDecoder::new(&body[..])
+                .take(limit)
+                .read_to_end(&mut decoded),
+            _ => flate2::read::ZlibDecoder::new(&body[..])
+                .take(limit)
+                .read_to_end(&mut decoded),
+        }
+        .map_err(|e| anyhow::anyhow!("failed to decode {} request body: {}", coding, e))?;
+        if decoded.len() > max_body_size {
+            return Err(crate::payload::BodyTooLarge {
+                limit: max_body_size,
+            }
+            .into());
+        }
+        body = decoded;
+    }
+    request.remove_header(hyper::header::CONTENT_ENCODING.as_str());
+    if request
+        .remove_header(hyper::header::CONTENT_LENGTH.as_str())
+        .is_some()
+    {
+        request.headers.push((
+            hyper::header::CONTENT_LENGTH.as_str().to_owned(),
+            body.len().to_string(),
+        ));
+    }
+    request.body = bytes::Bytes::from(body);
+    Ok(())
+}
@@ -0,0 +1,228 @@
+// Subcommands, which run instead of listening
+const COMMANDS: &[&str] = &["check"];
+
+// Short names of options, which override the file like their long names
+const SHORT_NAMES: &[(&str, &str)] = &[("--bind", "-b"), ("--target-url", "-t")];
+
+/// Command line arguments of each listener with options read from the file given by `--config`
+///
+/// Keys of the YAML or TOML file are long option names like `target-url` or `target_url`.
+/// `true` enables flags, arrays repeat options, and objects give `KEY=VALUE` pairs of options
+/// like `stage-variable`. `profiles` maps names to options selected by `--profile`, or `default`
+/// without it, and `listeners` is an array of options of listeners, each of which overrides the
+/// other options of the file merging mappings. There is one listener without `listeners`.
+/// Options given on the command line by long or short names take precedence over the file, and the
+/// subcommand is left out of them.
+pub fn args() -> Result<Vec<Vec<std::ffi::OsString>>, anyhow::Error> {
+    let mut args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    if command().is_some() {
+        args.remove(1);
+    }
+    let path = match option_value(&args, "--config") {
+        Some(path) => path,
+        None => return Ok(vec![args]),
+    };
+    let config = read_file(&path)?;
+    let options = match config {
+        serde_json::Value::Object(options) => options,
+        serde_json::Value::Null => serde_json::Map::new(),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "{} must contain a mapping of options",
+                path
+            ))
+        }
+    };
+    let mut options = normalize(&path, options)?;
+    let profile = option_value(&args, "--profile");
+    match (options.remove("--profiles"), profile) {
+        (Some(serde_json::Value::Object(profiles)), profile) => {
+            let name = profile.as_deref().unwrap_or("default");
+            match profiles.get(name) {
+                Some(serde_json::Value::Object(profile)) => {
+                    merge(&mut options, normalize(&path, profile.clone())?);
+                }
+                Some(_) => {
+                    return Err(anyhow::anyhow!(
+                        "profile {} in {} must be a mapping of options",
+                        name,
+                        path
+                    ))
+                }
+                None if profile.is_none() => {}
+                None => {
+                    let names: Vec<&str> = profiles.keys().map(|k| k.as_str()).collect();
+                    return Err(anyhow::anyhow!(
+                        "{} has no profile {} but {}",
+                        path,
+                        name,
+                        names.join(", ")
+                    ));
+                }
+            }
+        }
+        (Some(_), _) => return Err(anyhow::anyhow!("profiles in {} must be a mapping", path)),
+        (None, Some(name)) => return Err(anyhow::anyhow!("{} has no profile {}", path, name)),
+        (None, None) => {}
+    }
+    let listeners = match options.get("--listeners") {
+        None => vec![serde_json::Map::new()],
+        Some(serde_json::Value::Array(listeners)) => listeners
+            .iter()
+            .map(|listener| match listener {
+                serde_json::Value::Object(listener) => normalize(&path, listener.clone()),
+                _ => Err(anyhow::anyhow!(
+                    "listeners in {} must be mappings of options",
+                    path
+                )),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err(anyhow::anyhow!("listeners in {} must be an array", path)),
+    };
+
+    let program = if args.is_empty() {
+        None
+    } else {
+        Some(args.remove(0))
+    };
+    let mut listener_args = Vec::with_capacity(listeners.len());
+    for listener in listeners {
+        let mut options = options.clone();
+        options.remove("--listeners");
+        if let Some(name) = ["--listeners", "--profiles"]
+            .iter()
+            .find(|name| listener.contains_key(**name))
+        {
+            return Err(anyhow::anyhow!(
+                "{} can't be in listeners of {}",
+                &name[2..],
+                path
+            ));
+        }
+        merge(&mut options, listener);
+        let mut config_args = Vec::new();
+        for (name, value) in options {
+            let short = SHORT_NAMES
+                .iter()
+                .find(|(long, _)| *long == name)
+                .map(|(_, short)| *short);
+            let given = args.iter().any(|arg| {
+                let arg = arg.to_string_lossy();
+                // Short options can be followed by their values like -b0.0.0.0:8080
+                arg == name
+                    || arg.starts_with(&format!("{}=", name))
+                    || short.is_some_and(|short| arg.starts_with(short))
+            });
+            if !given {
+                push_option(&mut config_args, &name, value)
+                    .map_err(|e| anyhow::anyhow!("invalid {} in {}: {}", &name[2..], path, e))?;
+            }
+        }
+        listener_args.push(
+            program
+                .iter()
+                .cloned()
+                .chain(config_args.into_iter().map(std::ffi::OsString::from))
+                .chain(args.iter().cloned())
+                .collect(),
+        );
+    }
+    Ok(listener_args)
+}
+
+/// Subcommand given before the options like `check`
+pub fn command() -> Option<&'static str> {
+    let arg = std::env::args_os().nth(1)?;
+    COMMANDS.iter().copied().find(|command| arg == **command)
+}
+
+// Override options, keeping pairs of mappings like stage-variable missing in the overlay
+fn merge(
+    options: &mut serde_json::Map<String, serde_json::Value>,
+    overlay: serde_json::Map<String, serde_json::Value>,
+) {
+    for (name, value) in overlay {
+        crate::json::deep_merge(
+            options.entry(name).or_insert(serde_json::Value::Null),
+            value,
+        );
+    }
+}
+
+// Key options by their names like --target-url
+fn normalize(
+    path: &str,
+    options: serde_json::Map<String, serde_json::Value>,
+) -> Result<serde_json::Map<String, serde_json::Value>, anyhow::Error> {
+    let mut normalized = serde_json::Map::new();
+    for (key, value) in options {
+        let name = format!("--{}", key.replace('_', "-"));
+        if name == "--config" || name == "--profile" {
+            return Err(anyhow::anyhow!("{} can't contain {}", path, &name[2..]));
+        }
+        normalized.insert(name, value);
+    }
+    Ok(normalized)
+}
+
+// Value of the option on the command line, which is read before parsing the others
+fn option_value(args: &[std::ffi::OsString], name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    let mut args = args.iter().map(|arg| arg.to_string_lossy());
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next().map(|value| value.into_owned());
+        } else if let Some(value) = arg.strip_prefix(prefix.as_str()) {
+            return Some(value.to_owned());
+        }
+    }
+    None
+}
+
+fn read_file(path: &str) -> Result<serde_json::Value, anyhow::Error> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("failed to open {}: {}", path, e))?;
+    if path.ends_with(".toml") {
+        toml::from_str(&content).map_err(|e| anyhow::anyhow!("failed to parse {}: {}", path, e))
+    } else {
+        serde_yaml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("failed to parse {}: {}", path, e))
+    }
+}
+
+fn push_option(
+    args: &mut Vec<String>,
+    name: &str,
+    value: serde_json::Value,
+) -> Result<(), anyhow::Error> {
+    match value {
+        serde_json::Value::Null | serde_json::Value::Bool(false) => {}
+        serde_json::Value::Bool(true) => args.push(name.to_owned()),
+        serde_json::Value::Array(values) => {
+            for value in values {
+                args.push(name.to_owned());
+                args.push(scalar(value)?);
+            }
+        }
+        serde_json::Value::Object(pairs) => {
+            for (k, v) in pairs {
+                args.push(name.to_owned());
+                args.push(format!("{}={}", k, scalar(v)?));
+            }
+        }
+        value => {
+            args.push(name.to_owned());
+            args.push(scalar(value)?);
+        }
+    }
+    Ok(())
+}
+
+fn scalar(value: serde_json::Value) -> Result<String, anyhow::Error> {
+    match value {
+        serde_json::Value::String(s) => Ok(s),
+        serde_json::Value::Number(n) => Ok(n.to_string()),
+        serde_json::Value::Bool(b) => Ok(b.to_string()),
+        other => Err(anyhow::anyhow!("expected a scalar but got {}", other)),
+    }
+}
@@ -0,0 +1,114 @@
+const ALLOW_ORIGIN: &str = "access-control-allow-origin";
+const ALLOW_CREDENTIALS: &str = "access-control-allow-credentials";
+const EXPOSE_HEADERS: &str = "access-control-expose-headers";
+const ALLOW_METHODS: &str = "access-control-allow-methods";
+const ALLOW_HEADERS: &str = "access-control-allow-headers";
+const MAX_AGE: &str = "access-control-max-age";
+const REQUEST_METHOD: &str = "access-control-request-method";
+
+/// Whether CORS is configured by `--cors-allow-origin`
+pub fn is_enabled(opt: &crate::Opt) -> bool {
+    !opt.cors_allow_origins.is_empty()
+}
+
+/// Origin of the request if it's allowed, matching patterns like `https://*.example.com`
+fn allowed_origin<'a>(opt: &crate::Opt, origin: Option<&'a str>) -> Option<&'a str> {
+    let origin = origin?;
+    opt.cors_allow_origins
+        .iter()
+        .any(|pattern| crate::header_filter::glob_match(pattern, origin))
+        .then_some(origin)
+}
+
+/// Whether the request is a CORS preflight request, which browsers send before requests to other
+/// origins
+pub fn is_preflight<B>(request: &hyper::Request<B>) -> bool {
+    request.method() == hyper::Method::OPTIONS
+        && request.headers().contains_key(hyper::header::ORIGIN)
+        && request.headers().contains_key(REQUEST_METHOD)
+}
+
+/// Response to the preflight request from the origin, which HTTP APIs send without invoking
+/// functions even if there's no route of OPTIONS
+pub fn preflight(
+    opt: &crate::Opt,
+    origin: Option<&str>,
+) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    let mut response = hyper::Response::builder()
+        .status(hyper::StatusCode::NO_CONTENT)
+        .body(hyper::Body::empty())?;
+    let headers = response.headers_mut();
+    // Browsers reject the request when the origin isn't allowed
+    if allow_origin(opt, origin, headers)? {
+        for (name, values) in [
+            (ALLOW_METHODS, &opt.cors_allow_methods),
+            (ALLOW_HEADERS, &opt.cors_allow_headers),
+        ] {
+            if !values.is_empty() {
+                headers.insert(
+                    name,
+                    hyper::header::HeaderValue::from_str(&values.join(","))?,
+                );
+            }
+        }
+        if let Some(max_age) = opt.cors_max_age {
+            headers.insert(MAX_AGE, max_age.into());
+        }
+    }
+    Ok(response)
+}
+
+/// Set CORS headers to the response of the request from the origin like HTTP APIs
+///
+/// Like HTTP APIs, CORS headers returned by functions are ignored, and responses to origins not
+/// allowed get no CORS headers.
+pub fn apply(
+    opt: &crate::Opt,
+    origin: Option<&str>,
+    headers: &mut hyper::HeaderMap,
+) -> Result<(), anyhow::Error> {
+    let names: Vec<hyper::header::HeaderName> = headers
+        .keys()
+        .filter(|name| name.as_str().starts_with("access-control-"))
+        .cloned()
+        .collect();
+    for name in names {
+        headers.remove(name);
+    }
+    if allow_origin(opt, origin, headers)? && !opt.cors_expose_headers.is_empty() {
+        headers.insert(
+            EXPOSE_HEADERS,
+            hyper::header::HeaderValue::from_str(&opt.cors_expose_headers.join(","))?,
+        );
+    }
+    Ok(())
+}
+
+// Set Access-Control-Allow-Origin and Access-Control-Allow-Credentials if the origin is allowed
+fn allow_origin(
+    opt: &crate::Opt,
+    origin: Option<&str>,
+    headers: &mut hyper::HeaderMap,
+) -> Result<bool, anyhow::Error> {
+    let origin = match allowed_origin(opt, origin) {
+        Some(origin) => origin,
+        None => return Ok(false),
+    };
+    if opt.cors_allow_origins.iter().any(|o| o == "*") && !opt.cors_allow_credentials {
+        headers.insert(ALLOW_ORIGIN, hyper::header::HeaderValue::from_static("*"));
+    } else {
+        headers.insert(ALLOW_ORIGIN, hyper::header::HeaderValue::from_str(origin)?);
+        // Caches must not share responses to other origins
+        headers.append(
+            hyper::header::VARY,
+            hyper::header::HeaderValue::from_static("Origin"),
+        );
+    }
+    if opt.cors_allow_credentials {
+        headers.insert(
+            ALLOW_CREDENTIALS,
+            hyper::header::HeaderValue::from_static("true"),
+        );
+    }
+    Ok(true)
+}
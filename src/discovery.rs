@@ -0,0 +1,66 @@
+pub mod cdk;
+pub mod openapi;
+pub mod sam;
+pub mod serverless;
+
+/// Routes and resources of functions found in infrastructure definitions
+#[derive(Debug, Default)]
+pub struct Discovered {
+    pub routes: Vec<crate::route::Route>,
+    pub resources: Vec<crate::resource::Resource>,
+}
+
+impl Discovered {
+    /// Add the route of the function sent to its RIE, declaring the resource for path parameters
+    ///
+    /// Functions are sent to URLs given by `--function-target`, or to `--target-url` otherwise,
+    /// which are filled with the function name if they are templates.
+    fn add(
+        &mut self,
+        targets: &[(String, String)],
+        default_url: &str,
+        function: &str,
+        method: Option<&str>,
+        path: Option<&str>,
+        options: &[(&str, &str)],
+    ) -> Result<(), anyhow::Error> {
+        let target_url = targets
+            .iter()
+            .find(|(name, _)| name == function)
+            .map(|(_, url)| url.as_str())
+            .unwrap_or(default_url);
+        let method = method.unwrap_or("ANY").to_ascii_uppercase();
+        let mut route = match path {
+            Some(path) => {
+                let resource = format!("{} {}", method, path);
+                self.resources.push(resource.parse()?);
+                format!("{}={}", resource, target_url)
+            }
+            // Default routes of HTTP APIs match with every request
+            None => format!("{} /*={}", method, target_url),
+        };
+        route.push_str(&format!(" function=\"{}\"", function));
+        for (k, v) in options {
+            route.push_str(&format!(" {}=\"{}\"", k, v));
+        }
+        log::info!("Discovered route of {}: {}", function, route);
+        self.routes.push(route.parse()?);
+        Ok(())
+    }
+}
+
+// Strings, or Ref of intrinsic functions in either the short form or the full form
+fn ref_name(value: &serde_yaml::Value) -> Option<&str> {
+    match value {
+        serde_yaml::Value::Tagged(tagged) if tagged.tag == "Ref" => tagged.value.as_str(),
+        serde_yaml::Value::Mapping(_) => value.get("Ref")?.as_str(),
+        _ => None,
+    }
+}
+
+fn read_yaml(path: &std::path::Path) -> Result<serde_yaml::Value, anyhow::Error> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("failed to open {}: {}", path.display(), e))?;
+    serde_yaml::from_str(&content)
+        .map_err(|e| anyhow::anyhow!("failed to parse {}: {}", path.display(), e))
+}
@@ -0,0 +1,200 @@
+/// Routes of REST APIs and HTTP APIs of the stacks in the CDK cloud assembly like `cdk.out`
+///
+/// Methods of REST APIs get payload format 1.0 with the stage of their `AWS::ApiGateway::Stage`,
+/// and routes of HTTP APIs get `PayloadFormatVersion` of their integrations. Only Lambda proxy
+/// integrations are added, and functions are named by their construct IDs like `UsersFunction`.
+pub fn discover(
+    path: &std::path::Path,
+    targets: &[(String, String)],
+    default_url: &str,
+) -> Result<super::Discovered, anyhow::Error> {
+    let mut discovered = super::Discovered::default();
+    discover_assembly(path, targets, default_url, &mut discovered)?;
+    Ok(discovered)
+}
+
+fn discover_assembly(
+    dir: &std::path::Path,
+    targets: &[(String, String)],
+    default_url: &str,
+    discovered: &mut super::Discovered,
+) -> Result<(), anyhow::Error> {
+    let manifest = crate::json::read_file(&dir.join("manifest.json").to_string_lossy())?;
+    let artifacts = match manifest.get("artifacts").and_then(|a| a.as_object()) {
+        Some(artifacts) => artifacts,
+        None => return Ok(()),
+    };
+    for artifact in artifacts.values() {
+        let properties = &artifact["properties"];
+        match artifact["type"].as_str() {
+            Some("aws:cloudformation:stack") => {
+                if let Some(template) = properties["templateFile"].as_str() {
+                    let template = crate::json::read_file(&dir.join(template).to_string_lossy())?;
+                    discover_stack(&template, targets, default_url, discovered)?;
+                }
+            }
+            // Stages of CDK apps are nested assemblies
+            Some("cdk:cloud-assembly") => {
+                if let Some(nested) = properties["directoryName"].as_str() {
+                    discover_assembly(&dir.join(nested), targets, default_url, discovered)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn discover_stack(
+    template: &serde_json::Value,
+    targets: &[(String, String)],
+    default_url: &str,
+    discovered: &mut super::Discovered,
+) -> Result<(), anyhow::Error> {
+    let resources = match template["Resources"].as_object() {
+        Some(resources) => resources,
+        None => return Ok(()),
+    };
+    let of_type = |type_: &'static str| {
+        resources
+            .iter()
+            .filter(move |(_, resource)| resource["Type"] == type_)
+            .map(|(id, resource)| (id.as_str(), &resource["Properties"]))
+    };
+
+    for (id, method) in of_type("AWS::ApiGateway::Method") {
+        let integration = &method["Integration"];
+        if integration["Type"] != "AWS_PROXY" {
+            continue;
+        }
+        let function = match function_name(resources, &integration["Uri"]) {
+            Some(function) => function,
+            None => {
+                log::warn!("Ignoring {} since its integration isn't a function", id);
+                continue;
+            }
+        };
+        let path = match resource_path(resources, &method["ResourceId"]) {
+            Some(path) => path,
+            None => continue,
+        };
+        let rest_api = ref_name(&method["RestApiId"]);
+        let stage = of_type("AWS::ApiGateway::Stage")
+            .find(|(_, stage)| ref_name(&stage["RestApiId"]) == rest_api)
+            .and_then(|(_, stage)| stage["StageName"].as_str());
+        let mut options = vec![("payload-format", "1.0")];
+        if let Some(stage) = stage {
+            options.push(("stage", stage));
+        }
+        discovered.add(
+            targets,
+            default_url,
+            function,
+            method["HttpMethod"].as_str(),
+            Some(&path),
+            &options,
+        )?;
+    }
+
+    for (id, route) in of_type("AWS::ApiGatewayV2::Route") {
+        let integration = integration_id(&route["Target"]).and_then(|id| resources.get(id));
+        let integration = match integration {
+            Some(integration) if integration["Properties"]["IntegrationType"] == "AWS_PROXY" => {
+                &integration["Properties"]
+            }
+            _ => continue,
+        };
+        let function = match function_name(resources, &integration["IntegrationUri"]) {
+            Some(function) => function,
+            None => {
+                log::warn!("Ignoring {} since its integration isn't a function", id);
+                continue;
+            }
+        };
+        let (method, path) = match route["RouteKey"].as_str() {
+            Some("$default") => (None, None),
+            Some(route_key) => match route_key.split_once(' ') {
+                Some((method, path)) => (Some(method), Some(path)),
+                None => continue,
+            },
+            None => continue,
+        };
+        discovered.add(
+            targets,
+            default_url,
+            function,
+            method,
+            path,
+            &[(
+                "payload-format",
+                integration["PayloadFormatVersion"]
+                    .as_str()
+                    .unwrap_or("2.0"),
+            )],
+        )?;
+    }
+    Ok(())
+}
+
+// Path of the AWS::ApiGateway::Resource following its parents up to the root resource
+fn resource_path(
+    resources: &serde_json::Map<String, serde_json::Value>,
+    resource_id: &serde_json::Value,
+) -> Option<String> {
+    let id = match ref_name(resource_id) {
+        Some(id) => id,
+        // Fn::GetAtt of RootResourceId
+        None => return Some("/".to_owned()),
+    };
+    let properties = &resources.get(id)?["Properties"];
+    let parent = resource_path(resources, &properties["ParentId"])?;
+    Some(format!(
+        "{}/{}",
+        parent.trim_end_matches('/'),
+        properties["PathPart"].as_str()?
+    ))
+}
+
+// Construct ID of the AWS::Lambda::Function referenced somewhere in the value
+fn function_name<'a>(
+    resources: &'a serde_json::Map<String, serde_json::Value>,
+    value: &'a serde_json::Value,
+) -> Option<&'a str> {
+    let referenced = match value {
+        serde_json::Value::Object(object) => match (object.get("Fn::GetAtt"), object.get("Ref")) {
+            (Some(get_att), _) => get_att[0].as_str(),
+            (_, Some(reference)) => reference.as_str(),
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some((id, resource)) = referenced.and_then(|id| resources.get_key_value(id)) {
+        if resource["Type"] == "AWS::Lambda::Function" {
+            // Paths are like Stack/UsersFunction/Resource
+            let construct_id = resource["Metadata"]["aws:cdk:path"]
+                .as_str()
+                .and_then(|path| path.strip_suffix("/Resource"))
+                .and_then(|path| path.rsplit('/').next());
+            return Some(construct_id.unwrap_or(id));
+        }
+    }
+    match value {
+        serde_json::Value::Array(values) => values.iter().find_map(|v| function_name(resources, v)),
+        serde_json::Value::Object(object) => {
+            object.values().find_map(|v| function_name(resources, v))
+        }
+        _ => None,
+    }
+}
+
+// Integration of targets like {"Fn::Join": ["", ["integrations/", {"Ref": "Integration"}]]}
+fn integration_id(target: &serde_json::Value) -> Option<&str> {
+    target["Fn::Join"][1]
+        .as_array()?
+        .iter()
+        .find_map(|part| ref_name(part))
+}
+
+fn ref_name(value: &serde_json::Value) -> Option<&str> {
+    value["Ref"].as_str()
+}
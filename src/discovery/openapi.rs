@@ -0,0 +1,287 @@
+use std::convert::TryFrom as _;
+
+// Operations of methods other than these are ignored, like x-amazon-apigateway-any-method is ANY
+const METHODS: &[&str] = &[
+    "get",
+    "put",
+    "post",
+    "delete",
+    "options",
+    "head",
+    "patch",
+    "x-amazon-apigateway-any-method",
+];
+
+/// Routes of operations with `x-amazon-apigateway-integration` of Lambda integrations
+///
+/// Operations get `payloadFormatVersion` of their integrations or 1.0, and functions are named
+/// by their ARNs or `${Function.Arn}` references in the integration URIs. Required query string
+/// parameters and headers and the schemas of query string parameters are validated when the
+/// request validator of the operation validates request parameters, and JSON bodies by their
+/// schemas when it validates request bodies. Integrations of type `aws` map events and results by
+/// `requestTemplates` and `responses` of `application/json`.
+pub fn discover(
+    path: &std::path::Path,
+    targets: &[(String, String)],
+    default_url: &str,
+) -> Result<super::Discovered, anyhow::Error> {
+    let document = super::read_yaml(path)?;
+    let paths = document
+        .get("paths")
+        .and_then(|paths| paths.as_mapping())
+        .ok_or_else(|| anyhow::anyhow!("{} has no paths", path.display()))?;
+    let validators = document.get("x-amazon-apigateway-request-validators");
+    let default_validator = document.get("x-amazon-apigateway-request-validator");
+
+    let mut discovered = super::Discovered::default();
+    for (api_path, item) in paths {
+        let api_path = match api_path.as_str() {
+            Some(api_path) => api_path,
+            None => continue,
+        };
+        for method in METHODS {
+            let operation = match item.get(*method) {
+                Some(operation) => operation,
+                None => continue,
+            };
+            let integration = match operation.get("x-amazon-apigateway-integration") {
+                Some(integration) => integration,
+                None => continue,
+            };
+            let integration_type = integration
+                .get("type")
+                .and_then(|t| t.as_str())
+                .map(|t| t.to_ascii_lowercase());
+            let mapping = match integration_type.as_deref() {
+                Some("aws_proxy") => None,
+                Some("aws") => Some(mapping(integration).map_err(|e| {
+                    anyhow::anyhow!("invalid integration of {} {}: {:#}", method, api_path, e)
+                })?),
+                _ => {
+                    log::warn!(
+                        "Ignoring {} {} since its integration isn't aws_proxy or aws",
+                        method,
+                        api_path
+                    );
+                    continue;
+                }
+            };
+            let function = integration
+                .get("uri")
+                .and_then(function_name)
+                .or_else(|| {
+                    operation
+                        .get("operationId")?
+                        .as_str()
+                        .map(|id| id.to_owned())
+                })
+                .unwrap_or_else(|| format!("{} {}", method, api_path));
+            let mut options = vec![(
+                "payload-format",
+                match integration.get("payloadFormatVersion") {
+                    Some(serde_yaml::Value::Number(n)) => {
+                        format!("{:.1}", n.as_f64().unwrap_or(1.0))
+                    }
+                    Some(v) => v.as_str().unwrap_or("1.0").to_owned(),
+                    None => "1.0".to_owned(),
+                },
+            )];
+            let validator = operation
+                .get("x-amazon-apigateway-request-validator")
+                .or(default_validator)
+                .and_then(|name| validators?.get(name.as_str()?));
+            let validates = |name: &str| {
+                validator
+                    .and_then(|validator| validator.get(name)?.as_bool())
+                    .unwrap_or(false)
+            };
+            let mut query_properties = serde_json::Map::new();
+            if validates("validateRequestParameters") {
+                let parameters = item
+                    .get("parameters")
+                    .and_then(|p| p.as_sequence())
+                    .into_iter()
+                    .chain(operation.get("parameters").and_then(|p| p.as_sequence()))
+                    .flatten()
+                    .filter_map(|p| resolve(&document, p));
+                for parameter in parameters {
+                    let name = match parameter.get("name").and_then(|n| n.as_str()) {
+                        Some(name) => name.to_owned(),
+                        None => continue,
+                    };
+                    let location = parameter.get("in").and_then(|i| i.as_str());
+                    if location == Some("query") {
+                        // Parameters of Swagger 2.0 have their types themselves
+                        let schema = parameter.get("schema").unwrap_or(parameter);
+                        if let Some(serde_json::Value::Object(mut schema)) =
+                            inline_refs(&document, schema, 0)
+                        {
+                            for key in ["name", "in", "required", "description"] {
+                                schema.remove(key);
+                            }
+                            query_properties.insert(name.clone(), schema.into());
+                        }
+                    }
+                    if parameter.get("required").and_then(|r| r.as_bool()) != Some(true) {
+                        continue;
+                    }
+                    match location {
+                        Some("query") => options.push(("required-query", name)),
+                        Some("header") => options.push(("required-header", name)),
+                        _ => {}
+                    }
+                }
+            }
+            let options: Vec<(&str, &str)> =
+                options.iter().map(|(k, v)| (*k, v.as_str())).collect();
+            let (method, api_path) = match (*method, api_path) {
+                // $default route of HTTP APIs
+                (_, "$default") => (None, None),
+                ("x-amazon-apigateway-any-method", api_path) => (None, Some(api_path)),
+                (method, api_path) => (Some(method), Some(api_path)),
+            };
+            // Models of JSON bodies in OpenAPI 3.0 or body parameters of Swagger 2.0
+            let body_schema = operation
+                .get("requestBody")
+                .and_then(|body| resolve(&document, body))
+                .and_then(|body| body.get("content")?.get("application/json")?.get("schema"))
+                .or_else(|| {
+                    operation
+                        .get("parameters")?
+                        .as_sequence()?
+                        .iter()
+                        .filter_map(|p| resolve(&document, p))
+                        .find(|p| p.get("in").and_then(|i| i.as_str()) == Some("body"))?
+                        .get("schema")
+                })
+                .filter(|_| validates("validateRequestBody"))
+                .and_then(|schema| inline_refs(&document, schema, 0));
+            discovered.add(targets, default_url, &function, method, api_path, &options)?;
+            if let Some(route) = discovered.routes.last_mut() {
+                if mapping.is_some() {
+                    // Integrations without proxy are of REST APIs
+                    route.payload_format = Some(crate::payload::PayloadFormat::V1);
+                }
+                route.integration = mapping;
+                route.body_schema = body_schema.map(crate::schema::Schema::new);
+                if !query_properties.is_empty() {
+                    route.query_schema = Some(crate::schema::Schema::new(serde_json::json!({
+                        "type": "object",
+                        "properties": query_properties,
+                    })));
+                }
+            }
+        }
+    }
+    Ok(discovered)
+}
+
+// Mapping templates and responses of the Lambda integration without proxy, whose responses are
+// keyed by their selection patterns or default
+fn mapping(
+    integration: &serde_yaml::Value,
+) -> Result<crate::integration::Integration, anyhow::Error> {
+    let template = |templates: Option<&serde_yaml::Value>| {
+        templates
+            .and_then(|templates| templates.get("application/json")?.as_str())
+            .map(|template| template.parse::<crate::vtl::Template>())
+            .transpose()
+    };
+    let mut mapping =
+        crate::integration::Integration::new(template(integration.get("requestTemplates"))?);
+    let responses = integration.get("responses").and_then(|r| r.as_mapping());
+    for (pattern, response) in responses.into_iter().flatten() {
+        let pattern = match pattern.as_str() {
+            Some("default") => None,
+            Some(pattern) => Some(pattern),
+            None => continue,
+        };
+        let status_code = match response.get("statusCode") {
+            Some(serde_yaml::Value::Number(n)) => n.as_u64().and_then(|n| u16::try_from(n).ok()),
+            Some(serde_yaml::Value::String(s)) => s.parse().ok(),
+            _ => None,
+        }
+        .unwrap_or(200);
+        let parameters: Vec<(String, String)> = response
+            .get("responseParameters")
+            .and_then(|p| p.as_mapping())
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, value)| {
+                Some((name.as_str()?.to_owned(), value.as_str()?.to_owned()))
+            })
+            .collect();
+        mapping.add_response(
+            pattern,
+            status_code,
+            template(response.get("responseTemplates"))?,
+            &parameters,
+        )?;
+    }
+    Ok(mapping)
+}
+
+// Follow $ref of local components like #/components/parameters/Id
+fn resolve<'a>(
+    document: &'a serde_yaml::Value,
+    value: &'a serde_yaml::Value,
+) -> Option<&'a serde_yaml::Value> {
+    match value.get("$ref").and_then(|r| r.as_str()) {
+        Some(reference) => reference
+            .strip_prefix("#/")?
+            .split('/')
+            .try_fold(document, |value, key| value.get(key)),
+        None => Some(value),
+    }
+}
+
+// JSON of the schema with local $ref replaced by what they refer to, like models of REST APIs
+// imported from the document
+fn inline_refs(
+    document: &serde_yaml::Value,
+    schema: &serde_yaml::Value,
+    depth: usize,
+) -> Option<serde_json::Value> {
+    // Recursive models can't be inlined
+    if depth > 32 {
+        return None;
+    }
+    match schema {
+        serde_yaml::Value::Mapping(mapping) => {
+            if mapping.contains_key("$ref") {
+                return inline_refs(document, resolve(document, schema)?, depth + 1);
+            }
+            let mut object = serde_json::Map::new();
+            for (key, value) in mapping {
+                if let Some(key) = key.as_str() {
+                    object.insert(key.to_owned(), inline_refs(document, value, depth + 1)?);
+                }
+            }
+            Some(object.into())
+        }
+        serde_yaml::Value::Sequence(values) => values
+            .iter()
+            .map(|value| inline_refs(document, value, depth + 1))
+            .collect::<Option<Vec<_>>>()
+            .map(serde_json::Value::Array),
+        value => serde_json::to_value(value).ok(),
+    }
+}
+
+// Name of the function in the integration URI, which may be given by Fn::Sub
+fn function_name(uri: &serde_yaml::Value) -> Option<String> {
+    let uri = match uri {
+        serde_yaml::Value::Tagged(tagged) if tagged.tag == "Sub" => tagged.value.as_str()?,
+        serde_yaml::Value::Mapping(_) => uri.get("Fn::Sub")?.as_str()?,
+        _ => uri.as_str()?,
+    };
+    let reference = uri
+        .split("${")
+        .skip(1)
+        .find_map(|rest| rest.split_once('}')?.0.strip_suffix(".Arn"));
+    if let Some(name) = reference {
+        return Some(name.to_owned());
+    }
+    let (_, rest) = uri.split_once(":function:")?;
+    rest.split(['/', ':']).next().map(|name| name.to_owned())
+}
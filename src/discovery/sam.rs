@@ -0,0 +1,111 @@
+// Stage of the API implicitly created by SAM for Api events
+const IMPLICIT_API_STAGE: &str = "Prod";
+
+/// Routes of Api and HttpApi events of `AWS::Serverless::Function` in the SAM template
+///
+/// Api events get payload format 1.0 with the stage of their `AWS::Serverless::Api`, and HttpApi
+/// events get `PayloadFormatVersion` or 2.0. Environment variables of functions are logged as
+/// hints for their RIE containers.
+pub fn discover(
+    path: &std::path::Path,
+    targets: &[(String, String)],
+    default_url: &str,
+) -> Result<super::Discovered, anyhow::Error> {
+    let template = super::read_yaml(path)?;
+    let resources = template
+        .get("Resources")
+        .and_then(|resources| resources.as_mapping())
+        .ok_or_else(|| anyhow::anyhow!("{} has no Resources", path.display()))?;
+    let global_variables = template
+        .get("Globals")
+        .and_then(|globals| environment_variables(globals.get("Function")?));
+
+    let mut discovered = super::Discovered::default();
+    for (id, resource) in resources {
+        let id = match id.as_str() {
+            Some(id) => id,
+            None => continue,
+        };
+        if resource.get("Type").and_then(|t| t.as_str()) != Some("AWS::Serverless::Function") {
+            continue;
+        }
+        let properties = match resource.get("Properties") {
+            Some(properties) => properties,
+            None => continue,
+        };
+        let variables: Vec<&str> = global_variables
+            .iter()
+            .chain(&environment_variables(properties))
+            .flatten()
+            .copied()
+            .collect();
+        if !variables.is_empty() {
+            log::info!(
+                "Function {} expects environment variables {}",
+                id,
+                variables.join(", ")
+            );
+        }
+        let events = match properties.get("Events").and_then(|e| e.as_mapping()) {
+            Some(events) => events,
+            None => continue,
+        };
+        for event in events.values() {
+            let event_properties = event.get("Properties");
+            let property = |name: &str| event_properties?.get(name)?.as_str();
+            match event.get("Type").and_then(|t| t.as_str()) {
+                Some("Api") => {
+                    let stage = event_properties
+                        .and_then(|p| super::ref_name(p.get("RestApiId")?))
+                        .map(|api| {
+                            resources
+                                .get(api)
+                                .and_then(|api| api.get("Properties")?.get("StageName")?.as_str())
+                        })
+                        .unwrap_or(Some(IMPLICIT_API_STAGE));
+                    let mut options = vec![("payload-format", "1.0")];
+                    if let Some(stage) = stage {
+                        options.push(("stage", stage));
+                    }
+                    discovered.add(
+                        targets,
+                        default_url,
+                        id,
+                        property("Method"),
+                        property("Path"),
+                        &options,
+                    )?;
+                }
+                Some("HttpApi") => {
+                    let payload_format = event_properties
+                        .and_then(|p| p.get("PayloadFormatVersion"))
+                        .map(|v| match v {
+                            serde_yaml::Value::Number(n) => {
+                                format!("{:.1}", n.as_f64().unwrap_or(2.0))
+                            }
+                            v => v.as_str().unwrap_or("2.0").to_owned(),
+                        })
+                        .unwrap_or_else(|| "2.0".to_owned());
+                    discovered.add(
+                        targets,
+                        default_url,
+                        id,
+                        property("Method"),
+                        property("Path"),
+                        &[("payload-format", &payload_format)],
+                    )?;
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(discovered)
+}
+
+fn environment_variables(properties: &serde_yaml::Value) -> Option<Vec<&str>> {
+    let variables = properties
+        .get("Environment")?
+        .get("Variables")?
+        .as_mapping()?;
+    Some(variables.keys().filter_map(|k| k.as_str()).collect())
+}
@@ -0,0 +1,99 @@
+/// Routes of http and httpApi events of functions in the Serverless Framework configuration
+///
+/// http events get payload format 1.0 with `provider.stage` (default `dev`), and httpApi events
+/// get `provider.httpApi.payload` or 2.0. Environment variables of functions are logged as hints
+/// for their RIE containers.
+pub fn discover(
+    path: &std::path::Path,
+    targets: &[(String, String)],
+    default_url: &str,
+) -> Result<super::Discovered, anyhow::Error> {
+    let config = super::read_yaml(path)?;
+    let functions = config
+        .get("functions")
+        .and_then(|functions| functions.as_mapping())
+        .ok_or_else(|| anyhow::anyhow!("{} has no functions", path.display()))?;
+    let provider = config.get("provider");
+    let stage = provider
+        .and_then(|p| p.get("stage")?.as_str())
+        // Variables like ${opt:stage} are resolved only by the framework
+        .filter(|stage| !stage.contains("${"))
+        .unwrap_or("dev");
+    let http_api_payload = provider
+        .and_then(|p| p.get("httpApi")?.get("payload")?.as_str())
+        .unwrap_or("2.0");
+    let provider_variables = provider.and_then(environment_variables);
+
+    let mut discovered = super::Discovered::default();
+    for (name, function) in functions {
+        let name = match name.as_str() {
+            Some(name) => name,
+            None => continue,
+        };
+        let variables: Vec<&str> = provider_variables
+            .iter()
+            .chain(&environment_variables(function))
+            .flatten()
+            .copied()
+            .collect();
+        if !variables.is_empty() {
+            log::info!(
+                "Function {} expects environment variables {}",
+                name,
+                variables.join(", ")
+            );
+        }
+        let events = match function.get("events").and_then(|e| e.as_sequence()) {
+            Some(events) => events,
+            None => continue,
+        };
+        for event in events {
+            if let Some(http) = event.get("http") {
+                let (method, path) = method_and_path(http);
+                let path = path.map(|path| format!("/{}", path.trim_start_matches('/')));
+                discovered.add(
+                    targets,
+                    default_url,
+                    name,
+                    method,
+                    path.as_deref(),
+                    &[("payload-format", "1.0"), ("stage", stage)],
+                )?;
+            } else if let Some(http_api) = event.get("httpApi") {
+                let (method, path) = match method_and_path(http_api) {
+                    // * is the default route of HTTP APIs
+                    (Some("*"), None) | (_, Some("*")) => (None, None),
+                    (method, path) => (method.filter(|m| *m != "*"), path),
+                };
+                discovered.add(
+                    targets,
+                    default_url,
+                    name,
+                    method,
+                    path,
+                    &[("payload-format", http_api_payload)],
+                )?;
+            }
+        }
+    }
+    Ok(discovered)
+}
+
+// Events are either strings like `GET /users/{id}` or objects with method and path
+fn method_and_path(event: &serde_yaml::Value) -> (Option<&str>, Option<&str>) {
+    match event.as_str() {
+        Some(s) => match s.split_once(' ') {
+            Some((method, path)) => (Some(method), Some(path.trim())),
+            None => (Some(s), None),
+        },
+        None => (
+            event.get("method").and_then(|m| m.as_str()),
+            event.get("path").and_then(|p| p.as_str()),
+        ),
+    }
+}
+
+fn environment_variables(properties: &serde_yaml::Value) -> Option<Vec<&str>> {
+    let variables = properties.get("environment")?.as_mapping()?;
+    Some(variables.keys().filter_map(|k| k.as_str()).collect())
+}
@@ -0,0 +1,141 @@
+// Label of containers with the route pattern like /users/*, or labels prefixed by it for more routes
+const ROUTE_LABEL: &str = "rie-gateway.route";
+// Label of route options like payload-format=1.0 applied to the routes of the container
+const OPTIONS_LABEL: &str = "rie-gateway.options";
+// Label of the port RIE listens in the container
+const PORT_LABEL: &str = "rie-gateway.port";
+const DEFAULT_PORT: u16 = 8080;
+
+/// Routes of running containers labeled with `rie-gateway.route`
+///
+/// Containers are reached by the host port published for the RIE port, or their IP addresses in
+/// the network if given or if the port isn't published. Functions are named by the services of
+/// Docker Compose, or by the container names.
+pub async fn routes(host: &str, network: Option<&str>) -> Result<Vec<String>, anyhow::Error> {
+    let containers = get(host, "/containers/json").await?;
+    let mut containers: Vec<&serde_json::Value> = containers
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("unexpected response of Docker: {}", containers))?
+        .iter()
+        .collect();
+    containers.sort_by_key(|container| container["Names"][0].as_str());
+
+    let mut routes = Vec::new();
+    for container in containers {
+        let labels = match container["Labels"].as_object() {
+            Some(labels) => labels,
+            None => continue,
+        };
+        let name = container["Names"][0]
+            .as_str()
+            .unwrap_or_default()
+            .trim_start_matches('/');
+        let function = labels
+            .get("com.docker.compose.service")
+            .and_then(|service| service.as_str())
+            .unwrap_or(name);
+        let patterns: Vec<&str> = labels
+            .iter()
+            .filter(|(key, _)| {
+                key.as_str() == ROUTE_LABEL
+                    || key
+                        .strip_prefix(ROUTE_LABEL)
+                        .is_some_and(|rest| rest.starts_with('.'))
+            })
+            .filter_map(|(_, pattern)| pattern.as_str())
+            .collect();
+        if patterns.is_empty() {
+            continue;
+        }
+        let port = match labels.get(PORT_LABEL).and_then(|port| port.as_str()) {
+            Some(port) => port
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid {} of {}: {}", PORT_LABEL, name, e))?,
+            None => DEFAULT_PORT,
+        };
+        let target_url = match target_url(container, port, network) {
+            Some(url) => url,
+            None => {
+                log::warn!("Ignoring container {} since it isn't reachable", name);
+                continue;
+            }
+        };
+        let options = labels
+            .get(OPTIONS_LABEL)
+            .and_then(|options| options.as_str())
+            .unwrap_or_default();
+        for pattern in patterns {
+            routes.push(
+                format!(
+                    "{}={} function=\"{}\" {}",
+                    pattern, target_url, function, options
+                )
+                .trim_end()
+                .to_owned(),
+            );
+        }
+    }
+    Ok(routes)
+}
+
+fn target_url(container: &serde_json::Value, port: u16, network: Option<&str>) -> Option<String> {
+    let networks = container["NetworkSettings"]["Networks"].as_object()?;
+    let address = |network: &serde_json::Value| {
+        network["IPAddress"]
+            .as_str()
+            .filter(|ip| !ip.is_empty())
+            .map(|ip| format!("http://{}:{}", ip, port))
+    };
+    if let Some(network) = network {
+        return address(networks.get(network)?);
+    }
+    let published = container["Ports"].as_array().and_then(|ports| {
+        ports.iter().find_map(|p| {
+            if p["PrivatePort"] != port || p["Type"] != "tcp" {
+                return None;
+            }
+            let public_port = p["PublicPort"].as_u64()?;
+            let ip = match p["IP"].as_str() {
+                None | Some("0.0.0.0") | Some("::") | Some("") => "127.0.0.1",
+                Some(ip) => ip,
+            };
+            Some(if ip.contains(':') {
+                format!("http://[{}]:{}", ip, public_port)
+            } else {
+                format!("http://{}:{}", ip, public_port)
+            })
+        })
+    });
+    published.or_else(|| networks.values().find_map(address))
+}
+
+// GET the path of Docker Engine API listening the Unix socket of the host like unix:///var/run/docker.sock
+async fn get(host: &str, path: &str) -> Result<serde_json::Value, anyhow::Error> {
+    let socket = host
+        .strip_prefix("unix://")
+        .ok_or_else(|| anyhow::anyhow!("only unix:// Docker hosts are supported: {}", host))?;
+    let stream = tokio::net::UnixStream::connect(socket)
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to connect to {}: {}", socket, e))?;
+    let (mut sender, connection) = hyper::client::conn::handshake(stream).await?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            log::debug!("Connection to Docker failed: {}", e);
+        }
+    });
+    let request = hyper::Request::get(path)
+        .header(hyper::header::HOST, "docker")
+        .body(hyper::Body::empty())?;
+    let response = sender.send_request(request).await?;
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    if !status.is_success() {
+        anyhow::bail!(
+            "Docker responded {} to {}: {}",
+            status,
+            path,
+            String::from_utf8_lossy(&body)
+        );
+    }
+    Ok(serde_json::from_slice(&body)?)
+}
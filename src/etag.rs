@@ -0,0 +1,65 @@
+use std::hash::Hasher as _;
+
+/// ETags of the last successful responses per host and URI, answering `If-None-Match` without
+/// invocations
+#[derive(Debug, Default)]
+pub struct Store {
+    etags: std::sync::Mutex<std::collections::HashMap<String, String>>,
+}
+
+impl Store {
+    pub fn lookup(&self, url: &str) -> Option<String> {
+        self.etags.lock().unwrap().get(url).cloned()
+    }
+
+    /// Record the ETag of the response, adding a weak one computed from the body when missing
+    pub async fn tag(
+        &self,
+        url: &str,
+        response: hyper::Response<hyper::Body>,
+    ) -> Result<(hyper::Response<hyper::Body>, String), anyhow::Error> {
+        let (mut parts, body) = response.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        let etag = match parts
+            .headers
+            .get(hyper::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+        {
+            Some(etag) => etag.to_owned(),
+            None => {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                hasher.write(&body);
+                let etag = format!("W/\"{:016x}\"", hasher.finish());
+                parts.headers.insert(
+                    hyper::header::ETAG,
+                    hyper::header::HeaderValue::from_str(&etag)?,
+                );
+                etag
+            }
+        };
+        self.etags
+            .lock()
+            .unwrap()
+            .insert(url.to_owned(), etag.clone());
+        Ok((
+            hyper::Response::from_parts(parts, hyper::Body::from(body)),
+            etag,
+        ))
+    }
+}
+
+/// Whether `If-None-Match` matches the ETag, comparing weakly like RFC 7232 does
+pub fn matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_owned();
+    let etag = opaque(etag);
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == etag)
+}
+
+pub fn not_modified(etag: &str) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    Ok(hyper::Response::builder()
+        .status(hyper::StatusCode::NOT_MODIFIED)
+        .header(hyper::header::ETAG, etag)
+        .body(hyper::Body::empty())?)
+}
@@ -0,0 +1,63 @@
+/// Forward the request verbatim to a plain HTTP upstream like a frontend dev server
+///
+/// Only hop-by-hop headers are dropped, `Host` becomes the one of the upstream with the original
+/// one in `X-Forwarded-Host`, and redirects are returned to clients instead of being followed.
+pub async fn forward(
+    upstream_url: &str,
+    remote_addr: std::net::SocketAddr,
+    request: hyper::Request<hyper::Body>,
+) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    let (parts, body) = request.into_parts();
+    let url = format!(
+        "{}{}",
+        upstream_url,
+        parts
+            .uri
+            .path_and_query()
+            .map(|p| p.as_str())
+            .unwrap_or("/")
+    );
+    log::info!("Forward {} {} to {}", parts.method, parts.uri, url);
+
+    let mut headers = parts.headers;
+    strip_hop_by_hop_headers(&mut headers);
+    if let Some(host) = headers.remove(hyper::header::HOST) {
+        headers.insert("x-forwarded-host", host);
+    }
+    headers.append(
+        "x-forwarded-for",
+        hyper::header::HeaderValue::from_str(&remote_addr.ip().to_string())?,
+    );
+    let resp = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?
+        .request(parts.method, url)
+        .headers(headers)
+        .body(hyper::body::to_bytes(body).await?)
+        .send()
+        .await?;
+
+    let status = resp.status();
+    let mut headers = resp.headers().clone();
+    strip_hop_by_hop_headers(&mut headers);
+    let mut response = hyper::Response::new(hyper::Body::from(resp.bytes().await?));
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    Ok(response)
+}
+
+fn strip_hop_by_hop_headers(headers: &mut hyper::HeaderMap) {
+    let connection_values: Vec<String> = headers
+        .get_all(hyper::header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .map(|v| v.to_owned())
+        .collect();
+    for name in
+        crate::header_filter::hop_by_hop_headers(connection_values.iter().map(|v| v.as_str()))
+    {
+        headers.remove(name.as_str());
+    }
+    // Bodies are sent with their lengths again
+    headers.remove(hyper::header::CONTENT_LENGTH);
+}
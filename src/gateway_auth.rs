@@ -0,0 +1,71 @@
+// Header of the gateway token, which leaves Authorization to the emulated API
+static TOKEN_HEADER: hyper::header::HeaderName =
+    hyper::header::HeaderName::from_static("x-gateway-token");
+
+/// Credentials of basic authentication in USER:PASSWORD form
+pub fn parse_basic_auth(s: &str) -> Result<String, anyhow::Error> {
+    if !s.contains(':') {
+        anyhow::bail!("basic authentication must be in USER:PASSWORD form");
+    }
+    Ok(s.to_owned())
+}
+
+pub fn is_enabled(opt: &crate::Opt) -> bool {
+    opt.gateway_basic_auth.is_some() || opt.gateway_token.is_some()
+}
+
+/// Whether the request has credentials of the gateway itself, which are removed so that the
+/// emulated API and functions don't see them
+///
+/// The token is accepted in `X-Gateway-Token` or as `Authorization: Bearer TOKEN`, and the basic
+/// authentication in `Authorization`.
+pub fn authenticate<B>(opt: &crate::Opt, request: &mut hyper::Request<B>) -> bool {
+    let headers = request.headers_mut();
+    let given_token = headers
+        .remove(&TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok().map(|v| v.to_owned()));
+    if let (Some(token), Some(given)) = (&opt.gateway_token, &given_token) {
+        if equals(token, given) {
+            return true;
+        }
+    }
+    let authorization = headers
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok());
+    let matches = match authorization.and_then(|v| v.split_once(' ')) {
+        Some((scheme, credentials)) if scheme.eq_ignore_ascii_case("basic") => {
+            opt.gateway_basic_auth.as_ref().is_some_and(|expected| {
+                base64::decode(credentials.trim())
+                    .is_ok_and(|decoded| equals(expected.as_bytes(), &decoded))
+            })
+        }
+        Some((scheme, credentials)) if scheme.eq_ignore_ascii_case("bearer") => opt
+            .gateway_token
+            .as_ref()
+            .is_some_and(|token| equals(token, credentials.trim())),
+        _ => false,
+    };
+    if matches {
+        headers.remove(hyper::header::AUTHORIZATION);
+    }
+    matches
+}
+
+/// 401 asking for the credentials, which lets browsers prompt for basic authentication
+pub fn challenge(opt: &crate::Opt) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    let scheme = if opt.gateway_basic_auth.is_some() {
+        r#"Basic realm="aws-lambda-rie-gateway""#
+    } else {
+        "Bearer"
+    };
+    Ok(hyper::Response::builder()
+        .status(hyper::StatusCode::UNAUTHORIZED)
+        .header(hyper::header::WWW_AUTHENTICATE, scheme)
+        .body(hyper::Body::from("Unauthorized"))?)
+}
+
+// Compare every byte so that the time doesn't tell the matching prefix
+fn equals<A: AsRef<[u8]>, B: AsRef<[u8]>>(a: A, b: B) -> bool {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
@@ -0,0 +1,88 @@
+// Response types of API Gateway generated by the gateway
+pub const MISSING_AUTHENTICATION_TOKEN: &str = "MISSING_AUTHENTICATION_TOKEN";
+pub const REQUEST_TOO_LARGE: &str = "REQUEST_TOO_LARGE";
+pub const INTEGRATION_TIMEOUT: &str = "INTEGRATION_TIMEOUT";
+pub const BAD_REQUEST_PARAMETERS: &str = "BAD_REQUEST_PARAMETERS";
+pub const BAD_REQUEST_BODY: &str = "BAD_REQUEST_BODY";
+pub const UNAUTHORIZED: &str = "UNAUTHORIZED";
+pub const ACCESS_DENIED: &str = "ACCESS_DENIED";
+pub const INVALID_SIGNATURE: &str = "INVALID_SIGNATURE";
+pub const INVALID_API_KEY: &str = "INVALID_API_KEY";
+pub const THROTTLED: &str = "THROTTLED";
+pub const QUOTA_EXCEEDED: &str = "QUOTA_EXCEEDED";
+pub const WAF_FILTERED: &str = "WAF_FILTERED";
+
+/// Customized responses of errors generated by the gateway, like gateway responses of REST APIs
+///
+/// Keys are response types like `INTEGRATION_TIMEOUT`, and `DEFAULT_4XX` and `DEFAULT_5XX` apply
+/// to types without their own entries.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct GatewayResponses(std::collections::HashMap<String, GatewayResponse>);
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct GatewayResponse {
+    status_code: Option<u16>,
+    #[serde(default)]
+    headers: std::collections::HashMap<String, String>,
+    // Template where $context.error.message, $context.error.messageString,
+    // $context.error.responseType, $context.requestId and $context.extendedRequestId are replaced
+    body: Option<String>,
+}
+
+impl GatewayResponses {
+    pub fn read_file(path: &str) -> Result<Self, anyhow::Error> {
+        serde_json::from_value(crate::json::read_file(path)?)
+            .map_err(|e| anyhow::anyhow!("invalid gateway responses in {}: {}", path, e))
+    }
+
+    /// Customize the default response of the response type, if configured
+    pub fn apply(
+        &self,
+        response_type: Option<&str>,
+        mut response: crate::payload::LambdaResponse,
+        request_id: &crate::request_id::RequestId,
+    ) -> crate::payload::LambdaResponse {
+        let default_type = if response.status_code >= 500 {
+            "DEFAULT_5XX"
+        } else {
+            "DEFAULT_4XX"
+        };
+        let response_type = response_type.unwrap_or(default_type);
+        let custom = match self
+            .0
+            .get(response_type)
+            .or_else(|| self.0.get(default_type))
+        {
+            Some(custom) => custom,
+            None => return response,
+        };
+
+        let message = serde_json::from_str::<serde_json::Value>(&response.body)
+            .ok()
+            .and_then(|body| body.get("message")?.as_str().map(|m| m.to_owned()))
+            .unwrap_or_default();
+        if let Some(status_code) = custom.status_code {
+            response.status_code = status_code;
+        }
+        for (k, v) in &custom.headers {
+            response
+                .headers
+                .retain(|name, _| !name.eq_ignore_ascii_case(k));
+            response.headers.insert(k.clone(), v.clone());
+        }
+        if let Some(body) = &custom.body {
+            response.body = body
+                .replace(
+                    "$context.error.messageString",
+                    &serde_json::Value::String(message.clone()).to_string(),
+                )
+                .replace("$context.error.message", &message)
+                .replace("$context.error.responseType", response_type)
+                .replace("$context.requestId", &request_id.uuid)
+                .replace("$context.extendedRequestId", &request_id.extended);
+            response.is_base64_encoded = false;
+        }
+        response
+    }
+}
@@ -0,0 +1,64 @@
+/// Whether a response header passes `--response-header-allow` and `--response-header-deny`
+///
+/// Patterns are case-insensitive and `*` matches any characters. Headers are allowed when they
+/// match one of `allow` (or `allow` is empty) and none of `deny`.
+pub fn is_allowed(allow: &[String], deny: &[String], name: &str) -> bool {
+    (allow.is_empty() || allow.iter().any(|p| glob_match(p, name)))
+        && !deny.iter().any(|p| glob_match(p, name))
+}
+
+/// Case-insensitive match where `*` in the pattern matches any characters
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let name = name.to_ascii_lowercase();
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match name.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts: Vec<&str> = parts.collect();
+    match parts.split_last() {
+        // No wildcards
+        None => rest.is_empty(),
+        Some((last, middle)) => {
+            for part in middle {
+                match rest.find(part) {
+                    Some(i) => rest = &rest[i + part.len()..],
+                    None => return false,
+                }
+            }
+            rest.len() >= last.len() && rest.ends_with(last)
+        }
+    }
+}
+
+// Headers meaningful only for a single connection, which proxies must not forward
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Lowercase names of hop-by-hop headers, including ones listed in `Connection` header values
+pub fn hop_by_hop_headers<'a, I>(connection_values: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut names: Vec<String> = HOP_BY_HOP_HEADERS.iter().map(|h| (*h).to_owned()).collect();
+    for value in connection_values {
+        names.extend(
+            value
+                .split(',')
+                .map(|name| name.trim().to_ascii_lowercase())
+                .filter(|name| !name.is_empty()),
+        );
+    }
+    names
+}
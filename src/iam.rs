@@ -0,0 +1,368 @@
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+// Signatures older or newer than this are rejected like AWS
+const MAX_SKEW_SECS: i64 = 300;
+
+// Characters kept as they are by URI encoding of SigV4
+const UNRESERVED: &percent_encoding::AsciiSet = &percent_encoding::NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+const PATH_UNRESERVED: &percent_encoding::AsciiSet = &UNRESERVED.remove(b'/');
+
+/// Dummy credential of IAM users like `AKIDEXAMPLE=SECRET`, optionally followed by `=ARN` of
+/// the user
+#[derive(Debug, Clone)]
+pub struct Credential {
+    access_key_id: String,
+    secret_access_key: String,
+    user_arn: Option<String>,
+}
+impl std::str::FromStr for Credential {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, '=');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(access_key_id), Some(secret_access_key), user_arn)
+                if !access_key_id.is_empty() && !secret_access_key.is_empty() =>
+            {
+                Ok(Self {
+                    access_key_id: access_key_id.to_owned(),
+                    secret_access_key: secret_access_key.to_owned(),
+                    user_arn: user_arn.map(|arn| arn.to_owned()),
+                })
+            }
+            _ => Err(anyhow::anyhow!(
+                "credential must be in ACCESS_KEY_ID=SECRET_ACCESS_KEY[=ARN] form"
+            )),
+        }
+    }
+}
+
+/// Reason of requests rejected with 403
+#[derive(Debug)]
+pub enum Rejection {
+    // No Authorization header of SigV4
+    MissingToken,
+    InvalidSignature(String),
+}
+
+/// Credential whose signature of the `Authorization` header is valid
+///
+/// Query strings of presigned URLs aren't supported, and bodies are hashed unless
+/// `X-Amz-Content-Sha256` gives their hashes.
+pub fn verify<'a>(
+    credentials: &'a [Credential],
+    request: &crate::payload::Request,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<&'a Credential, Rejection> {
+    let invalid = |message: &str| Rejection::InvalidSignature(message.to_owned());
+    let authorization = request
+        .header(&hyper::header::AUTHORIZATION)
+        .ok_or(Rejection::MissingToken)?;
+    let fields = authorization
+        .strip_prefix(ALGORITHM)
+        .ok_or_else(|| invalid("Authorization header requires AWS4-HMAC-SHA256"))?;
+    let field = |name: &str| {
+        fields
+            .split(',')
+            .find_map(|field| field.trim().strip_prefix(name)?.strip_prefix('='))
+            .ok_or_else(|| {
+                invalid(&format!(
+                    "Authorization header requires '{}' parameter",
+                    name
+                ))
+            })
+    };
+    let (scope, signed_headers, signature) = (
+        field("Credential")?,
+        field("SignedHeaders")?,
+        field("Signature")?,
+    );
+    let (access_key_id, scope) = scope
+        .split_once('/')
+        .ok_or_else(|| invalid("Credential is malformed"))?;
+    let credential = credentials
+        .iter()
+        .find(|c| c.access_key_id == access_key_id)
+        .ok_or_else(|| invalid("The security token included in the request is invalid"))?;
+    let scope_parts: Vec<&str> = scope.split('/').collect();
+    let (date, region, service) = match scope_parts.as_slice() {
+        [date, region, service, "aws4_request"] => (*date, *region, *service),
+        _ => return Err(invalid("Credential should be scoped to a valid region")),
+    };
+    let amz_date = request
+        .header(&hyper::header::HeaderName::from_static("x-amz-date"))
+        .ok_or_else(|| invalid("Authorization header requires existence of X-Amz-Date"))?;
+    let time = chrono::NaiveDateTime::parse_from_str(amz_date, "%Y%m%dT%H%M%SZ")
+        .map_err(|_| invalid("X-Amz-Date is malformed"))?
+        .and_utc();
+    if (now - time).num_seconds().abs() > MAX_SKEW_SECS || !amz_date.starts_with(date) {
+        return Err(invalid("Signature expired"));
+    }
+
+    let mut headers = Vec::new();
+    for name in signed_headers.split(';') {
+        let values: Vec<String> = request
+            .headers
+            .iter()
+            .filter(|(k, _)| k == name)
+            .map(|(_, v)| v.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect();
+        headers.push(format!("{}:{}\n", name, values.join(",")));
+    }
+    let payload_hash = match request.header(&hyper::header::HeaderName::from_static(
+        "x-amz-content-sha256",
+    )) {
+        Some(hash) => hash.to_owned(),
+        None => hex(ring::digest::digest(&ring::digest::SHA256, &request.body).as_ref()),
+    };
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        request.parts.method,
+        percent_encoding::utf8_percent_encode(request.parts.uri.path(), PATH_UNRESERVED),
+        canonical_query(request.parts.uri.query().unwrap_or_default()),
+        headers.concat(),
+        signed_headers,
+        payload_hash
+    );
+    let string_to_sign = format!(
+        "{}\n{}\n{}\n{}",
+        ALGORITHM,
+        amz_date,
+        scope,
+        hex(ring::digest::digest(&ring::digest::SHA256, canonical_request.as_bytes()).as_ref())
+    );
+    let mut key = format!("AWS4{}", credential.secret_access_key).into_bytes();
+    for part in [date, region, service, "aws4_request"] {
+        key = hmac(&key, part.as_bytes());
+    }
+    let expected = hex(&hmac(&key, string_to_sign.as_bytes()));
+    // Compare every byte so that the time doesn't tell the matching prefix
+    let matched = expected.len() == signature.len()
+        && expected
+            .bytes()
+            .zip(signature.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0;
+    if !matched {
+        log::debug!("Canonical request of SigV4:\n{}", canonical_request);
+        return Err(invalid("The request signature we calculated does not match the signature you provided. Check your AWS Secret Access Key and signing method."));
+    }
+    Ok(credential)
+}
+
+// Parameters sorted by their names and values, which are encoded again
+fn canonical_query(query: &str) -> String {
+    let mut pairs: Vec<(String, String)> = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let encode = |s: &str| {
+                let decoded = percent_encoding::percent_decode_str(&s.replace('+', " "))
+                    .decode_utf8_lossy()
+                    .into_owned();
+                percent_encoding::utf8_percent_encode(&decoded, UNRESERVED).to_string()
+            };
+            (encode(name), encode(value))
+        })
+        .collect();
+    pairs.sort();
+    pairs
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, key);
+    ring::hmac::sign(&key, data).as_ref().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl Credential {
+    /// Fields of `requestContext` telling the caller, which are `identity` of REST APIs and
+    /// `authorizer.iam` of HTTP APIs
+    pub fn context(
+        &self,
+        payload_format: crate::payload::PayloadFormat,
+        account_id: &str,
+    ) -> Option<serde_json::Value> {
+        let user_arn = self
+            .user_arn
+            .clone()
+            .unwrap_or_else(|| format!("arn:aws:iam::{}:user/{}", account_id, self.access_key_id));
+        match payload_format {
+            crate::payload::PayloadFormat::V1 => Some(serde_json::json!({
+                "identity": {
+                    "accessKey": self.access_key_id,
+                    "accountId": account_id,
+                    "caller": self.access_key_id,
+                    "user": self.access_key_id,
+                    "userArn": user_arn,
+                },
+            })),
+            crate::payload::PayloadFormat::V2 => Some(serde_json::json!({
+                "authorizer": {
+                    "iam": {
+                        "accessKey": self.access_key_id,
+                        "accountId": account_id,
+                        "callerId": self.access_key_id,
+                        "cognitoIdentity": null,
+                        "principalOrgId": null,
+                        "userArn": user_arn,
+                        "userId": self.access_key_id,
+                    },
+                },
+            })),
+            crate::payload::PayloadFormat::AppSync => Some(serde_json::json!({
+                "accountId": account_id,
+                "userArn": user_arn,
+                "username": self.access_key_id,
+                "cognitoIdentityId": null,
+                "cognitoIdentityPoolId": null,
+            })),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Vectors of the AWS Signature Version 4 test suite
+    const CREDENTIAL: &str = "AKIDEXAMPLE=wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+    const SCOPE: &str = "AKIDEXAMPLE/20150830/us-east-1/service/aws4_request";
+
+    fn request(
+        method: hyper::Method,
+        uri: &str,
+        headers: &[(&str, &str)],
+        signed_headers: &str,
+        signature: &str,
+        body: &str,
+    ) -> crate::payload::Request {
+        let (parts, ()) = hyper::Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(())
+            .unwrap()
+            .into_parts();
+        let mut headers: Vec<(String, String)> = headers
+            .iter()
+            .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+            .collect();
+        headers.push(("host".to_owned(), "example.amazonaws.com".to_owned()));
+        headers.push(("x-amz-date".to_owned(), "20150830T123600Z".to_owned()));
+        headers.push((
+            "authorization".to_owned(),
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}, SignedHeaders={}, Signature={}",
+                SCOPE, signed_headers, signature
+            ),
+        ));
+        crate::payload::Request {
+            parts,
+            headers,
+            body: bytes::Bytes::copy_from_slice(body.as_bytes()),
+        }
+    }
+
+    fn verify(request: &crate::payload::Request, now: &str) -> Result<String, super::Rejection> {
+        let credentials = [CREDENTIAL.parse().unwrap()];
+        let now = chrono::DateTime::parse_from_rfc3339(now).unwrap().into();
+        super::verify(&credentials, request, now).map(|c| c.access_key_id.clone())
+    }
+
+    fn signed(method: hyper::Method, uri: &str, signature: &str) -> crate::payload::Request {
+        request(method, uri, &[], "host;x-amz-date", signature, "")
+    }
+
+    #[test]
+    fn get_vanilla() {
+        let request = signed(
+            hyper::Method::GET,
+            "/",
+            "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31",
+        );
+        assert_eq!(
+            verify(&request, "2015-08-30T12:36:00Z").unwrap(),
+            "AKIDEXAMPLE"
+        );
+    }
+
+    #[test]
+    fn get_vanilla_query_order_key_case() {
+        let request = signed(
+            hyper::Method::GET,
+            "/?Param2=value2&Param1=value1",
+            "b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500",
+        );
+        assert!(verify(&request, "2015-08-30T12:36:00Z").is_ok());
+    }
+
+    #[test]
+    fn get_vanilla_query_unreserved() {
+        let unreserved = "-._~0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+        let request = signed(
+            hyper::Method::GET,
+            &format!("/?{}={}", unreserved, unreserved),
+            "9c3e54bfcdf0b19771a7f523ee5669cdf59bc7cc0884027167c21bb143a40197",
+        );
+        assert!(verify(&request, "2015-08-30T12:36:00Z").is_ok());
+    }
+
+    #[test]
+    fn post_x_www_form_urlencoded() {
+        let request = request(
+            hyper::Method::POST,
+            "/",
+            &[("content-type", "application/x-www-form-urlencoded")],
+            "content-type;host;x-amz-date",
+            "ff11897932ad3f4e8b18135d722051e5ac45fc38421b1da7b9d196a0fe09473a",
+            "Param1=value1",
+        );
+        assert!(verify(&request, "2015-08-30T12:36:00Z").is_ok());
+    }
+
+    #[test]
+    fn signature_of_other_request_is_invalid() {
+        let request = signed(
+            hyper::Method::GET,
+            "/?Param1=value1",
+            "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31",
+        );
+        assert!(matches!(
+            verify(&request, "2015-08-30T12:36:00Z"),
+            Err(super::Rejection::InvalidSignature(_))
+        ));
+    }
+
+    #[test]
+    fn old_signature_is_expired() {
+        let request = signed(
+            hyper::Method::GET,
+            "/",
+            "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31",
+        );
+        match verify(&request, "2015-08-30T12:41:01Z") {
+            Err(super::Rejection::InvalidSignature(message)) => {
+                assert_eq!(message, "Signature expired")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn canonical_query_sorts_and_encodes_parameters() {
+        assert_eq!(
+            super::canonical_query("b=2&a=%7e1&a%20b=c+d&flag"),
+            "a=~1&a%20b=c%20d&b=2&flag="
+        );
+    }
+}
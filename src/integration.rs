@@ -0,0 +1,179 @@
+/// Lambda integration of REST APIs without proxy, whose mapping templates transform requests
+/// into events and results of functions into responses
+///
+/// Templates see the request of the REST API event built for the route, like `$context` of its
+/// requestContext. Without the request template, bodies are passed through as events, and
+/// results are passed through as bodies without response templates.
+#[derive(Debug, Clone)]
+pub struct Integration {
+    request_template: Option<crate::vtl::Template>,
+    // Responses selected by errorMessage of function errors, falling back to the default one
+    responses: Vec<Response>,
+}
+
+#[derive(Debug, Clone)]
+struct Response {
+    // Regex matching with the whole errorMessage, or None of the default response
+    selection_pattern: Option<regex::Regex>,
+    status_code: u16,
+    template: Option<crate::vtl::Template>,
+    headers: Vec<(String, HeaderValue)>,
+}
+
+// Response parameters mapped to headers
+#[derive(Debug, Clone)]
+enum HeaderValue {
+    // 'value'
+    Static(String),
+    // integration.response.body or integration.response.body.JSONPATH
+    Body(String),
+}
+
+impl Integration {
+    pub fn new(request_template: Option<crate::vtl::Template>) -> Self {
+        Self {
+            request_template,
+            responses: Vec::new(),
+        }
+    }
+
+    /// Add the response of the selection pattern, or the default response without the pattern,
+    /// whose headers are given by response parameters like `method.response.header.NAME` of
+    /// `'value'` or `integration.response.body.NAME`
+    pub fn add_response(
+        &mut self,
+        selection_pattern: Option<&str>,
+        status_code: u16,
+        template: Option<crate::vtl::Template>,
+        parameters: &[(String, String)],
+    ) -> Result<(), anyhow::Error> {
+        let selection_pattern = selection_pattern
+            .map(|pattern| regex::Regex::new(&format!("^(?s:{})$", pattern)))
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("invalid selection pattern: {}", e))?;
+        let mut headers = Vec::new();
+        for (name, value) in parameters {
+            let name = match name.strip_prefix("method.response.header.") {
+                Some(name) => name,
+                None => {
+                    log::warn!(
+                        "Ignoring response parameter {} of an unknown location",
+                        name
+                    );
+                    continue;
+                }
+            };
+            let value =
+                if let Some(value) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+                    HeaderValue::Static(value.to_owned())
+                } else if value == "integration.response.body" {
+                    HeaderValue::Body("$".to_owned())
+                } else if let Some(path) = value.strip_prefix("integration.response.body.") {
+                    HeaderValue::Body(format!("$.{}", path))
+                } else {
+                    log::warn!("Ignoring response parameter {} mapped from {}", name, value);
+                    continue;
+                };
+            headers.push((name.to_owned(), value));
+        }
+        self.responses.push(Response {
+            selection_pattern,
+            status_code,
+            template,
+            headers,
+        });
+        Ok(())
+    }
+
+    /// Payload of the invocation mapped from the REST API event
+    pub fn request(&self, event: &serde_json::Value) -> Result<bytes::Bytes, anyhow::Error> {
+        let body = request_body(event)?;
+        match &self.request_template {
+            Some(template) => Ok(template
+                .render(context(event, String::from_utf8_lossy(&body).into_owned()))?
+                .into()),
+            None => Ok(body.into()),
+        }
+    }
+
+    /// Response mapped from the result of the function, or its error like API Gateway does
+    pub fn response(
+        &self,
+        event: &serde_json::Value,
+        function_error: Option<&crate::payload::FunctionError>,
+        result: &[u8],
+    ) -> Result<crate::payload::LambdaResponse, anyhow::Error> {
+        // Successful results always get the default response
+        let selected = function_error.and_then(|e| {
+            self.responses.iter().find(|response| {
+                response
+                    .selection_pattern
+                    .as_ref()
+                    .is_some_and(|pattern| pattern.is_match(&e.error_message))
+            })
+        });
+        let default = self
+            .responses
+            .iter()
+            .find(|response| response.selection_pattern.is_none());
+        let result = String::from_utf8_lossy(result).into_owned();
+        let response = match selected.or(default) {
+            Some(response) => response,
+            None => return Ok(crate::payload::LambdaResponse::json(200, result)),
+        };
+        let body = match &response.template {
+            Some(template) => template.render(context(event, result.clone()))?,
+            None => result.clone(),
+        };
+        let mut lambda_response = crate::payload::LambdaResponse::json(response.status_code, body);
+        let json = serde_json::from_str(&result).ok();
+        for (name, value) in &response.headers {
+            let value = match value {
+                HeaderValue::Static(value) => value.clone(),
+                HeaderValue::Body(path) => match crate::vtl::json_path(json.as_ref(), path) {
+                    Some(serde_json::Value::String(s)) => s,
+                    Some(value) => value.to_string(),
+                    None => continue,
+                },
+            };
+            lambda_response.headers.insert(name.clone(), value);
+        }
+        Ok(lambda_response)
+    }
+}
+
+// Raw body of the REST API event
+fn request_body(event: &serde_json::Value) -> Result<Vec<u8>, anyhow::Error> {
+    let body = event["body"].as_str().unwrap_or_default();
+    if event["isBase64Encoded"].as_bool().unwrap_or(false) {
+        Ok(base64::decode(body)?)
+    } else {
+        Ok(body.as_bytes().to_vec())
+    }
+}
+
+// Variables of templates from the REST API event, whose $input is the body
+fn context(event: &serde_json::Value, body: String) -> crate::vtl::Context {
+    let object = |value: &serde_json::Value| {
+        if value.is_object() {
+            value.clone()
+        } else {
+            serde_json::json!({})
+        }
+    };
+    let mut variables = std::collections::HashMap::new();
+    variables.insert("context".to_owned(), object(&event["requestContext"]));
+    variables.insert(
+        "stageVariables".to_owned(),
+        object(&event["stageVariables"]),
+    );
+    crate::vtl::Context {
+        variables,
+        body,
+        params: serde_json::json!({
+            "path": object(&event["pathParameters"]),
+            "querystring": object(&event["queryStringParameters"]),
+            "header": object(&event["headers"]),
+        }),
+    }
+}
@@ -0,0 +1,21 @@
+/// Merge `overlay` into `base` recursively
+///
+/// Objects are merged key by key, and other values in `overlay` replace ones in `base`.
+pub fn deep_merge(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (k, v) in overlay {
+                deep_merge(base.entry(k).or_insert(serde_json::Value::Null), v);
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Read a JSON file given by a command line option
+pub fn read_file(path: &str) -> Result<serde_json::Value, anyhow::Error> {
+    let file =
+        std::fs::File::open(path).map_err(|e| anyhow::anyhow!("failed to open {}: {}", path, e))?;
+    serde_json::from_reader(std::io::BufReader::new(file))
+        .map_err(|e| anyhow::anyhow!("failed to parse {}: {}", path, e))
+}
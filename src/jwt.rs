@@ -0,0 +1,445 @@
+// Keys are fetched again for unknown key IDs at most this often, like rotations of JWKS
+const REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+// Allowed difference of clocks of the gateway and the identity provider
+const LEEWAY_SECS: i64 = 60;
+
+/// JWT authorizer of HTTP APIs validating bearer tokens of the `Authorization` header
+///
+/// Tokens must be signed by RS256, RS384 or RS512 with a key of the JWKS, and `iss` must be the
+/// issuer. `aud` or `client_id` must be one of the audiences if any, and `exp` and `nbf` must
+/// hold. Keys are read from the URL or the file of `--jwt-jwks`, or from `jwks_uri` of the
+/// OpenID Connect discovery document of the issuer.
+#[derive(Debug)]
+pub struct Authorizer {
+    issuer: String,
+    audiences: Vec<String>,
+    jwks: Option<String>,
+    keys: std::sync::RwLock<Option<(std::time::Instant, Vec<Key>)>>,
+}
+
+#[derive(Debug, Clone)]
+struct Key {
+    kid: Option<String>,
+    n: Vec<u8>,
+    e: Vec<u8>,
+}
+
+/// Claims and scopes of a valid token
+#[derive(Debug)]
+pub struct Claims {
+    claims: serde_json::Map<String, serde_json::Value>,
+    scopes: Option<Vec<String>>,
+}
+
+/// Error of tokens rejected with 401
+#[derive(Debug)]
+pub struct Unauthorized(pub String);
+impl std::fmt::Display for Unauthorized {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+impl std::error::Error for Unauthorized {}
+
+fn unauthorized(message: &str) -> anyhow::Error {
+    Unauthorized(message.to_owned()).into()
+}
+
+impl Authorizer {
+    pub fn new(issuer: String, audiences: Vec<String>, jwks: Option<String>) -> Self {
+        Self {
+            issuer,
+            audiences,
+            jwks,
+            keys: std::sync::RwLock::new(None),
+        }
+    }
+
+    /// Validate the bearer token of the request at the time, failing with `Unauthorized` for
+    /// invalid tokens
+    pub async fn authorize(
+        &self,
+        request: &crate::payload::Request,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Claims, anyhow::Error> {
+        let token = request
+            .header(&hyper::header::AUTHORIZATION)
+            .ok_or_else(|| unauthorized("no Authorization header"))?;
+        // Like HTTP APIs, the Bearer prefix is optional
+        let token = token.strip_prefix("Bearer ").unwrap_or(token).trim();
+        let mut parts = token.split('.');
+        let (header, payload, signature) =
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(header), Some(payload), Some(signature), None) => {
+                    (header, payload, signature)
+                }
+                _ => return Err(unauthorized("the token is malformed")),
+            };
+        let decode = |part: &str| {
+            base64::decode_config(part, base64::URL_SAFE_NO_PAD)
+                .map_err(|_| unauthorized("the token is malformed"))
+        };
+        let parse =
+            |part: &str| -> Result<serde_json::Map<String, serde_json::Value>, anyhow::Error> {
+                serde_json::from_slice(&decode(part)?)
+                    .map_err(|_| unauthorized("the token is malformed"))
+            };
+        let header = parse(header)?;
+        let algorithm: &ring::signature::RsaParameters =
+            match header.get("alg").and_then(|alg| alg.as_str()) {
+                Some("RS256") => &ring::signature::RSA_PKCS1_2048_8192_SHA256,
+                Some("RS384") => &ring::signature::RSA_PKCS1_2048_8192_SHA384,
+                Some("RS512") => &ring::signature::RSA_PKCS1_2048_8192_SHA512,
+                _ => return Err(unauthorized("the signing algorithm isn't supported")),
+            };
+        let kid = header.get("kid").and_then(|kid| kid.as_str());
+        let key = self
+            .key(kid)
+            .await?
+            .ok_or_else(|| unauthorized("no key of the token is in JWKS"))?;
+        let message = &token[..token.len() - signature.len() - 1];
+        ring::signature::RsaPublicKeyComponents {
+            n: &key.n,
+            e: &key.e,
+        }
+        .verify(algorithm, message.as_bytes(), &decode(signature)?)
+        .map_err(|_| unauthorized("the signature is invalid"))?;
+
+        let claims = parse(payload)?;
+        if claims.get("iss").and_then(|iss| iss.as_str()) != Some(self.issuer.as_str()) {
+            return Err(unauthorized("the issuer is invalid"));
+        }
+        if !self.audiences.is_empty() {
+            let audiences: Vec<&str> = match claims.get("aud").or_else(|| claims.get("client_id")) {
+                Some(serde_json::Value::String(aud)) => vec![aud],
+                Some(serde_json::Value::Array(auds)) => {
+                    auds.iter().filter_map(|aud| aud.as_str()).collect()
+                }
+                _ => Vec::new(),
+            };
+            if !audiences
+                .iter()
+                .any(|aud| self.audiences.iter().any(|a| a == aud))
+            {
+                return Err(unauthorized("the audience is invalid"));
+            }
+        }
+        let now = now.timestamp();
+        let time = |name: &str| {
+            claims
+                .get(name)
+                .and_then(|time| time.as_f64())
+                .map(|time| time as i64)
+        };
+        match time("exp") {
+            Some(exp) if exp + LEEWAY_SECS < now => {
+                return Err(unauthorized("the token has expired"))
+            }
+            Some(_) => {}
+            None => return Err(unauthorized("the token has no exp")),
+        }
+        if time("nbf").is_some_and(|nbf| nbf - LEEWAY_SECS > now) {
+            return Err(unauthorized("the token isn't valid yet"));
+        }
+        // Scopes of Cognito and Okta are space-separated in scope or scp
+        let scopes = match claims.get("scope").or_else(|| claims.get("scp")) {
+            Some(serde_json::Value::String(scopes)) => {
+                Some(scopes.split_whitespace().map(|s| s.to_owned()).collect())
+            }
+            Some(serde_json::Value::Array(scopes)) => Some(
+                scopes
+                    .iter()
+                    .filter_map(|s| s.as_str().map(|s| s.to_owned()))
+                    .collect(),
+            ),
+            _ => None,
+        };
+        Ok(Claims { claims, scopes })
+    }
+
+    // Key of the ID, fetching keys again if it's unknown
+    async fn key(&self, kid: Option<&str>) -> Result<Option<Key>, anyhow::Error> {
+        let find = |keys: &[Key]| {
+            keys.iter()
+                .find(|key| kid.is_none() || key.kid.as_deref() == kid)
+                .cloned()
+        };
+        let fetched_at = match &*self.keys.read().unwrap() {
+            Some((at, keys)) => match find(keys) {
+                Some(key) => return Ok(Some(key)),
+                None => Some(*at),
+            },
+            None => None,
+        };
+        if fetched_at.is_some_and(|at| at.elapsed() < REFRESH_INTERVAL) {
+            return Ok(None);
+        }
+        let keys = self.fetch_keys().await?;
+        let key = find(&keys);
+        *self.keys.write().unwrap() = Some((std::time::Instant::now(), keys));
+        Ok(key)
+    }
+
+    async fn fetch_keys(&self) -> Result<Vec<Key>, anyhow::Error> {
+        let jwks: serde_json::Value = match &self.jwks {
+            Some(url) if url.starts_with("http://") => get(url).await?,
+            Some(path) => crate::json::read_file(path)?,
+            None => {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.issuer.trim_end_matches('/')
+                );
+                let configuration = get(&url).await?;
+                let jwks_uri = configuration["jwks_uri"]
+                    .as_str()
+                    .ok_or_else(|| anyhow::anyhow!("no jwks_uri in {}", url))?;
+                get(jwks_uri).await?
+            }
+        };
+        let keys: Vec<Key> = jwks["keys"]
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("JWKS has no keys"))?
+            .iter()
+            .filter(|key| key["kty"] == "RSA" && key["use"] != "enc")
+            .filter_map(|key| {
+                let decode = |name: &str| {
+                    let bytes =
+                        base64::decode_config(key[name].as_str()?, base64::URL_SAFE_NO_PAD).ok()?;
+                    // Leading zeros aren't allowed by ring
+                    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+                    Some(bytes[start..].to_vec())
+                };
+                Some(Key {
+                    kid: key["kid"].as_str().map(|kid| kid.to_owned()),
+                    n: decode("n")?,
+                    e: decode("e")?,
+                })
+            })
+            .collect();
+        log::info!("Read {} keys of JWKS for {}", keys.len(), self.issuer);
+        Ok(keys)
+    }
+}
+
+/// Check that keys can be fetched, since only plain HTTP is supported and HTTPS issuers need JWKS
+/// files
+pub fn validate(issuer: &str, jwks: Option<&str>) -> Result<(), anyhow::Error> {
+    match jwks {
+        Some(jwks) if jwks.starts_with("https://") => Err(anyhow::anyhow!(
+            "--jwt-jwks can't be fetched by HTTPS, so give a file of {}",
+            jwks
+        )),
+        None if issuer.starts_with("https://") => Err(anyhow::anyhow!(
+            "keys of {} can't be fetched by HTTPS, so give a JWKS file by --jwt-jwks",
+            issuer
+        )),
+        _ => Ok(()),
+    }
+}
+
+async fn get(url: &str) -> Result<serde_json::Value, anyhow::Error> {
+    let response = reqwest::get(url)
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to fetch {}: {}", url, e))?;
+    Ok(response.error_for_status()?.json().await?)
+}
+
+impl Claims {
+    /// `requestContext.authorizer` of the payload format if it has one, where claims are strings
+    /// like HTTP APIs
+    pub fn context(
+        &self,
+        payload_format: crate::payload::PayloadFormat,
+    ) -> Option<serde_json::Value> {
+        let claims: serde_json::Map<String, serde_json::Value> = self
+            .claims
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    serde_json::Value::String(value) => value.clone(),
+                    // Arrays are written like [a b]
+                    serde_json::Value::Array(values) => format!(
+                        "[{}]",
+                        values
+                            .iter()
+                            .map(|v| v
+                                .as_str()
+                                .map(|s| s.to_owned())
+                                .unwrap_or_else(|| v.to_string()))
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    ),
+                    value => value.to_string(),
+                };
+                (name.clone(), value.into())
+            })
+            .collect();
+        let context = serde_json::json!({
+            "claims": claims,
+            "scopes": self.scopes,
+        });
+        match payload_format {
+            crate::payload::PayloadFormat::V1 => Some(context),
+            crate::payload::PayloadFormat::V2 => Some(serde_json::json!({ "jwt": context })),
+            // Like Cognito user pools of AppSync, whose claims keep their types
+            crate::payload::PayloadFormat::AppSync => {
+                let claim = |name: &str| self.claims.get(name).cloned().unwrap_or_default();
+                let username = match self.claims.get("cognito:username") {
+                    Some(username) => username.clone(),
+                    None => claim("username"),
+                };
+                Some(serde_json::json!({
+                    "claims": self.claims,
+                    "issuer": claim("iss"),
+                    "sub": claim("sub"),
+                    "username": username,
+                    "groups": self.claims.get("cognito:groups"),
+                    "defaultAuthStrategy": "ALLOW",
+                }))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const ISSUER: &str = "http://localhost:8081/realms/dev";
+    // 2026-01-01T00:00:00Z
+    const NOW: i64 = 1767225600;
+
+    fn key_pair() -> ring::signature::RsaKeyPair {
+        let pem = include_str!("../tests/fixtures/jwt-key.pem");
+        let der: String = pem
+            .lines()
+            .filter(|line| !line.starts_with("-----"))
+            .collect();
+        ring::signature::RsaKeyPair::from_pkcs8(&base64::decode(der).unwrap()).unwrap()
+    }
+
+    // Authorizer with the key of the fixture, whose keys were just fetched
+    fn authorizer(audiences: &[&str]) -> super::Authorizer {
+        let public_key: ring::rsa::PublicKeyComponents<Vec<u8>> = key_pair().public().into();
+        let key = super::Key {
+            kid: Some("test".to_owned()),
+            n: public_key.n,
+            e: public_key.e,
+        };
+        let authorizer = super::Authorizer::new(
+            ISSUER.to_owned(),
+            audiences.iter().map(|aud| (*aud).to_owned()).collect(),
+            None,
+        );
+        *authorizer.keys.write().unwrap() = Some((std::time::Instant::now(), vec![key]));
+        authorizer
+    }
+
+    fn token(kid: &str, claims: serde_json::Value) -> String {
+        let encode = |value: serde_json::Value| {
+            base64::encode_config(value.to_string(), base64::URL_SAFE_NO_PAD)
+        };
+        let message = format!(
+            "{}.{}",
+            encode(serde_json::json!({"alg": "RS256", "typ": "JWT", "kid": kid})),
+            encode(claims)
+        );
+        let key_pair = key_pair();
+        let mut signature = vec![0; key_pair.public().modulus_len()];
+        key_pair
+            .sign(
+                &ring::signature::RSA_PKCS1_SHA256,
+                &ring::rand::SystemRandom::new(),
+                message.as_bytes(),
+                &mut signature,
+            )
+            .unwrap();
+        format!(
+            "{}.{}",
+            message,
+            base64::encode_config(signature, base64::URL_SAFE_NO_PAD)
+        )
+    }
+
+    fn claims() -> serde_json::Value {
+        serde_json::json!({
+            "iss": ISSUER,
+            "sub": "user",
+            "aud": "app",
+            "exp": NOW + 3600,
+            "scope": "read write",
+        })
+    }
+
+    async fn authorize(
+        authorizer: &super::Authorizer,
+        token: &str,
+    ) -> Result<super::Claims, String> {
+        let (parts, ()) = hyper::Request::builder()
+            .uri("/")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let request = crate::payload::Request {
+            parts,
+            headers: vec![("authorization".to_owned(), format!("Bearer {}", token))],
+            body: bytes::Bytes::new(),
+        };
+        let now = chrono::DateTime::from_timestamp(NOW, 0).unwrap();
+        authorizer.authorize(&request, now).await.map_err(|e| {
+            assert!(e.is::<super::Unauthorized>());
+            e.to_string()
+        })
+    }
+
+    #[tokio::test]
+    async fn valid_token_has_claims_and_scopes() {
+        let claims = authorize(&authorizer(&["app"]), &token("test", claims()))
+            .await
+            .unwrap();
+        assert_eq!(claims.claims["sub"], "user");
+        assert_eq!(
+            claims.scopes,
+            Some(vec!["read".to_owned(), "write".to_owned()])
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_token_is_unauthorized() {
+        let mut claims = claims();
+        claims["exp"] = (NOW - super::LEEWAY_SECS - 1).into();
+        let error = authorize(&authorizer(&[]), &token("test", claims)).await;
+        assert_eq!(error.unwrap_err(), "the token has expired");
+    }
+
+    #[tokio::test]
+    async fn token_of_other_issuer_is_unauthorized() {
+        let mut claims = claims();
+        claims["iss"] = "http://localhost:8081/realms/other".into();
+        let error = authorize(&authorizer(&[]), &token("test", claims)).await;
+        assert_eq!(error.unwrap_err(), "the issuer is invalid");
+    }
+
+    #[tokio::test]
+    async fn token_of_other_audience_is_unauthorized() {
+        let error = authorize(&authorizer(&["other"]), &token("test", claims())).await;
+        assert_eq!(error.unwrap_err(), "the audience is invalid");
+    }
+
+    #[tokio::test]
+    async fn token_of_unknown_kid_is_unauthorized() {
+        let error = authorize(&authorizer(&[]), &token("unknown", claims())).await;
+        assert_eq!(error.unwrap_err(), "no key of the token is in JWKS");
+    }
+
+    #[tokio::test]
+    async fn tampered_token_is_unauthorized() {
+        let token = token("test", claims());
+        let mut claims = claims();
+        claims["sub"] = "admin".into();
+        let payload = base64::encode_config(claims.to_string(), base64::URL_SAFE_NO_PAD);
+        let parts: Vec<&str> = token.split('.').collect();
+        let tampered = format!("{}.{}.{}", parts[0], payload, parts[2]);
+        let error = authorize(&authorizer(&[]), &tampered).await;
+        assert_eq!(error.unwrap_err(), "the signature is invalid");
+    }
+}
@@ -0,0 +1,137 @@
+// Annotation of services and pods with the route pattern like /users/*, or annotations prefixed by
+// it for more routes
+const ROUTE_ANNOTATION: &str = "rie-gateway/route";
+// Annotation of route options like payload-format=1.0 applied to the routes of the object
+const OPTIONS_ANNOTATION: &str = "rie-gateway/options";
+// Annotation of the name or the number of the port RIE listens
+const PORT_ANNOTATION: &str = "rie-gateway/port";
+const DEFAULT_POD_PORT: u64 = 8080;
+
+/// Routes of services and running pods annotated with `rie-gateway/route`
+///
+/// Requests are sent through the proxy of the API server like the one of `kubectl proxy`, so that
+/// RIE in kind or minikube clusters is reachable from the host. Functions are named by
+/// `app.kubernetes.io/name` or `app` labels, or by the object names.
+pub async fn routes(api_url: &str, namespace: Option<&str>) -> Result<Vec<String>, anyhow::Error> {
+    let api_url = api_url.trim_end_matches('/');
+    let mut routes = Vec::new();
+    for kind in ["services", "pods"] {
+        let url = match namespace {
+            Some(namespace) => format!("{}/api/v1/namespaces/{}/{}", api_url, namespace, kind),
+            None => format!("{}/api/v1/{}", api_url, kind),
+        };
+        let list: serde_json::Value = reqwest::get(&url).await?.error_for_status()?.json().await?;
+        let mut items: Vec<&serde_json::Value> =
+            list["items"].as_array().into_iter().flatten().collect();
+        items.sort_by_key(|item| {
+            (
+                item["metadata"]["namespace"].as_str(),
+                item["metadata"]["name"].as_str(),
+            )
+        });
+        for item in items {
+            if kind == "pods" && item["status"]["phase"] != "Running" {
+                continue;
+            }
+            add_routes(&mut routes, api_url, kind, item)?;
+        }
+    }
+    Ok(routes)
+}
+
+fn add_routes(
+    routes: &mut Vec<String>,
+    api_url: &str,
+    kind: &str,
+    item: &serde_json::Value,
+) -> Result<(), anyhow::Error> {
+    let metadata = &item["metadata"];
+    let annotations = match metadata["annotations"].as_object() {
+        Some(annotations) => annotations,
+        None => return Ok(()),
+    };
+    let patterns: Vec<&str> = annotations
+        .iter()
+        .filter(|(key, _)| {
+            key.as_str() == ROUTE_ANNOTATION
+                || key
+                    .strip_prefix(ROUTE_ANNOTATION)
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+        .filter_map(|(_, pattern)| pattern.as_str())
+        .collect();
+    if patterns.is_empty() {
+        return Ok(());
+    }
+    let (name, namespace) = match (metadata["name"].as_str(), metadata["namespace"].as_str()) {
+        (Some(name), Some(namespace)) => (name, namespace),
+        _ => return Ok(()),
+    };
+    let port = match port(kind, item, annotations.get(PORT_ANNOTATION)) {
+        Some(port) => port,
+        None => {
+            log::warn!(
+                "Ignoring {} {}/{} since it has no port of RIE",
+                kind,
+                namespace,
+                name
+            );
+            return Ok(());
+        }
+    };
+    let labels = &metadata["labels"];
+    let function = labels["app.kubernetes.io/name"]
+        .as_str()
+        .or_else(|| labels["app"].as_str())
+        .unwrap_or(name);
+    let options = annotations
+        .get(OPTIONS_ANNOTATION)
+        .and_then(|options| options.as_str())
+        .unwrap_or_default();
+    for pattern in patterns {
+        routes.push(
+            format!(
+                "{}={}/api/v1/namespaces/{}/{}/{}:{}/proxy function=\"{}\" {}",
+                pattern, api_url, namespace, kind, name, port, function, options
+            )
+            .trim_end()
+            .to_owned(),
+        );
+    }
+    Ok(())
+}
+
+// Port of the annotation given by its name or number, or the first port of services
+fn port(
+    kind: &str,
+    item: &serde_json::Value,
+    annotation: Option<&serde_json::Value>,
+) -> Option<u64> {
+    let annotation = annotation.and_then(|port| port.as_str());
+    let ports: Vec<(Option<&str>, u64)> = if kind == "services" {
+        item["spec"]["ports"]
+            .as_array()?
+            .iter()
+            .filter_map(|port| Some((port["name"].as_str(), port["port"].as_u64()?)))
+            .collect()
+    } else {
+        item["spec"]["containers"]
+            .as_array()?
+            .iter()
+            .filter_map(|container| container["ports"].as_array())
+            .flatten()
+            .filter_map(|port| Some((port["name"].as_str(), port["containerPort"].as_u64()?)))
+            .collect()
+    };
+    match annotation {
+        Some(annotation) => match annotation.parse() {
+            Ok(number) => Some(number),
+            Err(_) => ports
+                .iter()
+                .find(|(name, _)| *name == Some(annotation))
+                .map(|(_, number)| *number),
+        },
+        None if kind == "services" => ports.first().map(|(_, number)| *number),
+        None => Some(DEFAULT_POD_PORT),
+    }
+}
@@ -1,155 +1,2344 @@
-use futures::stream::TryStreamExt as _;
+mod admin;
+mod authorizer;
+mod cache;
+mod check;
+mod cloudfront_headers;
+mod compression;
+mod config;
+mod cors;
+mod discovery;
+#[cfg(unix)]
+mod docker;
+mod etag;
+mod fallback;
+mod gateway_auth;
+mod gateway_responses;
+mod header_filter;
+mod iam;
+mod integration;
+mod json;
+mod jwt;
+mod kubernetes;
+mod mock;
+mod payload;
+mod request_id;
+mod resource;
+mod resource_policy;
+mod route;
+mod schema;
+mod security_headers;
+mod srv;
+mod static_files;
+mod throttle;
+mod tls;
+mod usage_plan;
+mod vtl;
+mod waf;
+
+use futures::StreamExt as _;
+use hyper::server::accept::Accept as _;
 use structopt::StructOpt as _;
 
-#[derive(Debug, structopt::StructOpt)]
+#[derive(Debug, Clone, structopt::StructOpt)]
+#[structopt(
+    after_help = "SUBCOMMANDS:\n    check    Print the routing table and check routes and targets without listening"
+)]
 struct Opt {
     #[structopt(
-        short,
+        long,
+        about = "YAML or TOML file of options keyed by their long names, which command line options override"
+    )]
+    config: Option<std::path::PathBuf>,
+    #[structopt(
+        long,
+        requires = "config",
+        about = "Profile of options in the config file [default: default if defined]"
+    )]
+    profile: Option<String>,
+    #[structopt(
+        short,
+        long,
+        env,
+        default_value = "127.0.0.1:8080",
+        about = "Bind address"
+    )]
+    bind: String,
+    #[structopt(
+        short,
+        long,
+        env,
+        default_value = "http://localhost:9000",
+        about = "Target root URL of RIE, or srv:// followed by a DNS name of SRV records (e.g. srv://_rie._tcp.users.service.consul)"
+    )]
+    target_url: String,
+    #[structopt(
+        long = "route",
+        number_of_values = 1,
+        about = "Route of requests matching a path to another RIE in PATH=URL form (e.g. /users/*=http://localhost:9001) or to a canned response (e.g. /todos=mock:todos.json), falling back to --target-url"
+    )]
+    routes: Vec<route::Route>,
+    #[structopt(
+        long,
+        env,
+        about = "Root URL of a plain HTTP upstream (e.g. a frontend dev server) receiving requests matching no routes, resources or passthrough as they are"
+    )]
+    fallback_url: Option<String>,
+    #[structopt(
+        long,
+        env,
+        about = "SAM template whose Api and HttpApi events of functions are added as routes and resources"
+    )]
+    sam_template: Option<std::path::PathBuf>,
+    #[structopt(
+        long,
+        env,
+        about = "Serverless Framework configuration whose http and httpApi events of functions are added as routes and resources"
+    )]
+    serverless_config: Option<std::path::PathBuf>,
+    #[structopt(
+        long,
+        env,
+        about = "OpenAPI document whose operations with x-amazon-apigateway-integration are added as routes and resources"
+    )]
+    openapi: Option<std::path::PathBuf>,
+    #[structopt(
+        long,
+        env,
+        about = "CDK cloud assembly directory (e.g. cdk.out) whose REST API methods and HTTP API routes are added as routes and resources"
+    )]
+    cdk_out: Option<std::path::PathBuf>,
+    #[structopt(
+        long = "function-target",
+        number_of_values = 1,
+        parse(try_from_str = parse_key_value),
+        about = "Root URL of the RIE running a discovered function in NAME=URL form, falling back to --target-url"
+    )]
+    function_targets: Vec<(String, String)>,
+    #[structopt(
+        long,
+        about = "Add routes of running Docker containers labeled with rie-gateway.route (e.g. rie-gateway.route=/users/*), refreshed as containers start and stop"
+    )]
+    docker: bool,
+    #[structopt(
+        long,
+        env,
+        default_value = "unix:///var/run/docker.sock",
+        about = "Docker daemon socket of --docker"
+    )]
+    docker_host: String,
+    #[structopt(
+        long,
+        about = "Docker network whose IP addresses of containers are routed to, instead of their published ports"
+    )]
+    docker_network: Option<String>,
+    #[structopt(
+        long,
+        env,
+        about = "Kubernetes API server without authentication (e.g. http://127.0.0.1:8001 of kubectl proxy) whose services and pods annotated with rie-gateway/route are added as routes, refreshed as they change"
+    )]
+    kubernetes_api: Option<String>,
+    #[structopt(
+        long,
+        env,
+        requires = "kubernetes-api",
+        about = "Namespace of services and pods of --kubernetes-api [default: all namespaces]"
+    )]
+    kubernetes_namespace: Option<String>,
+    #[structopt(
+        long,
+        default_value = "2",
+        about = "Seconds between refreshes of discovered routes of running containers and SRV records of srv:// targets"
+    )]
+    discovery_interval: u64,
+    #[structopt(
+        long,
+        env,
+        parse(try_from_str = srv::parse_server),
+        about = "DNS server resolving SRV records of srv:// targets (e.g. 127.0.0.1:8600 of Consul) [default: the nameserver of /etc/resolv.conf]"
+    )]
+    dns_server: Option<std::net::SocketAddr>,
+    #[structopt(
+        long = "function-port",
+        number_of_values = 1,
+        parse(try_from_str = parse_key_value),
+        about = "Port of the RIE running a function in NAME=PORT form, filling {port} of target URLs like http://localhost:{port}"
+    )]
+    function_ports: Vec<(String, String)>,
+    #[structopt(
+        long = "function-alias",
+        number_of_values = 1,
+        parse(try_from_str = parse_key_value),
+        about = "Root URL of the RIE running a version or alias of a function in NAME:QUALIFIER=URL form (e.g. users:prod=http://localhost:9002), invoked by POST /__invoke/NAME:QUALIFIER or routes with function=NAME qualifier=QUALIFIER"
+    )]
+    function_aliases: Vec<(String, String)>,
+    #[structopt(
+        long,
+        about = "Enable the admin API under /__gateway/admin/ listing and changing routes, features and options at runtime"
+    )]
+    admin: bool,
+    #[structopt(
+        long,
+        env,
+        requires = "admin",
+        hide_env_values = true,
+        about = "Bearer token of requests to the admin API [default: only loopback clients are allowed]"
+    )]
+    admin_token: Option<String>,
+    #[structopt(
+        long,
+        env,
+        default_value = "2.0",
+        alias = "mode",
+        possible_values = &["1.0", "2.0", "alb", "function-url", "edge-viewer-request", "edge-origin-request", "appsync"],
+        about = "Payload format of the event sent to RIE (1.0 for REST API, 2.0 for HTTP API, alb for Application Load Balancer, function-url for Lambda function URLs, edge-viewer-request and edge-origin-request for Lambda@Edge, appsync for AppSync direct Lambda resolvers)"
+    )]
+    payload_format: payload::PayloadFormat,
+    #[structopt(
+        long,
+        env,
+        default_value = "arn:aws:elasticloadbalancing:us-east-1:123456789012:targetgroup/aws-lambda-rie-gateway/0123456789abcdef",
+        about = "Target group ARN set to ALB events"
+    )]
+    alb_target_group_arn: String,
+    #[structopt(long, about = "Enable multi-value headers of the ALB target group")]
+    alb_multi_value_headers: bool,
+    #[structopt(
+        long = "resource",
+        number_of_values = 1,
+        about = "Resource path with path parameters (e.g. /users/{id}), optionally limited to a method (e.g. 'GET /users/{id}'), matched against requests"
+    )]
+    resources: Vec<resource::Resource>,
+    #[structopt(
+        long = "passthrough",
+        number_of_values = 1,
+        about = "Resource path whose request bodies are sent to RIE as events as is, returning raw results of the function"
+    )]
+    passthrough_resources: Vec<resource::Resource>,
+    #[structopt(long, env, default_value = "$default", about = "Stage name")]
+    stage: String,
+    #[structopt(
+        long,
+        about = "Strip /{stage} prefix from request paths like REST API does"
+    )]
+    strip_stage_prefix: bool,
+    #[structopt(
+        long = "stage-variable",
+        number_of_values = 1,
+        parse(try_from_str = parse_key_value),
+        about = "Stage variable in KEY=VALUE form"
+    )]
+    stage_variables: Vec<(String, String)>,
+    #[structopt(long, env, about = "JSON file containing an object of stage variables")]
+    stage_variables_file: Option<std::path::PathBuf>,
+    #[structopt(
+        long,
+        env,
+        default_value = "123456789012",
+        about = "AWS account ID set to requestContext"
+    )]
+    account_id: String,
+    #[structopt(
+        long,
+        env,
+        default_value = "1234567890",
+        about = "API ID set to requestContext"
+    )]
+    api_id: String,
+    #[structopt(
+        long,
+        about = "Use the client address in X-Forwarded-For header as sourceIp"
+    )]
+    trust_proxy: bool,
+    #[structopt(
+        long = "resource-policy-allow",
+        env = "RESOURCE_POLICY_ALLOW",
+        use_delimiter = true,
+        parse(try_from_str = resource_policy::parse_cidr),
+        about = "Comma-separated CIDR blocks of sourceIp allowed like aws:SourceIp of resource policies, rejecting others with 403"
+    )]
+    resource_policy_allow: Vec<ipnet::IpNet>,
+    #[structopt(
+        long = "resource-policy-deny",
+        env = "RESOURCE_POLICY_DENY",
+        use_delimiter = true,
+        parse(try_from_str = resource_policy::parse_cidr),
+        about = "Comma-separated CIDR blocks of sourceIp denied like aws:SourceIp of resource policies, taking precedence over allowed ones"
+    )]
+    resource_policy_deny: Vec<ipnet::IpNet>,
+    #[structopt(
+        long,
+        env,
+        parse(try_from_str = waf::Rules::read_file),
+        about = "JSON file of rules blocking requests by regexes or sizes of their methods, paths, query strings, headers or bodies with 403 like AWS WAF"
+    )]
+    waf_rules: Option<waf::Rules>,
+    #[structopt(
+        long,
+        env,
+        use_delimiter = true,
+        about = "Comma-separated media types (e.g. image/*) whose request bodies are base64-encoded in auto body encoding. All bodies are base64-encoded when omitted"
+    )]
+    binary_media_types: Vec<String>,
+    #[structopt(
+        long,
+        env,
+        default_value = "auto",
+        possible_values = &["never", "always", "auto"],
+        about = "Encoding of request bodies (never sends text, always sends base64, auto follows binary media types)"
+    )]
+    body_encoding: payload::BodyEncoding,
+    #[structopt(
+        long,
+        about = "Respond with 502 and the problems when function results are malformed in the payload format"
+    )]
+    strict_responses: bool,
+    #[structopt(
+        long,
+        about = "Add charset=utf-8 to textual Content-Type of text response bodies lacking charset"
+    )]
+    label_charset: bool,
+    #[structopt(
+        long,
+        about = "Return function results that aren't proxy responses as raw JSON with 200 instead of errors"
+    )]
+    raw_fallback: bool,
+    #[structopt(
+        long,
+        env,
+        use_delimiter = true,
+        about = "Comma-separated patterns (e.g. x-app-*) of the only response headers of functions passed to clients"
+    )]
+    response_header_allow: Vec<String>,
+    #[structopt(
+        long,
+        env,
+        use_delimiter = true,
+        about = "Comma-separated patterns (e.g. x-internal-*) of response headers of functions removed from responses"
+    )]
+    response_header_deny: Vec<String>,
+    #[structopt(
+        long,
+        about = "Add security headers like Strict-Transport-Security to responses"
+    )]
+    security_headers: bool,
+    #[structopt(
+        long = "security-header",
+        number_of_values = 1,
+        parse(try_from_str = parse_key_value),
+        about = "Security header in NAME=VALUE form (empty VALUE removes a default one), implying --security-headers"
+    )]
+    security_header_values: Vec<(String, String)>,
+    #[structopt(
+        long = "cors-allow-origin",
+        env = "CORS_ALLOW_ORIGINS",
+        use_delimiter = true,
+        about = "Comma-separated origins (e.g. https://*.example.com, or *) allowed by CORS handled by the gateway like HTTP APIs"
+    )]
+    cors_allow_origins: Vec<String>,
+    #[structopt(
+        long = "cors-allow-method",
+        env = "CORS_ALLOW_METHODS",
+        use_delimiter = true,
+        about = "Comma-separated methods allowed by CORS (e.g. GET,POST, or *)"
+    )]
+    cors_allow_methods: Vec<String>,
+    #[structopt(
+        long = "cors-allow-header",
+        env = "CORS_ALLOW_HEADERS",
+        use_delimiter = true,
+        about = "Comma-separated request headers allowed by CORS (e.g. authorization,content-type, or *)"
+    )]
+    cors_allow_headers: Vec<String>,
+    #[structopt(
+        long = "cors-expose-header",
+        env = "CORS_EXPOSE_HEADERS",
+        use_delimiter = true,
+        about = "Comma-separated response headers exposed to browsers by CORS"
+    )]
+    cors_expose_headers: Vec<String>,
+    #[structopt(
+        long,
+        requires = "cors-allow-origins",
+        about = "Allow credentials like cookies by CORS"
+    )]
+    cors_allow_credentials: bool,
+    #[structopt(
+        long,
+        env,
+        requires = "cors-allow-origins",
+        about = "Seconds browsers cache the results of CORS preflight requests"
+    )]
+    cors_max_age: Option<u64>,
+    #[structopt(
+        long,
+        env,
+        about = "Issuer of JWTs (e.g. http://localhost:8081/realms/dev) validated like JWT authorizers of HTTP APIs, where HTTPS issuers need --jwt-jwks files"
+    )]
+    jwt_issuer: Option<String>,
+    #[structopt(
+        long = "jwt-audience",
+        env = "JWT_AUDIENCES",
+        use_delimiter = true,
+        requires = "jwt-issuer",
+        about = "Comma-separated audiences, one of which aud or client_id of JWTs must be"
+    )]
+    jwt_audiences: Vec<String>,
+    #[structopt(
+        long,
+        env,
+        requires = "jwt-issuer",
+        about = "Plain HTTP URL or file of the JWKS verifying JWTs [default: jwks_uri of the OpenID Connect discovery of the issuer]"
+    )]
+    jwt_jwks: Option<String>,
+    #[structopt(
+        long = "iam-credential",
+        number_of_values = 1,
+        about = "Dummy credential in ACCESS_KEY_ID=SECRET_ACCESS_KEY[=USER_ARN] form, requiring requests signed with one of them by SigV4 like IAM authorization"
+    )]
+    iam_credentials: Vec<iam::Credential>,
+    #[structopt(
+        long,
+        env,
+        about = "Root URL of the RIE of a Lambda authorizer invoked before functions (e.g. http://localhost:9100)"
+    )]
+    authorizer_url: Option<String>,
+    #[structopt(
+        long,
+        env,
+        default_value = "request",
+        possible_values = &["token", "request"],
+        about = "Type of the Lambda authorizer, whose events are built from the token or the whole request"
+    )]
+    authorizer_type: authorizer::Type,
+    #[structopt(
+        long,
+        env,
+        default_value = "Authorization",
+        about = "Header of the identity source, without which requests are rejected with 401 (empty for none with request authorizers)"
+    )]
+    authorizer_identity_source: String,
+    #[structopt(
+        long,
+        requires = "authorizer-url",
+        about = "Take simple responses like {\"isAuthorized\": true} of HTTP API authorizers instead of IAM policies"
+    )]
+    authorizer_simple_responses: bool,
+    #[structopt(
+        long = "api-key",
+        number_of_values = 1,
+        about = "API key required in the x-api-key header of requests, without throttle limits or quotas"
+    )]
+    api_keys: Vec<String>,
+    #[structopt(
+        long,
+        env,
+        parse(try_from_str = usage_plan::ApiKeys::read_file),
+        about = "JSON file of API keys required in the x-api-key header with their throttle limits and quotas like usage plans"
+    )]
+    api_keys_file: Option<usage_plan::ApiKeys>,
+    #[structopt(
+        long,
+        env,
+        about = "Compress response bodies of at least this many bytes with br, gzip or deflate accepted by clients"
+    )]
+    minimum_compression_size: Option<usize>,
+    #[structopt(
+        long,
+        env,
+        requires = "throttle-burst-limit",
+        about = "Requests per second of the stage refilled to the token bucket of throttling, rejecting requests over it with 429"
+    )]
+    throttle_rate_limit: Option<f64>,
+    #[structopt(
+        long,
+        env,
+        requires = "throttle-rate-limit",
+        about = "Requests of the stage accepted at once by the token bucket of throttling"
+    )]
+    throttle_burst_limit: Option<u32>,
+    #[structopt(
+        long,
+        env,
+        requires = "client-burst-limit",
+        about = "Requests per second accepted from each client address, rejecting others with 429 regardless of the API (e.g. when the gateway is shared on a network)"
+    )]
+    client_rate_limit: Option<f64>,
+    #[structopt(
+        long,
+        env,
+        requires = "client-rate-limit",
+        about = "Requests accepted at once from each client address"
+    )]
+    client_burst_limit: Option<u32>,
+    #[structopt(
+        long,
+        env,
+        hide_env_values = true,
+        parse(try_from_str = gateway_auth::parse_basic_auth),
+        about = "USER:PASSWORD of basic authentication required by the whole gateway apart from the emulated API (e.g. when the gateway is exposed on a network)"
+    )]
+    gateway_basic_auth: Option<String>,
+    #[structopt(
+        long,
+        env,
+        use_delimiter = true,
+        parse(try_from_str = resource_policy::parse_cidr),
+        about = "Comma-separated CIDR blocks of client addresses accepted by the listener, closing connections of others (e.g. when binding 0.0.0.0 on a shared network)"
+    )]
+    client_allow: Vec<ipnet::IpNet>,
+    #[structopt(
+        long,
+        env,
+        hide_env_values = true,
+        about = "Token required by the whole gateway in the X-Gateway-Token header or as a bearer token of Authorization, apart from the emulated API"
+    )]
+    gateway_token: Option<String>,
+    #[structopt(
+        long,
+        about = "Decode request bodies with Content-Encoding br, gzip or deflate before building events"
+    )]
+    decompress_requests: bool,
+    #[structopt(
+        long,
+        env,
+        default_value = "10485760",
+        about = "Maximum size of request bodies in bytes, above which requests are rejected with 413 like API Gateway does"
+    )]
+    max_request_body: usize,
+    #[structopt(
+        long,
+        env,
+        about = "Integration timeout in milliseconds, after which requests are responded with 504 [default: 29000, or 900000 for function-url]"
+    )]
+    timeout_millis: Option<u64>,
+    #[structopt(
+        long,
+        env,
+        parse(try_from_str = gateway_responses::GatewayResponses::read_file),
+        about = "JSON file customizing status codes, headers and bodies of errors generated by the gateway by response type (e.g. DEFAULT_5XX)"
+    )]
+    gateway_responses: Option<gateway_responses::GatewayResponses>,
+    #[structopt(
+        long = "route-timeout",
+        number_of_values = 1,
+        parse(try_from_str = parse_route_timeout),
+        about = "Integration timeout of requests matching a resource path in PATH=MILLIS form (e.g. /reports/{proxy+}=60000)"
+    )]
+    route_timeouts: Vec<(resource::Resource, u64)>,
+    #[structopt(
+        long,
+        about = "Fail invocations whose results exceed the 6 MB response payload limit of Lambda"
+    )]
+    enforce_response_limit: bool,
+    #[structopt(
+        long,
+        about = "Tag GET responses with ETags and answer matching If-None-Match with 304 without invoking the function"
+    )]
+    etags: bool,
+    #[structopt(
+        long,
+        env,
+        about = "Cache successful GET responses for this many seconds like stage caching of REST APIs"
+    )]
+    cache_ttl: Option<u64>,
+    #[structopt(
+        long = "cache-key-header",
+        number_of_values = 1,
+        about = "Request header included in cache keys"
+    )]
+    cache_key_headers: Vec<String>,
+    #[structopt(
+        long = "cache-key-query",
+        number_of_values = 1,
+        about = "Query string parameter included in cache keys"
+    )]
+    cache_key_query_parameters: Vec<String>,
+    #[structopt(
+        long,
+        env,
+        about = "Return the recorded response to requests repeating an Idempotency-Key header within this many seconds"
+    )]
+    idempotency_ttl: Option<u64>,
+    #[structopt(
+        long,
+        about = "Add CloudFront headers like CloudFront-Viewer-Country to requests"
+    )]
+    cloudfront_headers: bool,
+    #[structopt(
+        long = "cloudfront-header",
+        number_of_values = 1,
+        parse(try_from_str = parse_key_value),
+        about = "CloudFront header value in NAME=VALUE form (e.g. CloudFront-Viewer-Country=JP), implying --cloudfront-headers"
+    )]
+    cloudfront_header_values: Vec<(String, String)>,
+    #[structopt(
+        long,
+        env,
+        parse(try_from_str = json::read_file),
+        about = "JSON file deep-merged into requestContext of every event (e.g. to set authorizer claims)"
+    )]
+    request_context_override: Option<serde_json::Value>,
+    #[structopt(
+        long = "authorizer-preset",
+        number_of_values = 1,
+        parse(try_from_str = authorizer::parse_preset),
+        about = "Named authorizer context in NAME=JSON form (a file or an inline object), which requests select by the X-RIE-Gateway-Authorizer header instead of running authorizers"
+    )]
+    authorizer_presets: Vec<(String, serde_json::Value)>,
+    #[structopt(
+        long,
+        env,
+        parse(try_from_str = json::read_file),
+        about = "JSON file deep-merged into every event for fields not generated by the gateway"
+    )]
+    event_template: Option<serde_json::Value>,
+    #[structopt(
+        long,
+        env,
+        parse(try_from_str = json::read_file),
+        about = "JSON template rendering requests into custom events instead of the payload format, whose raw results are returned"
+    )]
+    webhook_template: Option<serde_json::Value>,
+    #[structopt(
+        long,
+        about = "Respond with the events built for requests as JSON without invoking RIE, which X-RIE-Gateway-Dry-Run headers do for single requests"
+    )]
+    dry_run: bool,
+    #[structopt(
+        long,
+        env,
+        about = "Cognito identity ID set to requestContext.identity of REST API events"
+    )]
+    cognito_identity_id: Option<String>,
+    #[structopt(
+        long,
+        env,
+        about = "Cognito identity pool ID set to requestContext.identity of REST API events"
+    )]
+    cognito_identity_pool_id: Option<String>,
+    #[structopt(long, about = "Generate reproducible request IDs and timestamps")]
+    deterministic: bool,
+    #[structopt(
+        long,
+        env,
+        default_value = "0",
+        about = "Seed of request IDs in deterministic mode"
+    )]
+    seed: u64,
+    #[structopt(
+        long,
+        env,
+        default_value = "2020-01-01T00:00:00Z",
+        about = "Request time in deterministic mode (RFC 3339)"
+    )]
+    fixed_time: chrono::DateTime<chrono::Utc>,
+    #[structopt(
+        long,
+        env,
+        requires = "tls-key",
+        about = "PEM file of the server certificate chain to serve HTTPS"
+    )]
+    tls_cert: Option<std::path::PathBuf>,
+    #[structopt(
         long,
         env,
-        default_value = "127.0.0.1:8080",
-        about = "Bind address"
+        requires = "tls-cert",
+        about = "PEM file of the server private key"
     )]
-    bind: String,
+    tls_key: Option<std::path::PathBuf>,
     #[structopt(
-        short,
         long,
         env,
-        default_value = "http://localhost:9000",
-        about = "Target root URL of RIE"
+        requires = "tls-cert",
+        about = "PEM file of CA certificates verifying client certificates for mutual TLS"
     )]
-    target_url: String,
+    tls_client_ca: Option<std::path::PathBuf>,
+}
+
+/// Options and runtime state shared by requests
+// State is shared with gateways of the same listener whose features are toggled by the admin API
+struct Gateway {
+    opt: Opt,
+    routes: std::sync::Arc<route::Table>,
+    srv: std::sync::Arc<srv::Resolver>,
+    jwt: Option<std::sync::Arc<jwt::Authorizer>>,
+    usage: Option<std::sync::Arc<usage_plan::Meter>>,
+    throttle: Option<throttle::Shared>,
+    clients: Option<std::sync::Arc<throttle::PerClient>>,
+    request_ids: std::sync::Arc<request_id::Generator>,
+    etags: std::sync::Arc<etag::Store>,
+    cache: Option<std::sync::Arc<cache::Cache>>,
+    idempotency_cache: Option<std::sync::Arc<cache::Cache>>,
+}
+
+impl Gateway {
+    fn new(mut opt: Opt) -> Result<Self, anyhow::Error> {
+        if let Some(path) = &opt.config {
+            match &opt.profile {
+                Some(profile) => {
+                    log::info!(
+                        "Read options from {} with profile {}",
+                        path.display(),
+                        profile
+                    )
+                }
+                None => log::info!("Read options from {}", path.display()),
+            }
+        }
+        if let Some(path) = &opt.stage_variables_file {
+            let file = std::fs::File::open(path)
+                .map_err(|e| anyhow::anyhow!("failed to open {}: {}", path.display(), e))?;
+            let vars: std::collections::HashMap<String, String> = serde_json::from_reader(file)?;
+            // Variables given by --stage-variable take precedence
+            let mut stage_variables: Vec<_> = vars.into_iter().collect();
+            stage_variables.append(&mut opt.stage_variables);
+            opt.stage_variables = stage_variables;
+        }
+        let mut discovered = Vec::new();
+        if let Some(path) = &opt.sam_template {
+            discovered.push(discovery::sam::discover(
+                path,
+                &opt.function_targets,
+                &opt.target_url,
+            )?);
+        }
+        if let Some(path) = &opt.serverless_config {
+            discovered.push(discovery::serverless::discover(
+                path,
+                &opt.function_targets,
+                &opt.target_url,
+            )?);
+        }
+        if let Some(path) = &opt.openapi {
+            discovered.push(discovery::openapi::discover(
+                path,
+                &opt.function_targets,
+                &opt.target_url,
+            )?);
+        }
+        if let Some(path) = &opt.cdk_out {
+            discovered.push(discovery::cdk::discover(
+                path,
+                &opt.function_targets,
+                &opt.target_url,
+            )?);
+        }
+        for discovered in discovered {
+            opt.routes.extend(discovered.routes);
+            opt.resources.extend(discovered.resources);
+        }
+        for route in &mut opt.routes {
+            route.expand_target(&opt.function_ports, &opt.function_aliases)?;
+        }
+        if opt.cors_allow_credentials && opt.cors_allow_origins.iter().any(|o| o == "*") {
+            anyhow::bail!("--cors-allow-credentials can't be used with --cors-allow-origin *");
+        }
+        if opt.discovery_interval == 0 {
+            anyhow::bail!("--discovery-interval must be positive");
+        }
+        if let Some(issuer) = &opt.jwt_issuer {
+            jwt::validate(issuer, opt.jwt_jwks.as_deref())?;
+        }
+        Ok(Self {
+            routes: std::sync::Arc::new(route::Table::new(std::mem::take(&mut opt.routes))),
+            srv: std::sync::Arc::new(srv::Resolver::new(
+                opt.dns_server,
+                std::time::Duration::from_secs(opt.discovery_interval),
+            )),
+            jwt: opt.jwt_issuer.as_ref().map(|issuer| {
+                std::sync::Arc::new(jwt::Authorizer::new(
+                    issuer.clone(),
+                    opt.jwt_audiences.clone(),
+                    opt.jwt_jwks.clone(),
+                ))
+            }),
+            usage: if opt.api_keys.is_empty() && opt.api_keys_file.is_none() {
+                None
+            } else {
+                Some(std::sync::Arc::new(usage_plan::Meter::new(
+                    opt.api_keys_file.clone().unwrap_or_default(),
+                    &opt.api_keys,
+                )))
+            },
+            throttle: match (opt.throttle_rate_limit, opt.throttle_burst_limit) {
+                (Some(rate_limit), Some(burst_limit)) => Some(throttle::shared(throttle::Limits {
+                    rate_limit,
+                    burst_limit,
+                })),
+                _ => None,
+            },
+            clients: match (opt.client_rate_limit, opt.client_burst_limit) {
+                (Some(rate_limit), Some(burst_limit)) => Some(std::sync::Arc::new(
+                    throttle::PerClient::new(throttle::Limits {
+                        rate_limit,
+                        burst_limit,
+                    }),
+                )),
+                _ => None,
+            },
+            etags: std::sync::Arc::default(),
+            cache: opt.cache_ttl.map(|ttl| {
+                std::sync::Arc::new(cache::Cache::new(
+                    std::time::Duration::from_secs(ttl),
+                    &opt.cache_key_headers,
+                    &opt.cache_key_query_parameters,
+                ))
+            }),
+            idempotency_cache: opt.idempotency_ttl.map(|ttl| {
+                std::sync::Arc::new(cache::Cache::new(
+                    std::time::Duration::from_secs(ttl),
+                    &[],
+                    &[],
+                ))
+            }),
+            request_ids: std::sync::Arc::new(request_id::Generator::new(if opt.deterministic {
+                Some(opt.seed)
+            } else {
+                None
+            })),
+            opt,
+        })
+    }
+
+    // Gateway of other options keeping the routes, caches and generators
+    fn with_opt(&self, opt: Opt) -> Self {
+        Self {
+            opt,
+            routes: self.routes.clone(),
+            srv: self.srv.clone(),
+            jwt: self.jwt.clone(),
+            usage: self.usage.clone(),
+            throttle: self.throttle.clone(),
+            clients: self.clients.clone(),
+            request_ids: self.request_ids.clone(),
+            etags: self.etags.clone(),
+            cache: self.cache.clone(),
+            idempotency_cache: self.idempotency_cache.clone(),
+        }
+    }
+
+    fn now(&self) -> chrono::DateTime<chrono::Utc> {
+        if self.opt.deterministic {
+            self.opt.fixed_time
+        } else {
+            chrono::Utc::now()
+        }
+    }
+}
+
+// Requests can override requestContext with JSON in this header
+const CONTEXT_HEADER: &str = "x-rie-gateway-context";
+const AUTHORIZER_PRESET_HEADER: &str = "x-rie-gateway-authorizer";
+
+// Requests with this header get their events without invoking functions
+const DRY_RUN_HEADER: &str = "x-rie-gateway-dry-run";
+
+// Events POSTed to this path are sent to RIE as they are
+const INVOKE_PATH: &str = "/__invoke";
+
+// Client context of mobile SDKs, which is passed to RIE as the Invoke API does
+static CLIENT_CONTEXT_HEADER: hyper::header::HeaderName =
+    hyper::header::HeaderName::from_static("x-amz-client-context");
+
+// Requests with the same key get the response to the first one with --idempotency-ttl
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+// Response recorded for a request, which is answered only once the request passes access checks
+enum Recorded {
+    // 304 to If-None-Match of the ETag
+    NotModified(String),
+    Cached(hyper::Response<hyper::Body>),
+    Idempotent(hyper::Response<hyper::Body>),
+}
+
+// Marks responses answered from records, which aren't recorded again
+struct Replayed;
+
+impl Recorded {
+    fn respond(self, uri: &hyper::Uri) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+        let mut response = match self {
+            Self::NotModified(etag) => {
+                log::info!("Responding 304 for {} with ETag {}", uri, etag);
+                etag::not_modified(&etag)?
+            }
+            Self::Cached(response) => {
+                log::info!("Responding cached response for {}", uri);
+                response
+            }
+            Self::Idempotent(response) => {
+                log::info!(
+                    "Responding recorded response for {} with the same Idempotency-Key",
+                    uri
+                );
+                response
+            }
+        };
+        response.extensions_mut().insert(Replayed);
+        Ok(response)
+    }
+}
+
+// API keys of usage plans
+static API_KEY_HEADER: hyper::header::HeaderName =
+    hyper::header::HeaderName::from_static("x-api-key");
+
+// Set by the Invoke API when functions fail
+const FUNCTION_ERROR_HEADER: &str = "x-amz-function-error";
+
+// Maximum size of synchronous invocation results in Lambda
+const MAX_RESPONSE_PAYLOAD_SIZE: usize = 6291556;
+
+fn parse_route_timeout(s: &str) -> Result<(resource::Resource, u64), anyhow::Error> {
+    let (path, millis) = parse_key_value(s)?;
+    let millis = millis
+        .parse()
+        .map_err(|e| anyhow::anyhow!("invalid timeout {:?}: {}", millis, e))?;
+    Ok((path.parse()?, millis))
+}
+
+fn parse_key_value(s: &str) -> Result<(String, String), anyhow::Error> {
+    let mut kv = s.splitn(2, '=');
+    match (kv.next(), kv.next()) {
+        (Some(k), Some(v)) if !k.is_empty() => Ok((k.to_owned(), v.to_owned())),
+        _ => Err(anyhow::anyhow!("expected KEY=VALUE: {}", s)),
+    }
 }
 
+// Options of each listener, which keep the ones current when requests arrive
+type Current = std::sync::Arc<std::sync::RwLock<std::sync::Arc<Gateway>>>;
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
-    let Opt { bind, target_url } = Opt::from_args();
+    if config::command() == Some("check") {
+        let gateways = config::args()?
+            .into_iter()
+            .map(|args| Gateway::new(Opt::from_iter(args)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !check::run(&gateways).await {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    let mut listeners: Vec<Current> = Vec::new();
+    for args in config::args()? {
+        let gateway = Gateway::new(Opt::from_iter(args))?;
+        listeners.push(std::sync::Arc::new(std::sync::RwLock::new(
+            std::sync::Arc::new(gateway),
+        )));
+    }
+    #[cfg(unix)]
+    tokio::spawn(reload_on_hangup(listeners.clone()));
+    for current in &listeners {
+        let opt = &current.read().unwrap().opt;
+        #[cfg(unix)]
+        if opt.docker {
+            let host = opt.docker_host.clone();
+            let network = opt.docker_network.clone();
+            tokio::spawn(watch(current.clone(), "Docker containers", move || {
+                let host = host.clone();
+                let network = network.clone();
+                async move { docker::routes(&host, network.as_deref()).await }
+            }));
+        }
+        if let Some(api_url) = &opt.kubernetes_api {
+            let api_url = api_url.clone();
+            let namespace = opt.kubernetes_namespace.clone();
+            tokio::spawn(watch(current.clone(), "Kubernetes", move || {
+                let api_url = api_url.clone();
+                let namespace = namespace.clone();
+                async move { kubernetes::routes(&api_url, namespace.as_deref()).await }
+            }));
+        }
+    }
+
+    futures::future::try_join_all(
+        listeners
+            .into_iter()
+            .enumerate()
+            .map(|(index, current)| serve(index, current)),
+    )
+    .await?;
+    Ok(())
+}
 
-    let make_service = hyper::service::make_service_fn(move |_| {
-        let target_url = target_url.clone();
-        async {
+// Serve a listener, taking the socket of the index from systemd socket activation if given
+async fn serve(index: usize, current: Current) -> Result<(), anyhow::Error> {
+    let (acceptor, bind) = {
+        let opt = &current.read().unwrap().opt;
+        let acceptor = match (&opt.tls_cert, &opt.tls_key) {
+            (Some(cert), Some(key)) => Some(tokio_rustls::TlsAcceptor::from(std::sync::Arc::new(
+                tls::server_config(cert, key, opt.tls_client_ca.as_deref())?,
+            ))),
+            _ => None,
+        };
+        (acceptor, opt.bind.clone())
+    };
+
+    let accepting = current.clone();
+    let make_service = hyper::service::make_service_fn(move |conn: &tls::Connection| {
+        let current = current.clone();
+        let remote_addr = conn.remote_addr();
+        let client_cert = conn.client_cert().map(std::sync::Arc::new);
+        async move {
             Ok::<_, std::convert::Infallible>(hyper::service::service_fn(move |r| {
-                handle(target_url.clone(), r)
+                // Each request keeps the options current when it arrived until it completes
+                let gateway = current.read().unwrap().clone();
+                handle(
+                    current.clone(),
+                    gateway,
+                    remote_addr,
+                    client_cert.clone(),
+                    r,
+                )
             }))
         }
     });
-    let server = if let Some(listener) = listenfd::ListenFd::from_env().take_tcp_listener(0)? {
-        log::info!("Listen {}", listener.local_addr()?);
-        hyper::server::Server::from_tcp(listener)?
+    let listener =
+        if let Some(listener) = listenfd::ListenFd::from_env().take_tcp_listener(index)? {
+            listener
+        } else {
+            std::net::TcpListener::bind(bind.parse::<std::net::SocketAddr>()?)?
+        };
+    log::info!("Listen {}", listener.local_addr()?);
+    listener.set_nonblocking(true)?;
+    let mut incoming = hyper::server::conn::AddrIncoming::from_listener(
+        tokio::net::TcpListener::from_std(listener)?,
+    )?;
+    let streams =
+        futures::stream::poll_fn(move |cx| std::pin::Pin::new(&mut incoming).poll_accept(cx))
+            // Clients are checked before TLS handshakes and requests, reading reloaded options
+            .filter(move |stream| {
+                let allowed = match stream {
+                    Ok(stream) => {
+                        let ip = stream.remote_addr().ip().to_canonical();
+                        let allow = &accepting.read().unwrap().opt.client_allow;
+                        let allowed = allow.is_empty() || allow.iter().any(|net| net.contains(&ip));
+                        if !allowed {
+                            log::warn!(
+                                "Closed connection from {} not allowed by --client-allow",
+                                ip
+                            );
+                        }
+                        allowed
+                    }
+                    Err(_) => true,
+                };
+                futures::future::ready(allowed)
+            });
+    let connections = if let Some(acceptor) = acceptor {
+        streams
+            .map(move |stream| {
+                let acceptor = acceptor.clone();
+                async move { acceptor.accept(stream?).await }
+            })
+            // Handshakes run concurrently so that a slow client doesn't block others
+            .buffer_unordered(64)
+            .filter_map(|result| async move {
+                match result {
+                    Ok(stream) => Some(Ok(tls::Connection::Tls(Box::new(stream)))),
+                    Err(e) => {
+                        log::warn!("TLS handshake failed: {}", e);
+                        None
+                    }
+                }
+            })
+            .boxed()
     } else {
-        let addr = bind.parse()?;
-        log::info!("Listen {}", addr);
-        hyper::server::Server::bind(&addr)
-    }
-    .serve(make_service)
-    .with_graceful_shutdown(async {
-        let _ = tokio::signal::ctrl_c().await;
-        log::info!("Shutting down...");
-        ()
-    });
+        streams
+            .map(|stream| stream.map(tls::Connection::Plain))
+            .boxed()
+    };
+    let server = hyper::server::Server::builder(hyper::server::accept::from_stream(connections))
+        .serve(make_service)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+            log::info!("Shutting down...");
+        });
     server.await?;
     Ok(())
 }
 
-// https://docs.aws.amazon.com/apigateway/latest/developerguide/http-api-develop-integrations-lambda.html
-#[derive(Debug, serde::Serialize)]
-#[serde(rename_all = "camelCase")]
-struct ApiGatewayV2LambdaProxyIntegrationV2<'a> {
-    version: &'a str,
-    raw_path: &'a str,
-    headers: std::collections::HashMap<String, String>,
-    query_string_parameters: Option<std::collections::HashMap<String, String>>,
-    body: Option<String>,
-    is_base64_encoded: bool,
-    request_context: ApiGatewayV2LambdaProxyIntegrationV2RequestContext<'a>,
-}
-#[derive(Debug, serde::Serialize)]
-#[serde(rename_all = "camelCase")]
-struct ApiGatewayV2LambdaProxyIntegrationV2RequestContext<'a> {
-    http: ApiGatewayV2LambdaProxyIntegrationV2RequestContextHttp<'a>,
-    raw_query_string: Option<&'a str>,
-}
-#[derive(Debug, serde::Serialize)]
-#[serde(rename_all = "camelCase")]
-struct ApiGatewayV2LambdaProxyIntegrationV2RequestContextHttp<'a> {
-    method: String,
-    path: &'a str,
-    source_ip: &'a str,
-}
-#[derive(Debug, serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct ApiGatewayV2LambdaResponseV1 {
-    is_base64_encoded: bool,
-    status_code: u16,
-    #[serde(default)]
-    headers: std::collections::HashMap<String, String>,
-    body: String,
+// Read options again on SIGHUP. Options of the listener like --bind and --tls-cert need restarts
+#[cfg(unix)]
+async fn reload_on_hangup(listeners: Vec<Current>) -> Result<(), anyhow::Error> {
+    let mut hangups = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+    while hangups.recv().await.is_some() {
+        let gateways = config::args().and_then(|args| {
+            if args.len() != listeners.len() {
+                anyhow::bail!("the number of listeners can't change without restarts");
+            }
+            args.into_iter()
+                .map(|args| {
+                    let opt =
+                        Opt::from_iter_safe(args).map_err(|e| anyhow::anyhow!("{}", e.message))?;
+                    Gateway::new(opt)
+                })
+                .collect::<Result<Vec<_>, _>>()
+        });
+        match gateways {
+            Ok(gateways) => {
+                for (current, gateway) in listeners.iter().zip(gateways) {
+                    let mut current = current.write().unwrap();
+                    let opt = &current.opt;
+                    if gateway.opt.bind != opt.bind
+                        || gateway.opt.tls_cert != opt.tls_cert
+                        || gateway.opt.tls_key != opt.tls_key
+                        || gateway.opt.tls_client_ca != opt.tls_client_ca
+                    {
+                        log::warn!("Changes of listener options are applied after restarts");
+                    }
+                    *current = std::sync::Arc::new(gateway);
+                }
+                log::info!("Reloaded options");
+            }
+            Err(e) => log::error!(
+                "Failed to reload options, keeping the current ones: {:#}",
+                e
+            ),
+        }
+    }
+    Ok(())
+}
+
+// Refresh routes of the source every --discovery-interval, which gateways reloaded by SIGHUP get
+// again. Options of sources like --docker need restarts
+async fn watch<F, R>(current: Current, source: &'static str, mut list: F)
+where
+    F: FnMut() -> R,
+    R: std::future::Future<Output = Result<Vec<String>, anyhow::Error>>,
+{
+    let interval = current.read().unwrap().opt.discovery_interval;
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval));
+    let mut failing = false;
+    loop {
+        interval.tick().await;
+        let routes = match list().await {
+            Ok(routes) => routes,
+            Err(e) => {
+                // Warn once until the source responds again
+                if !failing {
+                    log::warn!("Failed to discover routes of {}: {:#}", source, e);
+                }
+                failing = true;
+                continue;
+            }
+        };
+        failing = false;
+        let gateway = current.read().unwrap().clone();
+        match gateway.routes.update(source, routes.clone()) {
+            Ok(true) if routes.is_empty() => log::info!("No routes of {}", source),
+            Ok(true) => {
+                for route in &routes {
+                    log::info!("Discovered route of {}: {}", source, route);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => log::error!("Ignoring routes of {}: {:#}", source, e),
+        }
+    }
 }
 
 async fn handle(
-    target_url: String,
-    request: hyper::Request<hyper::Body>,
+    current: Current,
+    gateway: std::sync::Arc<Gateway>,
+    remote_addr: std::net::SocketAddr,
+    client_cert: Option<std::sync::Arc<tls::ClientCert>>,
+    mut request: hyper::Request<hyper::Body>,
 ) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
-    let query_string_parameters = if request.uri().query().is_some() {
-        let u = url::Url::parse(&format!("{}", request.uri()))?;
-        let mut params = std::collections::HashMap::new();
-        for (k, v) in u.query_pairs() {
-            params.insert(k.into_owned(), v.into_owned());
+    let opt = &gateway.opt;
+    // Connections are limited by their addresses, since clients could forge X-Forwarded-For
+    if let Some(clients) = &gateway.clients {
+        if let Err(retry_after) = clients.take(remote_addr.ip()) {
+            log::warn!("Rate limited request from {}", remote_addr.ip());
+            return Ok(hyper::Response::builder()
+                .status(hyper::StatusCode::TOO_MANY_REQUESTS)
+                .header(hyper::header::RETRY_AFTER, retry_after)
+                .body(hyper::Body::from("Too Many Requests"))?);
+        }
+    }
+    // Browsers send preflights without credentials, which the gateway answers itself
+    if gateway_auth::is_enabled(opt)
+        && !(cors::is_enabled(opt) && cors::is_preflight(&request))
+        && !gateway_auth::authenticate(opt, &mut request)
+    {
+        log::warn!(
+            "Rejected request from {} without credentials of the gateway",
+            remote_addr
+        );
+        return gateway_auth::challenge(opt);
+    }
+    if opt.admin && request.uri().path().starts_with(admin::PREFIX) {
+        return admin::handle(&current, &gateway, remote_addr, request).await;
+    }
+    let routes = gateway.routes.snapshot();
+    if let Some(fallback_url) = &opt.fallback_url {
+        if !is_function_request(opt, &routes, &request) {
+            return match fallback::forward(fallback_url, remote_addr, request).await {
+                Ok(response) => Ok(response),
+                Err(e) => {
+                    log::error!("Failed to forward request to {}: {:#}", fallback_url, e);
+                    Ok(hyper::Response::builder()
+                        .status(hyper::StatusCode::BAD_GATEWAY)
+                        .body(hyper::Body::empty())?)
+                }
+            };
         }
-        Some(params)
+    }
+    let route = find_route(opt, &routes, &request);
+    let payload_format = payload_format(opt, route);
+    let request_id = gateway.request_ids.generate();
+    let time = gateway.now();
+    let is_head = request.method() == hyper::Method::HEAD;
+    let origin = request
+        .headers()
+        .get(hyper::header::ORIGIN)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_owned());
+    // Events of dry runs are neither cached nor recorded
+    let dry_run = opt.dry_run || request.headers().contains_key(DRY_RUN_HEADER);
+    let accept_encoding = request
+        .headers()
+        .get(hyper::header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_owned());
+    let etag_url = if opt.etags
+        && !dry_run
+        && (request.method() == hyper::Method::GET || request.method() == hyper::Method::HEAD)
+    {
+        // URIs of origin-form requests have no hosts, which can choose routes
+        let host = request
+            .headers()
+            .get(hyper::header::HOST)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        Some(format!("{} {}", host.to_ascii_lowercase(), request.uri()))
     } else {
         None
     };
-    let method = request.method().clone();
-    let uri = request.uri().clone();
-    let mut headers = std::collections::HashMap::new();
-    for (k, v) in request.headers() {
-        headers.insert(k.as_str().to_owned(), v.to_str()?.to_owned());
+    let if_none_match = request
+        .headers()
+        .get(hyper::header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_owned());
+    // Answer conditional requests for unchanged responses without invoking the function
+    let recorded = match (&etag_url, &if_none_match) {
+        (Some(url), Some(if_none_match)) => gateway
+            .etags
+            .lookup(url)
+            .filter(|etag| etag::matches(if_none_match, etag))
+            .map(Recorded::NotModified),
+        _ => None,
+    };
+    let cache_key = gateway
+        .cache
+        .as_ref()
+        .filter(|_| !dry_run)
+        .and_then(|cache| Some((cache, cache.key(&request)?)));
+    if let Some((cache, key)) = &cache_key {
+        // Like API Gateway, clients can invalidate the entry with Cache-Control: max-age=0
+        if request
+            .headers()
+            .get(hyper::header::CACHE_CONTROL)
+            .is_some_and(|v| v.as_bytes() == b"max-age=0")
+        {
+            cache.invalidate(key);
+        }
     }
-    let body = request
-        .into_body()
-        .map_ok(|b| bytes::BytesMut::from(&b[..]))
-        .try_concat()
-        .await?;
-    let payload = ApiGatewayV2LambdaProxyIntegrationV2 {
-        version: "2.0",
-        raw_path: uri.path(),
-        headers,
-        query_string_parameters,
-        body: if body.is_empty() {
+    let idempotency_key = gateway
+        .idempotency_cache
+        .as_ref()
+        .filter(|_| !dry_run)
+        .and_then(|cache| {
+            let key = request.headers().get(IDEMPOTENCY_KEY_HEADER)?;
+            // Callers are told apart by their credentials, so that nobody gets responses of others
+            let identity: Vec<_> = [
+                hyper::header::AUTHORIZATION.as_str(),
+                API_KEY_HEADER.as_str(),
+                AUTHORIZER_PRESET_HEADER,
+            ]
+            .iter()
+            .map(|name| {
+                request
+                    .headers()
+                    .get_all(*name)
+                    .iter()
+                    .map(|v| String::from_utf8_lossy(v.as_bytes()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .collect();
+            Some((
+                cache,
+                format!(
+                    "{} {}\n{}\n{}",
+                    request.method(),
+                    request.uri(),
+                    identity.join("\n"),
+                    String::from_utf8_lossy(key.as_bytes())
+                ),
+            ))
+        });
+    let recorded = recorded
+        .or_else(|| {
+            let (cache, key) = cache_key.as_ref()?;
+            cache.get(key).map(Recorded::Cached)
+        })
+        .or_else(|| {
+            let (cache, key) = idempotency_key.as_ref()?;
+            cache.get(key).map(Recorded::Idempotent)
+        });
+    if let Some(recorded) = recorded {
+        request.extensions_mut().insert(recorded);
+    }
+    let preflight = cors::is_enabled(opt) && cors::is_preflight(&request);
+    let mut response = if preflight {
+        log::info!(
+            "Responding to the CORS preflight request for {}",
+            request.uri()
+        );
+        cors::preflight(opt, origin.as_deref())?
+    } else {
+        let response = match proxy(
+            &gateway,
+            route,
+            remote_addr,
+            client_cert,
+            &request_id,
+            time,
+            request,
+        )
+        .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                // Respond like API Gateway does when invocations fail or responses are malformed
+                log::error!("Failed to proxy request: {:#}", e);
+                if e.downcast_ref::<reqwest::Error>()
+                    .is_some_and(|e| e.is_timeout())
+                {
+                    gateway_response(
+                        opt,
+                        Some(gateway_responses::INTEGRATION_TIMEOUT),
+                        payload_format.timeout_response(),
+                        &request_id,
+                    )?
+                } else {
+                    gateway_response(opt, None, payload_format.error_response(), &request_id)?
+                }
+            }
+        };
+        let replayed = response.extensions().get::<Replayed>().is_some();
+        let response = match cache_key {
+            Some((cache, key)) if !replayed && response.status().is_success() => {
+                cache.put(key, response).await?
+            }
+            _ => response,
+        };
+        // Server errors aren't recorded so that retries can succeed
+        match idempotency_key {
+            Some((cache, key)) if !replayed && !response.status().is_server_error() => {
+                cache.put(key, response).await?
+            }
+            _ => response,
+        }
+    };
+    if let Some(url) = &etag_url {
+        if response.status() == hyper::StatusCode::OK {
+            let (tagged, etag) = gateway.etags.tag(url, response).await?;
+            response = tagged;
+            if if_none_match.is_some_and(|if_none_match| etag::matches(&if_none_match, &etag)) {
+                *response.status_mut() = hyper::StatusCode::NOT_MODIFIED;
+            }
+        }
+    }
+    let headers = response.headers_mut();
+    for (name, value) in payload_format.response_headers(&request_id) {
+        headers.insert(name, hyper::header::HeaderValue::from_str(value)?);
+    }
+    headers
+        .entry(hyper::header::DATE)
+        .or_insert(hyper::header::HeaderValue::from_str(
+            &time.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+        )?);
+    if opt.security_headers || !opt.security_header_values.is_empty() {
+        security_headers::inject(headers, &opt.security_header_values)?;
+    }
+    // Preflight responses have their own CORS headers
+    if cors::is_enabled(opt) && !preflight {
+        cors::apply(opt, origin.as_deref(), headers)?;
+    }
+    let status = response.status();
+    if status == hyper::StatusCode::NO_CONTENT || status == hyper::StatusCode::NOT_MODIFIED {
+        // These responses never have a body, whatever the function returned
+        response.headers_mut().remove(hyper::header::CONTENT_LENGTH);
+        *response.body_mut() = hyper::Body::empty();
+    } else {
+        if let (Some(minimum_size), Some(accept_encoding)) =
+            (opt.minimum_compression_size, &accept_encoding)
+        {
+            response = compression::compress(response, accept_encoding, minimum_size).await?;
+        }
+        if is_head {
+            // Keep Content-Length of the body GET would return, without sending the body
+            let body = std::mem::take(response.body_mut());
+            let length = hyper::body::to_bytes(body).await?.len();
+            response
+                .headers_mut()
+                .insert(hyper::header::CONTENT_LENGTH, length.into());
+        }
+    }
+    Ok(response)
+}
+
+async fn proxy(
+    gateway: &Gateway,
+    route: Option<&route::Route>,
+    remote_addr: std::net::SocketAddr,
+    client_cert: Option<std::sync::Arc<tls::ClientCert>>,
+    request_id: &request_id::RequestId,
+    time: chrono::DateTime<chrono::Utc>,
+    request: hyper::Request<hyper::Body>,
+) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    let opt = &gateway.opt;
+    let mut request = match payload::Request::from_hyper(request, opt.max_request_body).await {
+        Ok(request) => request,
+        Err(e) if e.is::<payload::BodyTooLarge>() => {
+            log::warn!("Rejected request: {}", e);
+            return request_too_large(opt, request_id);
+        }
+        Err(e) => return Err(e),
+    };
+    if opt.decompress_requests {
+        if let Err(e) = compression::decompress_request(&mut request, opt.max_request_body) {
+            if e.is::<payload::BodyTooLarge>() {
+                log::warn!("Rejected decoded request: {}", e);
+                return request_too_large(opt, request_id);
+            }
+            return Ok(hyper::Response::builder()
+                .status(hyper::StatusCode::BAD_REQUEST)
+                .body(hyper::Body::from(format!("{:#}\n", e)))?);
+        }
+    }
+    let payload_format = payload_format(opt, route);
+    let dry_run = request.remove_header(DRY_RUN_HEADER).is_some() || opt.dry_run;
+    let source_ip = source_ip(&request, remote_addr, opt.trust_proxy);
+    // WAF is in front of the API
+    if let Some(rule) = opt
+        .waf_rules
+        .as_ref()
+        .and_then(|rules| rules.blocked_by(&request))
+    {
+        log::warn!("Blocked request by the WAF rule {}", rule);
+        return gateway_response(
+            opt,
+            Some(gateway_responses::WAF_FILTERED),
+            payload::LambdaResponse::json(403, r#"{"message":"Forbidden"}"#.to_owned()),
+            request_id,
+        );
+    }
+    if !resource_policy::allows(
+        &opt.resource_policy_allow,
+        &opt.resource_policy_deny,
+        &source_ip,
+    ) {
+        log::warn!("Rejected request from {} by the resource policy", source_ip);
+        return gateway_response(
+            opt,
+            Some(gateway_responses::ACCESS_DENIED),
+            payload::LambdaResponse::json(403, r#"{"message":"Forbidden"}"#.to_owned()),
+            request_id,
+        );
+    }
+    // Routes with their own limits don't take tokens of the stage
+    let bucket = route
+        .and_then(|route| route.throttle.as_ref())
+        .or(gateway.throttle.as_ref());
+    if bucket.is_some_and(|bucket| !bucket.lock().unwrap().take()) {
+        log::warn!("Throttled request to {}", request.parts.uri.path());
+        return gateway_response(
+            opt,
+            Some(gateway_responses::THROTTLED),
+            payload::LambdaResponse::json(429, r#"{"message":"Too Many Requests"}"#.to_owned()),
+            request_id,
+        );
+    }
+    // Presets stand in for authorizers, which don't run for them
+    let preset = match request.remove_header(AUTHORIZER_PRESET_HEADER) {
+        Some(name) => match opt.authorizer_presets.iter().find(|(n, _)| *n == name) {
+            Some((_, context)) => Some(context.clone()),
+            None => {
+                log::warn!(
+                    "Rejected request with an unknown authorizer preset {}",
+                    name
+                );
+                let body = serde_json::json!({
+                    "message": format!("Unknown authorizer preset: {}", name),
+                });
+                return gateway_response(
+                    opt,
+                    Some(gateway_responses::UNAUTHORIZED),
+                    payload::LambdaResponse::json(401, body.to_string()),
+                    request_id,
+                );
+            }
+        },
+        None => None,
+    };
+    let has_preset = preset.is_some();
+    // Like authorizers, tokens are validated before integrations including mocks
+    let mut authorizer = match &gateway.jwt {
+        Some(jwt) if !has_preset => match jwt.authorize(&request, gateway.now()).await {
+            Ok(claims) => claims.context(payload_format),
+            Err(e) if e.is::<jwt::Unauthorized>() => {
+                log::warn!("Rejected request by the JWT authorizer: {}", e);
+                let mut response =
+                    payload::LambdaResponse::json(401, r#"{"message":"Unauthorized"}"#.to_owned());
+                response.headers.insert(
+                    hyper::header::WWW_AUTHENTICATE.as_str().to_owned(),
+                    format!(
+                        r#"Bearer scope="" error="invalid_token" error_description="{}""#,
+                        e
+                    ),
+                );
+                return gateway_response(
+                    opt,
+                    Some(gateway_responses::UNAUTHORIZED),
+                    response,
+                    request_id,
+                );
+            }
+            Err(e) => return Err(e),
+        },
+        _ => preset,
+    };
+    let caller = if opt.iam_credentials.is_empty() {
+        None
+    } else {
+        match iam::verify(&opt.iam_credentials, &request, gateway.now()) {
+            Ok(credential) => credential.context(payload_format, &opt.account_id),
+            Err(rejection) => {
+                log::warn!("Rejected request by IAM authorization: {:?}", rejection);
+                // HTTP APIs don't tell the reason
+                let (response_type, message) = match rejection {
+                    _ if payload_format != payload::PayloadFormat::V1 => {
+                        (None, "Forbidden".to_owned())
+                    }
+                    iam::Rejection::MissingToken => (
+                        Some(gateway_responses::MISSING_AUTHENTICATION_TOKEN),
+                        "Missing Authentication Token".to_owned(),
+                    ),
+                    iam::Rejection::InvalidSignature(message) => {
+                        (Some(gateway_responses::INVALID_SIGNATURE), message)
+                    }
+                };
+                let body = serde_json::json!({ "message": message });
+                return gateway_response(
+                    opt,
+                    response_type,
+                    payload::LambdaResponse::json(403, body.to_string()),
+                    request_id,
+                );
+            }
+        }
+    };
+    let context_override = match request.remove_header(CONTEXT_HEADER) {
+        Some(value) => Some(
+            serde_json::from_str::<serde_json::Value>(&value)
+                .map_err(|e| anyhow::anyhow!("invalid {} header: {}", CONTEXT_HEADER, e))?,
+        ),
+        None => None,
+    };
+    if opt.cloudfront_headers || !opt.cloudfront_header_values.is_empty() {
+        cloudfront_headers::inject(
+            &mut request,
+            &opt.cloudfront_header_values,
+            gateway.request_ids.uuid(),
+        );
+    }
+    // Stage variables of the route take precedence
+    let stage_variables: std::collections::HashMap<String, String> = opt
+        .stage_variables
+        .iter()
+        .chain(route.iter().flat_map(|route| &route.stage_variables))
+        .cloned()
+        .collect();
+    // Events see the request of clients until routes rewrite it
+    let original_path = resource_path(opt, &request).to_owned();
+    let original_resource = resource::find(&opt.resources, &request.parts.method, &original_path);
+    let options = payload::EventOptions {
+        alb_target_group_arn: &opt.alb_target_group_arn,
+        alb_multi_value_headers: opt.alb_multi_value_headers,
+        resource: original_resource.as_ref(),
+        stage: route
+            .and_then(|route| route.stage.as_deref())
+            .unwrap_or(&opt.stage),
+        stage_variables: if stage_variables.is_empty() {
             None
         } else {
-            Some(base64::encode(&body))
-        },
-        is_base64_encoded: true,
-        request_context: ApiGatewayV2LambdaProxyIntegrationV2RequestContext {
-            http: ApiGatewayV2LambdaProxyIntegrationV2RequestContextHttp {
-                method: format!("{}", method),
-                path: uri.path(),
-                source_ip: "0.0.0.0",
-            },
-            raw_query_string: uri.query(),
+            Some(&stage_variables)
         },
+        path: &original_path,
+        account_id: &opt.account_id,
+        api_id: &opt.api_id,
+        request_id,
+        time,
+        source_ip: &source_ip,
+        client_cert: client_cert.as_deref(),
+        cognito_identity_id: opt.cognito_identity_id.as_deref(),
+        cognito_identity_pool_id: opt.cognito_identity_pool_id.as_deref(),
+        body_encoding: opt.body_encoding,
+        binary_media_types: &opt.binary_media_types,
+    };
+    // Like API Gateway, authorizers and API keys are checked before any integration
+    if let Some(authorizer_url) = opt.authorizer_url.as_ref().filter(|_| !has_preset) {
+        let method_arn = format!(
+            "arn:aws:execute-api:us-east-1:{}:{}/{}/{}{}",
+            opt.account_id, opt.api_id, options.stage, request.parts.method, options.path
+        );
+        let identity_source = &opt.authorizer_identity_source;
+        let identity = if identity_source.is_empty() {
+            None
+        } else {
+            match request.header(&hyper::header::HeaderName::from_bytes(
+                identity_source.to_ascii_lowercase().as_bytes(),
+            )?) {
+                Some(identity) => Some(identity.to_owned()),
+                None => {
+                    log::warn!("Rejected request without {}", identity_source);
+                    return unauthorized(opt, request_id);
+                }
+            }
+        };
+        // AppSync resolvers have no proxy events for authorizers to see
+        let event_format = if payload_format == payload::PayloadFormat::AppSync {
+            payload::PayloadFormat::V1
+        } else {
+            payload_format
+        };
+        let mut event = event_format.build_event(&request, &options)?;
+        merge_event(
+            opt,
+            &mut event,
+            authorizer.as_ref(),
+            caller.as_ref(),
+            context_override.as_ref(),
+        );
+        let authorizer_event = authorizer::event(
+            opt.authorizer_type,
+            payload_format,
+            &event,
+            identity.as_deref(),
+            &method_arn,
+        );
+        let resp = send(
+            gateway,
+            None,
+            authorizer_url,
+            &request,
+            serde_json::to_vec(&authorizer_event)?.into(),
+        )
+        .await?;
+        let function_error = resp.headers().contains_key(FUNCTION_ERROR_HEADER);
+        let result = read_result(opt, resp).await?;
+        match authorizer::decide(
+            payload_format,
+            opt.authorizer_simple_responses,
+            &method_arn,
+            function_error,
+            &result,
+        )? {
+            authorizer::Decision::Allow(context) => {
+                authorizer = Some(match authorizer.take() {
+                    Some(mut authorizer) => {
+                        json::deep_merge(&mut authorizer, context);
+                        authorizer
+                    }
+                    None => context,
+                });
+            }
+            authorizer::Decision::Unauthorized => {
+                log::warn!("Rejected request by the Lambda authorizer with 401");
+                return unauthorized(opt, request_id);
+            }
+            authorizer::Decision::Deny(message) => {
+                log::warn!("Rejected request by the Lambda authorizer: {}", message);
+                let body = serde_json::json!({ "message": message });
+                return gateway_response(
+                    opt,
+                    Some(gateway_responses::ACCESS_DENIED),
+                    payload::LambdaResponse::json(403, body.to_string()),
+                    request_id,
+                );
+            }
+        }
+    }
+    // Like REST APIs, API keys are checked after authorizers
+    let mut api_key_identity = None;
+    if let Some(usage) = &gateway.usage {
+        let api_key = request.header(&API_KEY_HEADER);
+        match usage.check(api_key, gateway.now()) {
+            Ok(api_key_id) => {
+                if payload_format == payload::PayloadFormat::V1 {
+                    api_key_identity =
+                        Some(serde_json::json!({ "apiKey": api_key, "apiKeyId": api_key_id }));
+                }
+            }
+            Err(rejection) => {
+                log::warn!("Rejected request by the usage plan: {:?}", rejection);
+                let (status, response_type, message) = match rejection {
+                    usage_plan::Rejection::InvalidKey => {
+                        (403, gateway_responses::INVALID_API_KEY, "Forbidden")
+                    }
+                    usage_plan::Rejection::Throttled => {
+                        (429, gateway_responses::THROTTLED, "Too Many Requests")
+                    }
+                    usage_plan::Rejection::QuotaExceeded => {
+                        (429, gateway_responses::QUOTA_EXCEEDED, "Limit Exceeded")
+                    }
+                };
+                let body = serde_json::json!({ "message": message });
+                return gateway_response(
+                    opt,
+                    Some(response_type),
+                    payload::LambdaResponse::json(status, body.to_string()),
+                    request_id,
+                );
+            }
+        }
+    }
+    if let Some(recorded) = request.parts.extensions.remove::<Recorded>() {
+        return recorded.respond(&request.parts.uri);
+    }
+    // Direct invocations pass the same access checks, which could be bypassed otherwise
+    if request.parts.method == hyper::Method::POST && is_invoke_path(request.parts.uri.path()) {
+        if let Err(e) = serde_json::from_slice::<serde::de::IgnoredAny>(&request.body) {
+            return Ok(hyper::Response::builder()
+                .status(hyper::StatusCode::BAD_REQUEST)
+                .body(hyper::Body::from(format!("invalid JSON event: {}\n", e)))?);
+        }
+        if dry_run {
+            return dry_run_response(request.body.clone());
+        }
+        let resp = match request.parts.uri.path().strip_prefix("/__invoke/") {
+            // Invocations of functions by names, optionally qualified like Invoke API
+            Some(function) => {
+                let function = percent_encoding::percent_decode_str(function).decode_utf8()?;
+                let target_url = match function_url(opt, &function)? {
+                    Some(url) => url,
+                    None => {
+                        let body = serde_json::json!({
+                            "Type": "User",
+                            "message": format!("Function not found: {}", function),
+                        });
+                        return to_hyper_response(payload::LambdaResponse::json(
+                            404,
+                            body.to_string(),
+                        ));
+                    }
+                };
+                log::info!("Invoke {} at {}", function, target_url);
+                send(gateway, None, &target_url, &request, request.body.clone()).await?
+            }
+            None => invoke(gateway, route, &request, request.body.clone()).await?,
+        };
+        return raw_response(read_result(opt, resp).await?);
+    }
+    let path = original_path.as_str();
+    match route.map(|route| &route.target) {
+        Some(route::Target::Mock(mock)) => {
+            log::info!(
+                "Responding mock response for {} {}",
+                request.parts.method,
+                path
+            );
+            return to_hyper_response(mock.respond().await);
+        }
+        Some(route::Target::Static(files)) => {
+            return files.serve(&request.parts.method, path).await
+        }
+        _ => {}
+    }
+    // Like request validators, parameters are validated before any transformation
+    let missing = route
+        .map(|route| route.missing_parameters(&request))
+        .unwrap_or_default();
+    if !missing.is_empty() {
+        log::warn!(
+            "Rejected request missing parameters: {}",
+            missing.join(", ")
+        );
+        let body = serde_json::json!({
+            "message": format!("Missing required request parameters: [{}]", missing.join(", ")),
+        });
+        return gateway_response(
+            opt,
+            Some(gateway_responses::BAD_REQUEST_PARAMETERS),
+            payload::LambdaResponse::json(400, serde_json::to_string(&body)?),
+            request_id,
+        );
+    }
+    let (errors, is_body) = route
+        .map(|route| route.validation_errors(&request))
+        .unwrap_or_default();
+    if !errors.is_empty() {
+        log::warn!("Rejected invalid request: [{}]", errors.join(", "));
+        let (response_type, message) = if is_body {
+            (gateway_responses::BAD_REQUEST_BODY, "Invalid request body")
+        } else {
+            (
+                gateway_responses::BAD_REQUEST_PARAMETERS,
+                "Invalid request parameters",
+            )
+        };
+        let body = serde_json::json!({ "message": message });
+        return gateway_response(
+            opt,
+            Some(response_type),
+            payload::LambdaResponse::json(400, body.to_string()),
+            request_id,
+        );
+    }
+    if let Some(rewritten) = route.and_then(|route| route.rewrite_path(path)) {
+        // Keep the stage prefix stripped from the path
+        let uri_path = request.parts.uri.path();
+        let rewritten = format!(
+            "{}{}",
+            uri_path.strip_suffix(path).unwrap_or(uri_path),
+            rewritten
+        );
+        log::info!("Rewrite {} to {}", uri_path, rewritten);
+        request.set_path(&rewritten)?;
+    }
+    if let Some(route) = route {
+        route.apply_header_rules(&mut request);
+        route.apply_query_rules(&mut request)?;
+    }
+    let path = resource_path(opt, &request);
+    let method = &request.parts.method;
+    if resource::find(&opt.passthrough_resources, method, path).is_some() {
+        if dry_run {
+            return dry_run_response(request.body.clone());
+        }
+        let resp = invoke(gateway, route, &request, request.body.clone()).await?;
+        return raw_response(read_result(opt, resp).await?);
+    }
+    let resource = resource::find(&opt.resources, method, path);
+    if resource.is_none() && resource::matches_path(&opt.resources, path) {
+        // Like API Gateway, resources don't accept methods other than theirs
+        let response_type = if payload_format == payload::PayloadFormat::V1 {
+            Some(gateway_responses::MISSING_AUTHENTICATION_TOKEN)
+        } else {
+            None
+        };
+        log::warn!("No resource matches with {} {}", method, path);
+        return gateway_response(
+            opt,
+            response_type,
+            payload_format.missing_resource_response(),
+            request_id,
+        );
+    }
+    let options = payload::EventOptions {
+        resource: resource.as_ref(),
+        path,
+        ..options
+    };
+    if let Some(template) = &opt.webhook_template {
+        let event = payload::webhook::build(&request, &options, template);
+        if dry_run {
+            return dry_run_response(serde_json::to_vec_pretty(&event)?.into());
+        }
+        let resp = invoke(gateway, route, &request, serde_json::to_vec(&event)?.into()).await?;
+        return raw_response(read_result(opt, resp).await?);
+    }
+    if payload_format == payload::PayloadFormat::AppSync {
+        // Resolvers see the caller as the identity instead of requestContext
+        let identity = authorizer
+            .into_iter()
+            .chain(caller)
+            .reduce(|mut identity, caller| {
+                json::deep_merge(&mut identity, caller);
+                identity
+            });
+        return handle_appsync(gateway, route, &request, identity.as_ref(), dry_run).await;
+    }
+    let mut event = payload_format.build_event(&request, &options)?;
+    merge_event(
+        opt,
+        &mut event,
+        authorizer.as_ref(),
+        caller.as_ref(),
+        context_override.as_ref(),
+    );
+    if let Some(identity) = api_key_identity {
+        json::deep_merge(&mut event["requestContext"]["identity"], identity);
+    }
+    let integration = route.and_then(|route| route.integration.as_ref());
+    if dry_run {
+        return match integration {
+            Some(integration) => dry_run_response(integration.request(&event)?),
+            None => dry_run_response(serde_json::to_vec_pretty(&event)?.into()),
+        };
+    }
+    if let Some(integration) = integration {
+        let resp = invoke(gateway, route, &request, integration.request(&event)?).await?;
+        let function_error = resp.headers().contains_key(FUNCTION_ERROR_HEADER);
+        let resp_body = read_result(opt, resp).await?;
+        let error = payload::FunctionError::parse(function_error, &resp_body);
+        if let Some(e) = &error {
+            log_function_error(e);
+        }
+        let lambda_response = integration.response(&event, error.as_ref(), &resp_body)?;
+        log::info!("Received upstream response: {:?}", lambda_response);
+        return to_hyper_response(lambda_response);
+    }
+    let resp = invoke(gateway, route, &request, serde_json::to_vec(&event)?.into()).await?;
+    let function_error = resp.headers().contains_key(FUNCTION_ERROR_HEADER);
+    let resp_body = read_result(opt, resp).await?;
+    if let Some(e) = payload::FunctionError::parse(function_error, &resp_body) {
+        // API Gateway hides errors of functions from clients
+        log_function_error(&e);
+        return gateway_response(opt, None, payload_format.error_response(), request_id);
+    }
+    if opt.strict_responses {
+        let errors = payload_format.validate_response(&resp_body);
+        if !errors.is_empty() {
+            for error in &errors {
+                log::error!("Malformed Lambda proxy response: {}", error);
+            }
+            let body = serde_json::json!({
+                "message": "Malformed Lambda proxy response",
+                "errors": errors,
+            });
+            return gateway_response(
+                opt,
+                None,
+                payload::LambdaResponse::json(502, serde_json::to_string(&body)?),
+                request_id,
+            );
+        }
+    }
+    let lambda_response = match payload_format.parse_response(&resp_body) {
+        Ok(lambda_response) => lambda_response,
+        Err(e) if opt.raw_fallback => {
+            log::warn!(
+                "Returning the function result as is since it's not a proxy response: {}",
+                e
+            );
+            return raw_response(resp_body);
+        }
+        Err(e) => return Err(e),
+    };
+    // Hop-by-hop headers and Content-Length of functions would corrupt responses
+    let mut hop_by_hop =
+        header_filter::hop_by_hop_headers(lambda_response.connection_header_values());
+    hop_by_hop.push(hyper::header::CONTENT_LENGTH.as_str().to_owned());
+    let lambda_response = lambda_response.filter_headers(|name| {
+        !hop_by_hop.contains(&name.to_ascii_lowercase())
+            && header_filter::is_allowed(
+                &opt.response_header_allow,
+                &opt.response_header_deny,
+                name,
+            )
+    });
+    let mut lambda_response = if payload_format == payload::PayloadFormat::V1 {
+        lambda_response.keep_unless_binary(
+            request.header(&hyper::header::ACCEPT),
+            &opt.binary_media_types,
+        )
+    } else {
+        lambda_response
+    };
+    if opt.label_charset {
+        lambda_response.label_utf8();
+    }
+    log::info!("Received upstream response: {:?}", lambda_response);
+    to_hyper_response(lambda_response)
+}
+
+// Merge the event template and request contexts of authorizers, callers and overrides
+fn merge_event(
+    opt: &Opt,
+    event: &mut serde_json::Value,
+    authorizer: Option<&serde_json::Value>,
+    caller: Option<&serde_json::Value>,
+    context_override: Option<&serde_json::Value>,
+) {
+    if let Some(template) = &opt.event_template {
+        json::deep_merge(event, template.clone());
+    }
+    if let Some(authorizer) = authorizer {
+        json::deep_merge(
+            &mut event["requestContext"]["authorizer"],
+            authorizer.clone(),
+        );
+    }
+    if let Some(caller) = caller {
+        json::deep_merge(&mut event["requestContext"], caller.clone());
+    }
+    for overlay in opt.request_context_override.iter().chain(context_override) {
+        json::deep_merge(&mut event["requestContext"], overlay.clone());
+    }
+}
+
+// AppSync invokes the resolver of each top-level field in order
+async fn handle_appsync(
+    gateway: &Gateway,
+    route: Option<&route::Route>,
+    request: &payload::Request,
+    identity: Option<&serde_json::Value>,
+    dry_run: bool,
+) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    let opt = &gateway.opt;
+    let mut events = match payload::appsync::build_events(request, identity) {
+        Ok(events) => events,
+        Err(e) => return to_hyper_response(payload::appsync::error_response(&e)?),
     };
+    if let Some(template) = &opt.event_template {
+        for (_, event) in &mut events {
+            json::deep_merge(event, template.clone());
+        }
+    }
+    if dry_run {
+        // Events of all the top-level fields in order
+        let events: Vec<&serde_json::Value> = events.iter().map(|(_, event)| event).collect();
+        return dry_run_response(serde_json::to_vec_pretty(&events)?.into());
+    }
+    let mut results = Vec::with_capacity(events.len());
+    for (key, event) in events {
+        let resp = invoke(gateway, route, request, serde_json::to_vec(&event)?.into()).await?;
+        let function_error = resp.headers().contains_key(FUNCTION_ERROR_HEADER);
+        let resp_body = read_result(opt, resp).await?;
+        log::info!(
+            "Received upstream response: {}",
+            String::from_utf8_lossy(&resp_body)
+        );
+        let result = match payload::FunctionError::parse(function_error, &resp_body) {
+            Some(e) => {
+                log_function_error(&e);
+                Err(e)
+            }
+            None => Ok(serde_json::from_slice(&resp_body)?),
+        };
+        results.push((key, result));
+    }
+    to_hyper_response(payload::appsync::build_response(results)?)
+}
+
+fn request_too_large(
+    opt: &Opt,
+    request_id: &request_id::RequestId,
+) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    gateway_response(
+        opt,
+        Some(gateway_responses::REQUEST_TOO_LARGE),
+        payload::LambdaResponse::json(413, r#"{"message":"Request Entity Too Large"}"#.to_owned()),
+        request_id,
+    )
+}
+
+fn unauthorized(
+    opt: &Opt,
+    request_id: &request_id::RequestId,
+) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    gateway_response(
+        opt,
+        Some(gateway_responses::UNAUTHORIZED),
+        payload::LambdaResponse::json(401, r#"{"message":"Unauthorized"}"#.to_owned()),
+        request_id,
+    )
+}
+
+fn dry_run_response(event: bytes::Bytes) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    log::info!("Responding the event of the dry run without invoking the function");
+    Ok(hyper::Response::builder()
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(hyper::Body::from(event))?)
+}
+
+fn log_function_error(error: &payload::FunctionError) {
+    log::error!(
+        "Function error: {}: {}",
+        error.error_type.as_deref().unwrap_or("Unhandled"),
+        error.error_message
+    );
+    for line in &error.stack_trace {
+        log::error!("    {}", line);
+    }
+}
+
+async fn invoke(
+    gateway: &Gateway,
+    route: Option<&route::Route>,
+    request: &payload::Request,
+    payload: bytes::Bytes,
+) -> Result<reqwest::Response, anyhow::Error> {
+    let opt = &gateway.opt;
     log::info!(
         "Send upstream request: {}",
-        serde_json::to_string(&payload)?
+        String::from_utf8_lossy(&payload)
     );
-    let resp = reqwest::Client::new()
-        .post(&format!(
+    // Mock and static file routes respond before invocations
+    let target_url = route
+        .and_then(|route| route.target_url())
+        .unwrap_or(&opt.target_url);
+    if target_url.contains('{') {
+        // Templates of --target-url are filled for routes of functions only
+        anyhow::bail!("no function of the request to fill {}", target_url);
+    }
+    send(gateway, route, target_url, request, payload).await
+}
+
+async fn send(
+    gateway: &Gateway,
+    route: Option<&route::Route>,
+    target_url: &str,
+    request: &payload::Request,
+    payload: bytes::Bytes,
+) -> Result<reqwest::Response, anyhow::Error> {
+    let opt = &gateway.opt;
+    let target_url = gateway.srv.resolve(target_url).await?;
+    let timeout = integration_timeout(opt, route, request);
+    let client_context = request.header(&CLIENT_CONTEXT_HEADER);
+    for mirror_url in route.map(|route| route.mirror_urls()).unwrap_or_default() {
+        let mirror = match gateway.srv.resolve(mirror_url).await {
+            Ok(url) => invocation(&url, timeout, client_context),
+            Err(e) => {
+                log::warn!("Failed to mirror invocation to {}: {:#}", mirror_url, e);
+                continue;
+            }
+        };
+        let payload = payload.clone();
+        let mirror_url = mirror_url.clone();
+        // Mirrors never delay or change responses
+        tokio::spawn(async move {
+            match mirror.body(payload).send().await {
+                Ok(resp) => log::info!(
+                    "Mirrored invocation to {} responded {}",
+                    mirror_url,
+                    resp.status()
+                ),
+                Err(e) => log::warn!("Failed to mirror invocation to {}: {}", mirror_url, e),
+            }
+        });
+    }
+    Ok(invocation(&target_url, timeout, client_context)
+        .body(payload)
+        .send()
+        .await?)
+}
+
+fn invocation(
+    target_url: &str,
+    timeout: std::time::Duration,
+    client_context: Option<&str>,
+) -> reqwest::RequestBuilder {
+    let mut upstream = reqwest::Client::new()
+        .post(format!(
             "{}/2015-03-31/functions/function/invocations",
             target_url
         ))
-        .json(&payload)
-        .send()
-        .await?;
-    let lambda_response: ApiGatewayV2LambdaResponseV1 = resp.json().await?;
-    log::info!("Received upstream response: {:?}", lambda_response);
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        // Covers reading the result too, and dropping the request aborts the invocation
+        .timeout(timeout);
+    if let Some(client_context) = client_context {
+        upstream = upstream.header(CLIENT_CONTEXT_HEADER.as_str(), client_context);
+    }
+    upstream
+}
+
+fn integration_timeout(
+    opt: &Opt,
+    route: Option<&route::Route>,
+    request: &payload::Request,
+) -> std::time::Duration {
+    let millis = match resource::find_value(
+        &opt.route_timeouts,
+        &request.parts.method,
+        resource_path(opt, request),
+    ) {
+        Some((millis, _)) => *millis,
+        None => opt
+            .timeout_millis
+            .unwrap_or_else(|| payload_format(opt, route).default_timeout_millis()),
+    };
+    std::time::Duration::from_millis(millis)
+}
+
+async fn read_result(opt: &Opt, resp: reqwest::Response) -> Result<bytes::Bytes, anyhow::Error> {
+    let body = resp.bytes().await?;
+    if opt.enforce_response_limit && body.len() > MAX_RESPONSE_PAYLOAD_SIZE {
+        // Lambda fails such invocations, which API Gateway turns into its error response
+        anyhow::bail!(
+            "Function.ResponseSizeTooLarge: Response payload size ({} bytes) exceeded maximum allowed payload size ({} bytes)",
+            body.len(),
+            MAX_RESPONSE_PAYLOAD_SIZE
+        );
+    }
+    Ok(body)
+}
+
+// Raw results of functions are returned as they are
+fn raw_response(resp_body: bytes::Bytes) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    log::info!(
+        "Received upstream response: {}",
+        String::from_utf8_lossy(&resp_body)
+    );
+    Ok(hyper::Response::builder()
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(hyper::Body::from(resp_body))?)
+}
 
+// Errors generated by the gateway itself, which --gateway-responses can customize
+fn gateway_response(
+    opt: &Opt,
+    response_type: Option<&str>,
+    response: payload::LambdaResponse,
+    request_id: &request_id::RequestId,
+) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    match &opt.gateway_responses {
+        Some(gateway_responses) => {
+            to_hyper_response(gateway_responses.apply(response_type, response, request_id))
+        }
+        None => to_hyper_response(response),
+    }
+}
+
+fn to_hyper_response(
+    lambda_response: payload::LambdaResponse,
+) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    let body = lambda_response.decode_body()?;
     let mut builder = hyper::Response::builder().status(lambda_response.status_code);
-    for (k, v) in lambda_response.headers {
-        builder = builder.header(k.as_bytes(), v);
+    // Like API Gateway, multiValueHeaders take precedence over headers of the same name
+    for (k, v) in &lambda_response.headers {
+        if !lambda_response
+            .multi_value_headers
+            .keys()
+            .any(|m| m.eq_ignore_ascii_case(k))
+        {
+            builder = builder.header(k.as_bytes(), v.as_str());
+        }
+    }
+    for (k, vs) in &lambda_response.multi_value_headers {
+        for v in vs {
+            builder = builder.header(k.as_bytes(), v.as_str());
+        }
+    }
+    for cookie in &lambda_response.cookies {
+        builder = builder.header(hyper::header::SET_COOKIE, cookie.as_str());
+    }
+    Ok(builder.body(hyper::Body::from(body))?)
+}
+
+// Whether the request is for functions rather than the upstream of --fallback-url
+fn is_function_request<B>(opt: &Opt, routes: &[route::Route], request: &hyper::Request<B>) -> bool {
+    let path = request.uri().path();
+    if request.method() == hyper::Method::POST && is_invoke_path(path) {
+        return true;
+    }
+    let path = if opt.strip_stage_prefix {
+        strip_stage_prefix(path, &opt.stage)
+    } else {
+        path
+    };
+    find_route(opt, routes, request).is_some()
+        || resource::matches_path(&opt.resources, path)
+        || resource::matches_path(&opt.passthrough_resources, path)
+}
+
+fn is_invoke_path(path: &str) -> bool {
+    path == INVOKE_PATH || path.starts_with("/__invoke/")
+}
+
+// Root URL of the function like users or users:prod, where qualifiers need their RIE
+fn function_url(opt: &Opt, function: &str) -> Result<Option<String>, anyhow::Error> {
+    let (name, qualifier) = match function.split_once(':') {
+        Some((name, "$LATEST")) => (name, None),
+        Some((name, qualifier)) => (name, Some(qualifier)),
+        None => (function, None),
+    };
+    if qualifier.is_some() {
+        if let Some((_, url)) = opt.function_aliases.iter().find(|(k, _)| k == function) {
+            return Ok(Some(url.trim_end_matches('/').to_owned()));
+        }
+    } else if let Some((_, url)) = opt.function_targets.iter().find(|(k, _)| k == name) {
+        return Ok(Some(url.trim_end_matches('/').to_owned()));
+    }
+    // Templates without {qualifier} can't tell versions apart
+    if opt.target_url.contains('{')
+        && (qualifier.is_none() || opt.target_url.contains("{qualifier}"))
+    {
+        return route::expand_url(&opt.target_url, Some(name), qualifier, &opt.function_ports)
+            .map(Some);
+    }
+    Ok(match qualifier {
+        Some(_) => None,
+        None => Some(opt.target_url.clone()),
+    })
+}
+
+// Route of the request, matched with the path before rewrites
+fn find_route<'a, B>(
+    opt: &Opt,
+    routes: &'a [route::Route],
+    request: &hyper::Request<B>,
+) -> Option<&'a route::Route> {
+    let host = request
+        .headers()
+        .get(hyper::header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| request.uri().host())
+        .map(|host| host.split(':').next().unwrap_or(host))
+        .filter(|host| !host.is_empty());
+    let path = if opt.strip_stage_prefix {
+        strip_stage_prefix(request.uri().path(), &opt.stage)
+    } else {
+        request.uri().path()
+    };
+    route::find(routes, request.method(), host, path)
+}
+
+fn payload_format(opt: &Opt, route: Option<&route::Route>) -> payload::PayloadFormat {
+    route
+        .and_then(|route| route.payload_format)
+        .unwrap_or(opt.payload_format)
+}
+
+// Path matched against resources
+fn resource_path<'a>(opt: &Opt, request: &'a payload::Request) -> &'a str {
+    if opt.strip_stage_prefix {
+        strip_stage_prefix(request.parts.uri.path(), &opt.stage)
+    } else {
+        request.parts.uri.path()
+    }
+}
+
+fn strip_stage_prefix<'a>(path: &'a str, stage: &str) -> &'a str {
+    match path.strip_prefix('/').and_then(|p| p.strip_prefix(stage)) {
+        Some("") => "/",
+        Some(rest) if rest.starts_with('/') => rest,
+        _ => path,
+    }
+}
+
+fn source_ip(
+    request: &payload::Request,
+    remote_addr: std::net::SocketAddr,
+    trust_proxy: bool,
+) -> String {
+    if trust_proxy {
+        // The leftmost address is the original client
+        let forwarded_for = request
+            .headers
+            .iter()
+            .find(|(k, _)| k == "x-forwarded-for")
+            .and_then(|(_, v)| v.split(',').next())
+            .map(|ip| ip.trim())
+            .filter(|ip| !ip.is_empty());
+        if let Some(ip) = forwarded_for {
+            return ip.to_owned();
+        }
     }
-    Ok(builder.body(hyper::Body::from(lambda_response.body))?)
+    remote_addr.ip().to_string()
 }
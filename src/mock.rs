@@ -0,0 +1,59 @@
+/// Canned response of routes to `mock:`, like MOCK integrations of API Gateway
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Mock {
+    #[serde(default = "default_status_code")]
+    status_code: u16,
+    #[serde(default)]
+    headers: std::collections::HashMap<String, String>,
+    // Strings are sent as they are, and other values as JSON
+    #[serde(default)]
+    body: serde_json::Value,
+    latency_millis: Option<u64>,
+}
+
+fn default_status_code() -> u16 {
+    200
+}
+
+impl Mock {
+    /// Parse the inline JSON object or the JSON file at the head of `s`, returning the rest
+    pub fn parse(s: &str) -> Result<(Self, &str), anyhow::Error> {
+        if s.starts_with('{') {
+            let mut values = serde_json::Deserializer::from_str(s).into_iter::<Self>();
+            let mock = values
+                .next()
+                .unwrap_or_else(|| Err(serde::de::Error::custom("missing mock response")))
+                .map_err(|e| anyhow::anyhow!("invalid mock response {}: {}", s, e))?;
+            Ok((mock, &s[values.byte_offset()..]))
+        } else {
+            let end = s.find(char::is_whitespace).unwrap_or(s.len());
+            let path = &s[..end];
+            let mock = serde_json::from_value(crate::json::read_file(path)?)
+                .map_err(|e| anyhow::anyhow!("invalid mock response in {}: {}", path, e))?;
+            Ok((mock, &s[end..]))
+        }
+    }
+
+    pub async fn respond(&self) -> crate::payload::LambdaResponse {
+        if let Some(millis) = self.latency_millis {
+            tokio::time::sleep(std::time::Duration::from_millis(millis)).await;
+        }
+        let (body, is_json) = match &self.body {
+            serde_json::Value::Null => (String::new(), false),
+            serde_json::Value::String(body) => (body.clone(), false),
+            body => (body.to_string(), true),
+        };
+        let mut response = crate::payload::LambdaResponse::json(self.status_code, body);
+        if !is_json {
+            response.headers.clear();
+        }
+        for (k, v) in &self.headers {
+            response
+                .headers
+                .retain(|name, _| !name.eq_ignore_ascii_case(k));
+            response.headers.insert(k.clone(), v.clone());
+        }
+        response
+    }
+}
@@ -0,0 +1,758 @@
+mod alb;
+pub mod appsync;
+mod function_url;
+mod lambda_edge;
+mod lenient;
+mod v1;
+mod v2;
+mod validate;
+pub mod webhook;
+
+use futures::stream::TryStreamExt as _;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PayloadFormat {
+    V1,
+    V2,
+    Alb,
+    FunctionUrl,
+    EdgeViewerRequest,
+    EdgeOriginRequest,
+    AppSync,
+}
+impl std::str::FromStr for PayloadFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1.0" => Ok(Self::V1),
+            "2.0" => Ok(Self::V2),
+            "alb" => Ok(Self::Alb),
+            "function-url" => Ok(Self::FunctionUrl),
+            "edge-viewer-request" => Ok(Self::EdgeViewerRequest),
+            "edge-origin-request" => Ok(Self::EdgeOriginRequest),
+            "appsync" => Ok(Self::AppSync),
+            _ => Err(anyhow::anyhow!("unknown payload format: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for PayloadFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::V1 => "1.0",
+            Self::V2 => "2.0",
+            Self::Alb => "alb",
+            Self::FunctionUrl => "function-url",
+            Self::EdgeViewerRequest => "edge-viewer-request",
+            Self::EdgeOriginRequest => "edge-origin-request",
+            Self::AppSync => "appsync",
+        })
+    }
+}
+
+/// How request bodies are encoded in events
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BodyEncoding {
+    // Send bodies as text
+    Never,
+    // Base64-encode every body
+    Always,
+    // Base64-encode bodies matching with binary media types
+    Auto,
+}
+impl std::str::FromStr for BodyEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(Self::Never),
+            "always" => Ok(Self::Always),
+            "auto" => Ok(Self::Auto),
+            _ => Err(anyhow::anyhow!("unknown body encoding: {}", s)),
+        }
+    }
+}
+
+/// Error of request bodies exceeding the size limit
+#[derive(Debug)]
+pub struct BodyTooLarge {
+    pub limit: usize,
+}
+impl std::fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "request body exceeds {} bytes", self.limit)
+    }
+}
+impl std::error::Error for BodyTooLarge {}
+
+/// Incoming HTTP request split into the pieces every payload format is built from
+#[derive(Debug)]
+pub struct Request {
+    pub parts: hyper::http::request::Parts,
+    pub headers: Vec<(String, String)>,
+    pub body: bytes::Bytes,
+}
+impl Request {
+    /// Read the request, failing with `BodyTooLarge` when the body exceeds `max_body_size` bytes
+    pub async fn from_hyper(
+        request: hyper::Request<hyper::Body>,
+        max_body_size: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let (parts, mut body) = request.into_parts();
+        // Keep each occurrence of repeated headers, and non-ASCII values instead of rejecting them
+        let mut headers: Vec<(String, String)> = parts
+            .headers
+            .iter()
+            .map(|(k, v)| {
+                (
+                    k.as_str().to_owned(),
+                    String::from_utf8_lossy(v.as_bytes()).into_owned(),
+                )
+            })
+            .collect();
+        // API Gateway doesn't pass hop-by-hop headers to functions
+        let hop_by_hop = crate::header_filter::hop_by_hop_headers(
+            headers
+                .iter()
+                .filter(|(k, _)| k == hyper::header::CONNECTION.as_str())
+                .map(|(_, v)| v.as_str()),
+        );
+        headers.retain(|(k, _)| !hop_by_hop.contains(k));
+        let too_large = BodyTooLarge {
+            limit: max_body_size,
+        };
+        let content_length = parts
+            .headers
+            .get(hyper::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<usize>().ok());
+        if content_length.is_some_and(|n| n > max_body_size) {
+            return Err(too_large.into());
+        }
+        // Stop buffering chunked bodies as soon as they exceed the limit
+        let mut buf = bytes::BytesMut::new();
+        while let Some(chunk) = body.try_next().await? {
+            if buf.len() + chunk.len() > max_body_size {
+                return Err(too_large.into());
+            }
+            buf.extend_from_slice(&chunk);
+        }
+        let body = buf.freeze();
+        Ok(Self {
+            parts,
+            headers,
+            body,
+        })
+    }
+
+    /// Remove all occurrences of the header, returning the last value
+    pub fn remove_header(&mut self, name: &str) -> Option<String> {
+        let mut value = None;
+        self.headers.retain(|(k, v)| {
+            if k == name {
+                value = Some(v.clone());
+                false
+            } else {
+                true
+            }
+        });
+        value
+    }
+
+    pub fn header(&self, name: &hyper::header::HeaderName) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k == name.as_str())
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Replace the path of the request-target, keeping the query string
+    pub fn set_path(&mut self, path: &str) -> Result<(), anyhow::Error> {
+        let query = self.parts.uri.query().map(|query| query.to_owned());
+        self.set_path_and_query(path, query.as_deref())
+    }
+
+    /// Replace the query string of the request-target, removing it when empty
+    pub fn set_query(&mut self, query: &str) -> Result<(), anyhow::Error> {
+        let path = self.parts.uri.path().to_owned();
+        self.set_path_and_query(&path, Some(query).filter(|query| !query.is_empty()))
+    }
+
+    fn set_path_and_query(&mut self, path: &str, query: Option<&str>) -> Result<(), anyhow::Error> {
+        let path_and_query = match query {
+            Some(query) => format!("{}?{}", path, query),
+            None => path.to_owned(),
+        };
+        let mut uri = self.parts.uri.clone().into_parts();
+        uri.path_and_query = Some(path_and_query.parse()?);
+        self.parts.uri = hyper::Uri::from_parts(uri)?;
+        Ok(())
+    }
+
+    // Host header without the port, which is used as domainName like custom domain names
+    pub fn domain_name(&self) -> Option<&str> {
+        self.header(&hyper::header::HOST)
+            .or_else(|| self.parts.uri.host())
+            .map(|host| host.split(':').next().unwrap_or(host))
+            .filter(|host| !host.is_empty())
+    }
+
+    // Body and isBase64Encoded of the event
+    fn encode_body(&self, options: &EventOptions<'_>) -> (Option<String>, bool) {
+        if self.body.is_empty() {
+            return (None, false);
+        }
+        let binary = match options.body_encoding {
+            BodyEncoding::Always => true,
+            BodyEncoding::Never => false,
+            BodyEncoding::Auto => {
+                let content_type = self.header(&hyper::header::CONTENT_TYPE);
+                // Multipart bodies may contain binary file parts even when they happen to be valid UTF-8,
+                // and bodies which aren't valid UTF-8 would be corrupted as text
+                options.binary_media_types.is_empty()
+                    || content_type
+                        .map(|content_type| {
+                            is_binary_media_type(content_type, options.binary_media_types)
+                                || is_binary_media_type(content_type, &["multipart/*"])
+                        })
+                        .unwrap_or(false)
+                    || std::str::from_utf8(&self.body).is_err()
+            }
+        };
+        if binary {
+            (Some(base64::encode(&self.body)), true)
+        } else {
+            (
+                Some(String::from_utf8_lossy(&self.body).into_owned()),
+                false,
+            )
+        }
+    }
+
+    // Query string split into key-value pairs in the original order without decoding them.
+    // Keys without `=` have empty values.
+    fn raw_query_pairs(&self) -> Vec<(&str, &str)> {
+        self.parts
+            .uri
+            .query()
+            .unwrap_or("")
+            .split('&')
+            .filter(|p| !p.is_empty())
+            .map(|p| p.split_once('=').unwrap_or((p, "")))
+            .collect()
+    }
+
+    /// Query string parameters in the original order, whose percent-encoding is decoded but `+` is
+    /// kept as is like API Gateway does
+    pub fn query_pairs(&self) -> Vec<(String, String)> {
+        let decode = |s: &str| {
+            percent_encoding::percent_decode_str(s)
+                .decode_utf8_lossy()
+                .into_owned()
+        };
+        self.raw_query_pairs()
+            .into_iter()
+            .map(|(k, v)| (decode(k), decode(v)))
+            .collect()
+    }
+}
+
+/// Whether the Content-Type matches with binary media types like `image/png` or `image/*`
+fn is_binary_media_type<S>(content_type: &str, binary_media_types: &[S]) -> bool
+where
+    S: AsRef<str>,
+{
+    let media_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let (type_, subtype) = match media_type.split_once('/') {
+        Some(t) => t,
+        None => return false,
+    };
+    binary_media_types.iter().any(|pattern| {
+        let pattern = pattern.as_ref().trim().to_ascii_lowercase();
+        match pattern.split_once('/') {
+            Some(("*", "*")) => true,
+            Some((t, "*")) => t == type_,
+            Some((t, s)) => t == type_ && s == subtype,
+            None => false,
+        }
+    })
+}
+
+// Media types of text like text/html, application/json or image/svg+xml
+fn is_textual_media_type(content_type: &str) -> bool {
+    let media_type = content_type.split(';').next().unwrap_or_default().trim();
+    media_type.starts_with("text/")
+        || media_type.ends_with("+json")
+        || media_type.ends_with("+xml")
+        || matches!(
+            media_type,
+            "application/json" | "application/xml" | "application/javascript"
+        )
+}
+
+/// Collect every value of repeated keys in the original order
+fn multi_value_map<I>(pairs: I) -> indexmap::IndexMap<String, Vec<String>>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut map: indexmap::IndexMap<String, Vec<String>> = indexmap::IndexMap::new();
+    for (k, v) in pairs {
+        map.entry(k).or_default().push(v);
+    }
+    map
+}
+
+/// Gateway settings and routing results reflected into generated events
+#[derive(Debug, Clone, Copy)]
+pub struct EventOptions<'a> {
+    pub alb_target_group_arn: &'a str,
+    pub alb_multi_value_headers: bool,
+    pub resource: Option<&'a crate::resource::Match>,
+    pub stage: &'a str,
+    pub stage_variables: Option<&'a std::collections::HashMap<String, String>>,
+    // Request path without the stage prefix
+    pub path: &'a str,
+    pub account_id: &'a str,
+    pub api_id: &'a str,
+    pub request_id: &'a crate::request_id::RequestId,
+    pub time: chrono::DateTime<chrono::Utc>,
+    pub source_ip: &'a str,
+    // Client certificate of mutual TLS connections
+    pub client_cert: Option<&'a crate::tls::ClientCert>,
+    pub cognito_identity_id: Option<&'a str>,
+    pub cognito_identity_pool_id: Option<&'a str>,
+    pub body_encoding: BodyEncoding,
+    // All request bodies are base64-encoded in auto mode when empty
+    pub binary_media_types: &'a [String],
+}
+
+impl<'a> EventOptions<'a> {
+    // Format of requestContext.time and requestContext.requestTime
+    fn request_time(&self) -> String {
+        self.time.format("%d/%b/%Y:%H:%M:%S %z").to_string()
+    }
+
+    // domainName and domainPrefix, falling back to the default domain of the API
+    fn domain_name_and_prefix(&self, request: &Request, default_suffix: &str) -> (String, String) {
+        let domain_name = match request.domain_name() {
+            Some(domain_name) => domain_name.to_owned(),
+            None => format!("{}.{}", self.api_id, default_suffix),
+        };
+        let domain_prefix = domain_name.split('.').next().unwrap_or_default().to_owned();
+        (domain_name, domain_prefix)
+    }
+}
+
+// All payload formats share the same basic response shape
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LambdaResponse {
+    #[serde(deserialize_with = "lenient::status_code")]
+    pub status_code: u16,
+    #[serde(default, deserialize_with = "lenient::headers")]
+    pub headers: std::collections::HashMap<String, String>,
+    // Used by REST API and ALB with multi-value headers enabled
+    #[serde(default, deserialize_with = "lenient::multi_value_headers")]
+    pub multi_value_headers: std::collections::HashMap<String, Vec<String>>,
+    // Used by HTTP API and function URLs to set Set-Cookie headers
+    #[serde(default, deserialize_with = "lenient::strings")]
+    pub cookies: Vec<String>,
+    #[serde(default, deserialize_with = "lenient::body")]
+    pub body: String,
+    // Binary bodies are base64-encoded by functions
+    #[serde(default, deserialize_with = "lenient::boolean")]
+    pub is_base64_encoded: bool,
+}
+/// Error of a failed function returned by RIE
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionError {
+    pub error_message: String,
+    #[serde(default)]
+    pub error_type: Option<String>,
+    #[serde(default, deserialize_with = "lenient::strings")]
+    pub stack_trace: Vec<String>,
+}
+impl FunctionError {
+    // Results shaped like errors count even without X-Amz-Function-Error, which RIE doesn't
+    // always set
+    pub fn parse(function_error: bool, body: &[u8]) -> Option<Self> {
+        let value: serde_json::Value = serde_json::from_slice(body).unwrap_or_default();
+        let error_shaped = value.get("errorMessage").is_some_and(|m| m.is_string())
+            && (function_error || value.get("errorType").is_some());
+        if error_shaped {
+            serde_json::from_value(value).ok()
+        } else if function_error {
+            Some(Self {
+                error_message: String::from_utf8_lossy(body).into_owned(),
+                error_type: None,
+                stack_trace: Vec::new(),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl LambdaResponse {
+    // Response with a JSON body generated by the gateway instead of the function
+    pub fn json(status_code: u16, body: String) -> Self {
+        let mut headers = std::collections::HashMap::new();
+        headers.insert(
+            hyper::header::CONTENT_TYPE.as_str().to_owned(),
+            "application/json".to_owned(),
+        );
+        Self {
+            status_code,
+            headers,
+            multi_value_headers: std::collections::HashMap::new(),
+            cookies: Vec::new(),
+            body,
+            is_base64_encoded: false,
+        }
+    }
+
+    // Values of Connection headers set by the function
+    pub fn connection_header_values(&self) -> Vec<&str> {
+        let connection = hyper::header::CONNECTION.as_str();
+        self.headers
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(connection))
+            .map(|(_, v)| v.as_str())
+            .chain(
+                self.multi_value_headers
+                    .iter()
+                    .filter(|(k, _)| k.eq_ignore_ascii_case(connection))
+                    .flat_map(|(_, vs)| vs.iter().map(|v| v.as_str())),
+            )
+            .collect()
+    }
+
+    // Drop headers, including cookies, whose names are rejected by `allowed`
+    pub fn filter_headers<F>(mut self, allowed: F) -> Self
+    where
+        F: Fn(&str) -> bool,
+    {
+        self.headers.retain(|k, _| allowed(k));
+        self.multi_value_headers.retain(|k, _| allowed(k));
+        if !allowed(hyper::header::SET_COOKIE.as_str()) {
+            self.cookies.clear();
+        }
+        self
+    }
+
+    /// Keep base64 bodies as text unless they are binary like REST APIs do
+    ///
+    /// With binary media types, REST APIs decode base64 bodies only when Content-Type of the
+    /// response or Accept of the request matches one of them.
+    pub fn keep_unless_binary(
+        mut self,
+        accept: Option<&str>,
+        binary_media_types: &[String],
+    ) -> Self {
+        if !self.is_base64_encoded || binary_media_types.is_empty() {
+            return self;
+        }
+        let binary = self
+            .content_type()
+            .into_iter()
+            .chain(accept.into_iter().flat_map(|accept| accept.split(',')))
+            .any(|media_type| is_binary_media_type(media_type, binary_media_types));
+        if !binary {
+            self.is_base64_encoded = false;
+        }
+        self
+    }
+
+    /// Add charset=utf-8 to textual Content-Type of text bodies, which are always UTF-8
+    pub fn label_utf8(&mut self) {
+        if self.is_base64_encoded {
+            return;
+        }
+        let content_type = hyper::header::CONTENT_TYPE.as_str();
+        let values = self
+            .multi_value_headers
+            .iter_mut()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(content_type))
+            .flat_map(|(_, vs)| vs.iter_mut())
+            .chain(
+                self.headers
+                    .iter_mut()
+                    .filter(|(k, _)| k.eq_ignore_ascii_case(content_type))
+                    .map(|(_, v)| v),
+            );
+        for value in values {
+            let lower = value.to_ascii_lowercase();
+            if is_textual_media_type(&lower) && !lower.contains("charset=") {
+                value.push_str("; charset=utf-8");
+            }
+        }
+    }
+
+    // Content-Type set by the function, where multiValueHeaders take precedence
+    fn content_type(&self) -> Option<&str> {
+        let content_type = hyper::header::CONTENT_TYPE.as_str();
+        self.multi_value_headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(content_type))
+            .and_then(|(_, vs)| vs.last())
+            .or_else(|| {
+                self.headers
+                    .iter()
+                    .find(|(k, _)| k.eq_ignore_ascii_case(content_type))
+                    .map(|(_, v)| v)
+            })
+            .map(|v| v.as_str())
+    }
+
+    // Body bytes, decoding base64 of binary responses
+    pub fn decode_body(&self) -> Result<Vec<u8>, anyhow::Error> {
+        if self.is_base64_encoded {
+            // Tolerate line breaks inserted by some base64 encoders
+            let encoded: Vec<u8> = self
+                .body
+                .bytes()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            base64::decode(&encoded)
+                .map_err(|e| anyhow::anyhow!("invalid base64 response body: {}", e))
+        } else {
+            Ok(self.body.as_bytes().to_vec())
+        }
+    }
+}
+
+impl PayloadFormat {
+    pub fn build_event(
+        self,
+        request: &Request,
+        options: &EventOptions<'_>,
+    ) -> Result<serde_json::Value, anyhow::Error> {
+        Ok(match self {
+            Self::V1 => serde_json::to_value(v1::build(request, options))?,
+            Self::V2 => serde_json::to_value(v2::build(request, options))?,
+            Self::Alb => serde_json::to_value(alb::build(request, options))?,
+            Self::FunctionUrl => serde_json::to_value(function_url::build(request, options))?,
+            Self::EdgeViewerRequest => serde_json::to_value(lambda_edge::build(
+                request,
+                options,
+                lambda_edge::EventType::ViewerRequest,
+            ))?,
+            Self::EdgeOriginRequest => serde_json::to_value(lambda_edge::build(
+                request,
+                options,
+                lambda_edge::EventType::OriginRequest,
+            ))?,
+            // A request is resolved by an event per field
+            Self::AppSync => return Err(anyhow::anyhow!("use appsync::build_events instead")),
+        })
+    }
+
+    // Request ID headers added to every response
+    pub fn response_headers(
+        self,
+        request_id: &crate::request_id::RequestId,
+    ) -> Vec<(&'static str, &str)> {
+        match self {
+            Self::V1 => vec![
+                ("x-amzn-requestid", &request_id.uuid),
+                ("x-amz-apigw-id", &request_id.extended),
+            ],
+            Self::V2 => vec![("apigw-requestid", &request_id.extended)],
+            Self::FunctionUrl | Self::AppSync => vec![("x-amzn-requestid", &request_id.uuid)],
+            Self::Alb | Self::EdgeViewerRequest | Self::EdgeOriginRequest => Vec::new(),
+        }
+    }
+
+    // Response of failed invocations and malformed function results
+    pub fn error_response(self) -> LambdaResponse {
+        match self {
+            Self::V1 => {
+                LambdaResponse::json(502, r#"{"message":"Internal server error"}"#.to_owned())
+            }
+            Self::V2 | Self::AppSync => {
+                LambdaResponse::json(500, r#"{"message":"Internal Server Error"}"#.to_owned())
+            }
+            Self::FunctionUrl | Self::EdgeViewerRequest | Self::EdgeOriginRequest => {
+                LambdaResponse::json(502, r#"{"message":"Internal Server Error"}"#.to_owned())
+            }
+            Self::Alb => {
+                let mut response = LambdaResponse::json(
+                    502,
+                    "<html>\r\n<head><title>502 Bad Gateway</title></head>\r\n<body>\r\n<center><h1>502 Bad Gateway</h1></center>\r\n</body>\r\n</html>\r\n".to_owned(),
+                );
+                response.headers.insert(
+                    hyper::header::CONTENT_TYPE.as_str().to_owned(),
+                    "text/html".to_owned(),
+                );
+                response
+            }
+        }
+    }
+
+    // Maximum integration timeout of the emulated service, which function URLs don't have
+    pub fn default_timeout_millis(self) -> u64 {
+        match self {
+            Self::FunctionUrl => 900_000,
+            _ => 29_000,
+        }
+    }
+
+    // Response of requests matching with no resource
+    pub fn missing_resource_response(self) -> LambdaResponse {
+        match self {
+            // REST APIs don't tell missing resources from unauthenticated requests
+            Self::V1 => LambdaResponse::json(
+                403,
+                r#"{"message":"Missing Authentication Token"}"#.to_owned(),
+            ),
+            _ => LambdaResponse::json(404, r#"{"message":"Not Found"}"#.to_owned()),
+        }
+    }
+
+    // Response of invocations exceeding the integration timeout
+    pub fn timeout_response(self) -> LambdaResponse {
+        match self {
+            // HTTP APIs respond with 503 instead
+            Self::V2 => {
+                LambdaResponse::json(503, r#"{"message":"Service Unavailable"}"#.to_owned())
+            }
+            Self::Alb => {
+                let mut response = LambdaResponse::json(
+                    504,
+                    "<html>\r\n<head><title>504 Gateway Time-out</title></head>\r\n<body>\r\n<center><h1>504 Gateway Time-out</h1></center>\r\n</body>\r\n</html>\r\n".to_owned(),
+                );
+                response.headers.insert(
+                    hyper::header::CONTENT_TYPE.as_str().to_owned(),
+                    "text/html".to_owned(),
+                );
+                response
+            }
+            _ => LambdaResponse::json(
+                504,
+                r#"{"message":"Endpoint request timed out"}"#.to_owned(),
+            ),
+        }
+    }
+
+    // Problems of the function result in the response format, if any
+    pub fn validate_response(self, body: &[u8]) -> Vec<String> {
+        validate::response(self, body)
+    }
+
+    pub fn parse_response(self, body: &[u8]) -> Result<LambdaResponse, anyhow::Error> {
+        match self {
+            Self::V2 | Self::FunctionUrl => v2::parse_response(body),
+            Self::EdgeViewerRequest | Self::EdgeOriginRequest => lambda_edge::parse_response(body),
+            Self::AppSync => Err(anyhow::anyhow!("use appsync::build_response instead")),
+            Self::V1 | Self::Alb => Ok(serde_json::from_slice(body)?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const BOUNDARY: &str = "----boundary";
+
+    // multipart/form-data body with a text part and a file part which isn't valid UTF-8
+    fn multipart_body() -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nhello\r\n",
+                BOUNDARY
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+                BOUNDARY
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(&[0x00, 0xff, 0xfe, 0x80, 0xc3, 0x28, b'\r', b'\n']);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+        body
+    }
+
+    fn request(body: &[u8]) -> super::Request {
+        let (parts, ()) = hyper::Request::builder()
+            .method(hyper::Method::POST)
+            .uri("/upload")
+            .body(())
+            .unwrap()
+            .into_parts();
+        super::Request {
+            parts,
+            headers: vec![(
+                "content-type".to_owned(),
+                format!("multipart/form-data; boundary={}", BOUNDARY),
+            )],
+            body: bytes::Bytes::copy_from_slice(body),
+        }
+    }
+
+    fn event(request: &super::Request, binary_media_types: &[String]) -> serde_json::Value {
+        let request_id = crate::request_id::Generator::new(Some(0)).generate();
+        let options = super::EventOptions {
+            alb_target_group_arn: "",
+            alb_multi_value_headers: false,
+            resource: None,
+            stage: "$default",
+            stage_variables: None,
+            path: "/upload",
+            account_id: "123456789012",
+            api_id: "1234567890",
+            request_id: &request_id,
+            time: chrono::Utc::now(),
+            source_ip: "127.0.0.1",
+            client_cert: None,
+            cognito_identity_id: None,
+            cognito_identity_pool_id: None,
+            body_encoding: super::BodyEncoding::Auto,
+            binary_media_types,
+        };
+        super::PayloadFormat::V1
+            .build_event(request, &options)
+            .unwrap()
+    }
+
+    #[test]
+    fn multipart_body_is_base64_encoded() {
+        let body = multipart_body();
+        let event = event(&request(&body), &["image/png".to_owned()]);
+        assert_eq!(event["isBase64Encoded"], true);
+        let decoded = base64::decode(event["body"].as_str().unwrap()).unwrap();
+        assert_eq!(decoded, body);
+    }
+
+    #[test]
+    fn multipart_body_without_binary_media_types_is_base64_encoded() {
+        let body = multipart_body();
+        let event = event(&request(&body), &[]);
+        assert_eq!(event["isBase64Encoded"], true);
+        let decoded = base64::decode(event["body"].as_str().unwrap()).unwrap();
+        assert_eq!(decoded, body);
+    }
+
+    #[test]
+    fn v2_string_result_is_body() {
+        let response = super::v2::parse_response(br#""hello""#).unwrap();
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.body, "hello");
+    }
+
+    #[test]
+    fn v2_array_result_is_json_body() {
+        let response = super::v2::parse_response(br#"[1, "a"]"#).unwrap();
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.body, r#"[1, "a"]"#);
+    }
+}
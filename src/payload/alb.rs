@@ -0,0 +1,64 @@
+// https://docs.aws.amazon.com/elasticloadbalancing/latest/application/lambda-functions.html#receive-event-from-load-balancer
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlbTargetGroupRequest<'a> {
+    request_context: AlbTargetGroupRequestContext<'a>,
+    http_method: String,
+    path: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query_string_parameters: Option<indexmap::IndexMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    multi_value_query_string_parameters: Option<indexmap::IndexMap<String, Vec<String>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    headers: Option<indexmap::IndexMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    multi_value_headers: Option<indexmap::IndexMap<String, Vec<String>>>,
+    body: String,
+    is_base64_encoded: bool,
+}
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AlbTargetGroupRequestContext<'a> {
+    elb: AlbTargetGroupRequestContextElb<'a>,
+}
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AlbTargetGroupRequestContextElb<'a> {
+    target_group_arn: &'a str,
+}
+
+pub fn build<'a>(
+    request: &'a super::Request,
+    options: &super::EventOptions<'a>,
+) -> AlbTargetGroupRequest<'a> {
+    // ALB passes query string parameters as is without URL-decoding them
+    let query_pairs: Vec<(String, String)> = request
+        .raw_query_pairs()
+        .into_iter()
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .collect();
+    let (body, is_base64_encoded) = request.encode_body(options);
+    let mut payload = AlbTargetGroupRequest {
+        request_context: AlbTargetGroupRequestContext {
+            elb: AlbTargetGroupRequestContextElb {
+                target_group_arn: options.alb_target_group_arn,
+            },
+        },
+        http_method: format!("{}", request.parts.method),
+        path: request.parts.uri.path(),
+        query_string_parameters: None,
+        multi_value_query_string_parameters: None,
+        headers: None,
+        multi_value_headers: None,
+        body: body.unwrap_or_default(),
+        is_base64_encoded,
+    };
+    if options.alb_multi_value_headers {
+        payload.multi_value_headers = Some(super::multi_value_map(request.headers.iter().cloned()));
+        payload.multi_value_query_string_parameters = Some(super::multi_value_map(query_pairs));
+    } else {
+        payload.headers = Some(request.headers.iter().cloned().collect());
+        payload.query_string_parameters = Some(query_pairs.into_iter().collect());
+    }
+    payload
+}
@@ -0,0 +1,297 @@
+// https://docs.aws.amazon.com/appsync/latest/devguide/resolver-context-reference.html
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AppSyncResolverEvent<'a> {
+    arguments: serde_json::Map<String, serde_json::Value>,
+    identity: Option<serde_json::Value>,
+    source: Option<serde_json::Value>,
+    request: AppSyncResolverEventRequest<'a>,
+    info: AppSyncResolverEventInfo<'a>,
+    prev: Option<serde_json::Value>,
+    stash: serde_json::Map<String, serde_json::Value>,
+}
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AppSyncResolverEventRequest<'a> {
+    headers: indexmap::IndexMap<String, String>,
+    domain_name: Option<&'a str>,
+}
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AppSyncResolverEventInfo<'a> {
+    selection_set_list: Vec<String>,
+    #[serde(rename = "selectionSetGraphQL")]
+    selection_set_graphql: String,
+    parent_type_name: &'a str,
+    field_name: &'a str,
+    variables: &'a serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphQlRequest {
+    query: String,
+    #[serde(default)]
+    operation_name: Option<String>,
+    #[serde(default)]
+    variables: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+type SelectionSet<'a> = graphql_parser::query::SelectionSet<'a, String>;
+type Fragments<'a> = std::collections::HashMap<&'a str, &'a SelectionSet<'a>>;
+
+/// Builds a resolver event for each top-level field of the GraphQL operation in the request
+/// body, paired with the response key of the field, with the identity of the caller if any
+pub fn build_events(
+    request: &super::Request,
+    identity: Option<&serde_json::Value>,
+) -> Result<Vec<(String, serde_json::Value)>, anyhow::Error> {
+    if request.parts.method != hyper::Method::POST {
+        return Err(anyhow::anyhow!("GraphQL operations must be POSTed"));
+    }
+    let body: GraphQlRequest = serde_json::from_slice(&request.body)?;
+    let variables = body.variables.unwrap_or_default();
+    let document = graphql_parser::parse_query::<String>(&body.query)?;
+
+    let mut fragments = Fragments::new();
+    let mut operations = Vec::new();
+    for definition in &document.definitions {
+        match definition {
+            graphql_parser::query::Definition::Fragment(fragment) => {
+                fragments.insert(&fragment.name, &fragment.selection_set);
+            }
+            graphql_parser::query::Definition::Operation(operation) => {
+                operations.push(match operation {
+                    graphql_parser::query::OperationDefinition::SelectionSet(s) => {
+                        (None, "Query", s)
+                    }
+                    graphql_parser::query::OperationDefinition::Query(q) => {
+                        (q.name.as_deref(), "Query", &q.selection_set)
+                    }
+                    graphql_parser::query::OperationDefinition::Mutation(m) => {
+                        (m.name.as_deref(), "Mutation", &m.selection_set)
+                    }
+                    graphql_parser::query::OperationDefinition::Subscription(s) => {
+                        (s.name.as_deref(), "Subscription", &s.selection_set)
+                    }
+                });
+            }
+        }
+    }
+    let (_, parent_type_name, selection_set) = match body.operation_name.as_deref() {
+        Some(name) => operations
+            .into_iter()
+            .find(|(n, _, _)| *n == Some(name))
+            .ok_or_else(|| anyhow::anyhow!("unknown operation: {}", name))?,
+        None if operations.len() == 1 => operations.remove(0),
+        None => {
+            return Err(anyhow::anyhow!(
+                "operationName is required for multiple operations"
+            ))
+        }
+    };
+
+    let mut fields = Vec::new();
+    top_level_fields(selection_set, &fragments, &mut Vec::new(), &mut fields)?;
+    let headers: indexmap::IndexMap<String, String> = request.headers.iter().cloned().collect();
+    fields
+        .into_iter()
+        .map(|field| {
+            let mut selection_set_list = Vec::new();
+            collect_selection_set_list(
+                &field.selection_set,
+                &fragments,
+                &mut Vec::new(),
+                "",
+                &mut selection_set_list,
+            )?;
+            let event = AppSyncResolverEvent {
+                arguments: field
+                    .arguments
+                    .iter()
+                    .map(|(k, v)| (k.clone(), to_json(v, &variables)))
+                    .collect(),
+                identity: identity.cloned(),
+                source: None,
+                request: AppSyncResolverEventRequest {
+                    headers: headers.clone(),
+                    domain_name: None,
+                },
+                info: AppSyncResolverEventInfo {
+                    selection_set_list,
+                    selection_set_graphql: if field.selection_set.items.is_empty() {
+                        String::new()
+                    } else {
+                        field.selection_set.to_string().trim_end().to_owned()
+                    },
+                    parent_type_name,
+                    field_name: &field.name,
+                    variables: &variables,
+                },
+                prev: None,
+                stash: serde_json::Map::new(),
+            };
+            Ok((
+                field.alias.clone().unwrap_or_else(|| field.name.clone()),
+                serde_json::to_value(event)?,
+            ))
+        })
+        .collect()
+}
+
+// Fragments spread into the selection set, whose names are pushed to the spreading fragments
+// so that fragments spreading themselves are rejected instead of recursing forever
+fn spread<'a>(
+    fragments: &Fragments<'a>,
+    spreading: &mut Vec<&'a str>,
+    name: &'a str,
+) -> Result<Option<&'a SelectionSet<'a>>, anyhow::Error> {
+    if spreading.contains(&name) {
+        return Err(anyhow::anyhow!("fragment {} spreads itself", name));
+    }
+    let selection_set = fragments.get(name).copied();
+    if selection_set.is_some() {
+        spreading.push(name);
+    }
+    Ok(selection_set)
+}
+
+fn top_level_fields<'a>(
+    selection_set: &'a SelectionSet<'a>,
+    fragments: &Fragments<'a>,
+    spreading: &mut Vec<&'a str>,
+    fields: &mut Vec<&'a graphql_parser::query::Field<'a, String>>,
+) -> Result<(), anyhow::Error> {
+    for item in &selection_set.items {
+        match item {
+            graphql_parser::query::Selection::Field(field) => fields.push(field),
+            graphql_parser::query::Selection::FragmentSpread(s) => {
+                if let Some(s) = spread(fragments, spreading, &s.fragment_name)? {
+                    top_level_fields(s, fragments, spreading, fields)?;
+                    spreading.pop();
+                }
+            }
+            graphql_parser::query::Selection::InlineFragment(fragment) => {
+                top_level_fields(&fragment.selection_set, fragments, spreading, fields)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// selectionSetList lists paths of selected fields like ["id", "author", "author/name"]
+fn collect_selection_set_list<'a>(
+    selection_set: &'a SelectionSet<'a>,
+    fragments: &Fragments<'a>,
+    spreading: &mut Vec<&'a str>,
+    prefix: &str,
+    list: &mut Vec<String>,
+) -> Result<(), anyhow::Error> {
+    for item in &selection_set.items {
+        match item {
+            graphql_parser::query::Selection::Field(field) => {
+                let path = format!("{}{}", prefix, field.name);
+                list.push(path.clone());
+                collect_selection_set_list(
+                    &field.selection_set,
+                    fragments,
+                    spreading,
+                    &format!("{}/", path),
+                    list,
+                )?;
+            }
+            graphql_parser::query::Selection::FragmentSpread(s) => {
+                if let Some(s) = spread(fragments, spreading, &s.fragment_name)? {
+                    collect_selection_set_list(s, fragments, spreading, prefix, list)?;
+                    spreading.pop();
+                }
+            }
+            graphql_parser::query::Selection::InlineFragment(fragment) => {
+                collect_selection_set_list(
+                    &fragment.selection_set,
+                    fragments,
+                    spreading,
+                    prefix,
+                    list,
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn to_json(
+    value: &graphql_parser::query::Value<'_, String>,
+    variables: &serde_json::Map<String, serde_json::Value>,
+) -> serde_json::Value {
+    match value {
+        graphql_parser::query::Value::Variable(name) => {
+            variables.get(name).cloned().unwrap_or_default()
+        }
+        graphql_parser::query::Value::Int(n) => n.as_i64().map(Into::into).unwrap_or_default(),
+        graphql_parser::query::Value::Float(f) => serde_json::Number::from_f64(*f)
+            .map(serde_json::Value::Number)
+            .unwrap_or_default(),
+        graphql_parser::query::Value::String(s) | graphql_parser::query::Value::Enum(s) => {
+            serde_json::Value::String(s.clone())
+        }
+        graphql_parser::query::Value::Boolean(b) => serde_json::Value::Bool(*b),
+        graphql_parser::query::Value::Null => serde_json::Value::Null,
+        graphql_parser::query::Value::List(values) => {
+            values.iter().map(|v| to_json(v, variables)).collect()
+        }
+        graphql_parser::query::Value::Object(fields) => serde_json::Value::Object(
+            fields
+                .iter()
+                .map(|(k, v)| (k.clone(), to_json(v, variables)))
+                .collect(),
+        ),
+    }
+}
+
+/// Builds a GraphQL response from results of functions resolving each field
+pub fn build_response(
+    results: Vec<(String, Result<serde_json::Value, super::FunctionError>)>,
+) -> Result<super::LambdaResponse, anyhow::Error> {
+    let mut data = serde_json::Map::new();
+    let mut errors = Vec::new();
+    for (key, result) in results {
+        match result {
+            Ok(value) => {
+                data.insert(key, value);
+            }
+            Err(e) => {
+                errors.push(serde_json::json!({
+                    "path": [key],
+                    "data": null,
+                    "errorType": e.error_type,
+                    "errorInfo": null,
+                    "message": e.error_message,
+                }));
+                data.insert(key, serde_json::Value::Null);
+            }
+        }
+    }
+    let mut body = serde_json::json!({ "data": data });
+    if !errors.is_empty() {
+        body["errors"] = serde_json::Value::Array(errors);
+    }
+    Ok(super::LambdaResponse::json(
+        200,
+        serde_json::to_string(&body)?,
+    ))
+}
+
+/// Response of requests that can't be resolved, e.g. because of invalid GraphQL
+pub fn error_response(error: &anyhow::Error) -> Result<super::LambdaResponse, anyhow::Error> {
+    let body = serde_json::json!({
+        "errors": [{
+            "errorType": "MalformedHttpRequestException",
+            "message": error.to_string(),
+        }],
+    });
+    Ok(super::LambdaResponse::json(
+        400,
+        serde_json::to_string(&body)?,
+    ))
+}
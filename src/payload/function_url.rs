@@ -0,0 +1,21 @@
+// https://docs.aws.amazon.com/lambda/latest/dg/urls-invocation.html#urls-request-payload
+pub fn build<'a>(
+    request: &'a super::Request,
+    options: &super::EventOptions<'a>,
+) -> super::v2::ApiGatewayV2LambdaProxyIntegrationV2<'a> {
+    // Function URLs have neither routes, stages nor mutual TLS
+    let options = super::EventOptions {
+        resource: None,
+        stage: "$default",
+        stage_variables: None,
+        path: request.parts.uri.path(),
+        client_cert: None,
+        ..*options
+    };
+    let mut payload = super::v2::build(request, &options);
+    let (domain_name, domain_prefix) =
+        options.domain_name_and_prefix(request, "lambda-url.us-east-1.on.aws");
+    payload.request_context.domain_name = domain_name;
+    payload.request_context.domain_prefix = domain_prefix;
+    payload
+}
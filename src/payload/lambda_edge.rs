@@ -0,0 +1,191 @@
+// https://docs.aws.amazon.com/AmazonCloudFront/latest/DeveloperGuide/lambda-event-structure.html
+#[derive(Debug, serde::Serialize)]
+pub struct CloudFrontEvent<'a> {
+    #[serde(rename = "Records")]
+    records: Vec<CloudFrontEventRecord<'a>>,
+}
+#[derive(Debug, serde::Serialize)]
+struct CloudFrontEventRecord<'a> {
+    cf: CloudFrontEventCf<'a>,
+}
+#[derive(Debug, serde::Serialize)]
+struct CloudFrontEventCf<'a> {
+    config: CloudFrontEventConfig<'a>,
+    request: CloudFrontEventRequest<'a>,
+}
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CloudFrontEventConfig<'a> {
+    distribution_domain_name: &'a str,
+    distribution_id: &'a str,
+    event_type: &'a str,
+    request_id: &'a str,
+}
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CloudFrontEventRequest<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<CloudFrontEventBody>,
+    client_ip: &'a str,
+    headers: indexmap::IndexMap<String, Vec<CloudFrontHeader>>,
+    method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    origin: Option<CloudFrontEventOrigin<'a>>,
+    querystring: &'a str,
+    uri: &'a str,
+}
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CloudFrontEventBody {
+    input_truncated: bool,
+    action: &'static str,
+    encoding: &'static str,
+    data: String,
+}
+#[derive(Debug, serde::Serialize)]
+struct CloudFrontEventOrigin<'a> {
+    custom: CloudFrontEventCustomOrigin<'a>,
+}
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CloudFrontEventCustomOrigin<'a> {
+    custom_headers: std::collections::HashMap<String, Vec<CloudFrontHeader>>,
+    domain_name: &'a str,
+    keepalive_timeout: u32,
+    path: &'a str,
+    port: u16,
+    protocol: &'a str,
+    read_timeout: u32,
+    ssl_protocols: Vec<&'a str>,
+}
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct CloudFrontHeader {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    value: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventType {
+    ViewerRequest,
+    OriginRequest,
+}
+
+pub fn build<'a>(
+    request: &'a super::Request,
+    options: &super::EventOptions<'a>,
+    event_type: EventType,
+) -> CloudFrontEvent<'a> {
+    // CloudFront keys headers by lowercase names and keeps the original names in `key`
+    let mut headers: indexmap::IndexMap<String, Vec<CloudFrontHeader>> = indexmap::IndexMap::new();
+    for (k, v) in &request.headers {
+        headers
+            .entry(k.clone())
+            .or_default()
+            .push(CloudFrontHeader {
+                key: Some(title_case(k)),
+                value: v.clone(),
+            });
+    }
+    let origin = match event_type {
+        EventType::ViewerRequest => None,
+        EventType::OriginRequest => Some(CloudFrontEventOrigin {
+            custom: CloudFrontEventCustomOrigin {
+                custom_headers: std::collections::HashMap::new(),
+                domain_name: request.domain_name().unwrap_or("example.org"),
+                keepalive_timeout: 5,
+                path: "",
+                port: 443,
+                protocol: "https",
+                read_timeout: 30,
+                ssl_protocols: vec!["TLSv1.2"],
+            },
+        }),
+    };
+    // Bodies are exposed to functions as base64 like the "include body" option of CloudFront
+    let body = if request.body.is_empty() {
+        None
+    } else {
+        Some(CloudFrontEventBody {
+            input_truncated: false,
+            action: "read-only",
+            encoding: "base64",
+            data: base64::encode(&request.body),
+        })
+    };
+    CloudFrontEvent {
+        records: vec![CloudFrontEventRecord {
+            cf: CloudFrontEventCf {
+                config: CloudFrontEventConfig {
+                    distribution_domain_name: "d111111abcdef8.cloudfront.net",
+                    distribution_id: "EDFDVBD6EXAMPLE",
+                    event_type: match event_type {
+                        EventType::ViewerRequest => "viewer-request",
+                        EventType::OriginRequest => "origin-request",
+                    },
+                    request_id: &options.request_id.extended,
+                },
+                request: CloudFrontEventRequest {
+                    body,
+                    client_ip: options.source_ip,
+                    headers,
+                    method: format!("{}", request.parts.method),
+                    origin,
+                    querystring: request.parts.uri.query().unwrap_or(""),
+                    uri: request.parts.uri.path(),
+                },
+            },
+        }],
+    }
+}
+
+fn title_case(name: &str) -> String {
+    name.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(c) => c.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// https://docs.aws.amazon.com/AmazonCloudFront/latest/DeveloperGuide/lambda-generating-http-responses.html
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CloudFrontResponse {
+    status: String,
+    #[serde(default)]
+    headers: std::collections::HashMap<String, Vec<CloudFrontHeader>>,
+    #[serde(default)]
+    body: String,
+    #[serde(default)]
+    body_encoding: Option<String>,
+}
+
+pub fn parse_response(body: &[u8]) -> Result<super::LambdaResponse, anyhow::Error> {
+    let value: serde_json::Value = serde_json::from_slice(body)?;
+    if value.get("status").is_none() {
+        // Functions returning the request let CloudFront forward it to the origin. There's no
+        // origin here, so the forwarded request is shown instead
+        return Ok(super::LambdaResponse::json(
+            200,
+            serde_json::to_string(&value)?,
+        ));
+    }
+    let response: CloudFrontResponse = serde_json::from_value(value)?;
+    Ok(super::LambdaResponse {
+        status_code: response.status.parse()?,
+        headers: std::collections::HashMap::new(),
+        multi_value_headers: response
+            .headers
+            .into_iter()
+            .map(|(k, vs)| (k, vs.into_iter().map(|h| h.value).collect()))
+            .collect(),
+        cookies: Vec::new(),
+        body: response.body,
+        is_base64_encoded: response.body_encoding.as_deref() == Some("base64"),
+    })
+}
@@ -0,0 +1,92 @@
+// Functions written in dynamic languages often return loosely typed responses, which API Gateway
+// coerces instead of rejecting
+use serde::Deserialize as _;
+use std::convert::TryFrom as _;
+
+// Text of scalar values, or JSON of arrays and objects. null has no text
+fn to_text(value: serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(s) => Some(s),
+        other => Some(other.to_string()),
+    }
+}
+
+pub fn status_code<'de, D>(deserializer: D) -> Result<u16, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    let status_code = match &value {
+        serde_json::Value::Number(n) => n.as_u64(),
+        serde_json::Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    };
+    status_code
+        .and_then(|n| u16::try_from(n).ok())
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid statusCode: {}", value)))
+}
+
+pub fn headers<'de, D>(
+    deserializer: D,
+) -> Result<std::collections::HashMap<String, String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let headers =
+        Option::<std::collections::HashMap<String, serde_json::Value>>::deserialize(deserializer)?;
+    Ok(headers
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(k, v)| to_text(v).map(|v| (k, v)))
+        .collect())
+}
+
+pub fn multi_value_headers<'de, D>(
+    deserializer: D,
+) -> Result<std::collections::HashMap<String, Vec<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let headers =
+        Option::<std::collections::HashMap<String, serde_json::Value>>::deserialize(deserializer)?;
+    Ok(headers
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(k, v)| (k, values(v)))
+        .collect())
+}
+
+pub fn strings<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(values(serde_json::Value::deserialize(deserializer)?))
+}
+
+// A single value is treated as an array of the value
+fn values(value: serde_json::Value) -> Vec<String> {
+    match value {
+        serde_json::Value::Array(values) => values.into_iter().filter_map(to_text).collect(),
+        other => to_text(other).into_iter().collect(),
+    }
+}
+
+// Non-string bodies are sent as JSON
+pub fn body<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(to_text(serde_json::Value::deserialize(deserializer)?).unwrap_or_default())
+}
+
+pub fn boolean<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Bool(b) => b,
+        serde_json::Value::String(s) => s.eq_ignore_ascii_case("true"),
+        _ => false,
+    })
+}
@@ -0,0 +1,123 @@
+// https://docs.aws.amazon.com/apigateway/latest/developerguide/set-up-lambda-proxy-integrations.html#api-gateway-simple-proxy-for-lambda-input-format
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGatewayLambdaProxyIntegrationV1<'a> {
+    resource: &'a str,
+    path: &'a str,
+    http_method: String,
+    headers: indexmap::IndexMap<String, String>,
+    multi_value_headers: indexmap::IndexMap<String, Vec<String>>,
+    query_string_parameters: Option<indexmap::IndexMap<String, String>>,
+    multi_value_query_string_parameters: Option<indexmap::IndexMap<String, Vec<String>>>,
+    path_parameters: Option<std::collections::HashMap<String, String>>,
+    stage_variables: Option<std::collections::HashMap<String, String>>,
+    request_context: ApiGatewayLambdaProxyIntegrationV1RequestContext<'a>,
+    body: Option<String>,
+    is_base64_encoded: bool,
+}
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiGatewayLambdaProxyIntegrationV1RequestContext<'a> {
+    account_id: &'a str,
+    api_id: &'a str,
+    domain_name: String,
+    domain_prefix: String,
+    extended_request_id: &'a str,
+    http_method: String,
+    identity: ApiGatewayLambdaProxyIntegrationV1RequestContextIdentity<'a>,
+    path: &'a str,
+    protocol: String,
+    request_id: &'a str,
+    request_time: String,
+    request_time_epoch: i64,
+    resource_path: &'a str,
+    stage: &'a str,
+}
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiGatewayLambdaProxyIntegrationV1RequestContextIdentity<'a> {
+    access_key: Option<String>,
+    account_id: Option<String>,
+    caller: Option<String>,
+    client_cert: Option<&'a crate::tls::ClientCert>,
+    cognito_authentication_provider: Option<String>,
+    cognito_authentication_type: Option<String>,
+    cognito_identity_id: Option<&'a str>,
+    cognito_identity_pool_id: Option<&'a str>,
+    principal_org_id: Option<String>,
+    source_ip: &'a str,
+    user: Option<String>,
+    user_agent: Option<&'a str>,
+    user_arn: Option<String>,
+}
+
+pub fn build<'a>(
+    request: &'a super::Request,
+    options: &super::EventOptions<'a>,
+) -> ApiGatewayLambdaProxyIntegrationV1<'a> {
+    // REST API keeps the last value in single-value maps and every value in multi-value maps
+    let headers = request.headers.iter().cloned().collect();
+    let multi_value_headers = super::multi_value_map(request.headers.iter().cloned());
+    let query_pairs = request.query_pairs();
+    let (query_string_parameters, multi_value_query_string_parameters) = if query_pairs.is_empty() {
+        (None, None)
+    } else {
+        (
+            Some(query_pairs.iter().cloned().collect()),
+            Some(super::multi_value_map(query_pairs)),
+        )
+    };
+    let parts = &request.parts;
+    let resource = options.resource;
+    let resource_path = resource
+        .map(|r| r.template.as_str())
+        .unwrap_or(options.path);
+    let (body, is_base64_encoded) = request.encode_body(options);
+    let (domain_name, domain_prefix) =
+        options.domain_name_and_prefix(request, "execute-api.us-east-1.amazonaws.com");
+    ApiGatewayLambdaProxyIntegrationV1 {
+        resource: resource_path,
+        path: options.path,
+        http_method: format!("{}", parts.method),
+        headers,
+        multi_value_headers,
+        query_string_parameters,
+        multi_value_query_string_parameters,
+        path_parameters: resource
+            .filter(|r| !r.path_parameters.is_empty())
+            .map(|r| r.path_parameters.clone()),
+        stage_variables: options.stage_variables.cloned(),
+        request_context: ApiGatewayLambdaProxyIntegrationV1RequestContext {
+            account_id: options.account_id,
+            api_id: options.api_id,
+            domain_name,
+            domain_prefix,
+            extended_request_id: &options.request_id.extended,
+            http_method: format!("{}", parts.method),
+            identity: ApiGatewayLambdaProxyIntegrationV1RequestContextIdentity {
+                access_key: None,
+                account_id: None,
+                caller: None,
+                client_cert: options.client_cert,
+                cognito_authentication_provider: None,
+                cognito_authentication_type: None,
+                cognito_identity_id: options.cognito_identity_id,
+                cognito_identity_pool_id: options.cognito_identity_pool_id,
+                principal_org_id: None,
+                source_ip: options.source_ip,
+                user: None,
+                user_agent: request.header(&hyper::header::USER_AGENT),
+                user_arn: None,
+            },
+            path: parts.uri.path(),
+            protocol: format!("{:?}", parts.version),
+            request_id: &options.request_id.uuid,
+            request_time: options.request_time(),
+            request_time_epoch: options.time.timestamp_millis(),
+            resource_path,
+            stage: options.stage,
+        },
+        body,
+        is_base64_encoded,
+    }
+}
@@ -0,0 +1,172 @@
+// https://docs.aws.amazon.com/apigateway/latest/developerguide/http-api-develop-integrations-lambda.html
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGatewayV2LambdaProxyIntegrationV2<'a> {
+    version: &'a str,
+    route_key: String,
+    raw_path: &'a str,
+    raw_query_string: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cookies: Option<Vec<String>>,
+    headers: indexmap::IndexMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query_string_parameters: Option<indexmap::IndexMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path_parameters: Option<std::collections::HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stage_variables: Option<std::collections::HashMap<String, String>>,
+    pub request_context: ApiGatewayV2LambdaProxyIntegrationV2RequestContext<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    is_base64_encoded: bool,
+}
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGatewayV2LambdaProxyIntegrationV2RequestContext<'a> {
+    account_id: &'a str,
+    api_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    authentication: Option<ApiGatewayV2LambdaProxyIntegrationV2RequestContextAuthentication<'a>>,
+    pub domain_name: String,
+    pub domain_prefix: String,
+    http: ApiGatewayV2LambdaProxyIntegrationV2RequestContextHttp<'a>,
+    request_id: &'a str,
+    route_key: String,
+    stage: &'a str,
+    time: String,
+    time_epoch: i64,
+}
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiGatewayV2LambdaProxyIntegrationV2RequestContextAuthentication<'a> {
+    client_cert: &'a crate::tls::ClientCert,
+}
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiGatewayV2LambdaProxyIntegrationV2RequestContextHttp<'a> {
+    method: String,
+    path: &'a str,
+    protocol: String,
+    source_ip: &'a str,
+    user_agent: &'a str,
+}
+
+pub fn build<'a>(
+    request: &'a super::Request,
+    options: &super::EventOptions<'a>,
+) -> ApiGatewayV2LambdaProxyIntegrationV2<'a> {
+    // Payload format 2.0 joins duplicated query string parameters and headers with commas
+    let mut headers: indexmap::IndexMap<String, String> = indexmap::IndexMap::new();
+    let mut cookies = Vec::new();
+    for (k, v) in &request.headers {
+        // Cookies are moved to the cookies field instead of headers
+        if k == hyper::header::COOKIE.as_str() {
+            cookies.extend(
+                v.split(';')
+                    .map(|c| c.trim())
+                    .filter(|c| !c.is_empty())
+                    .map(|c| c.to_owned()),
+            );
+            continue;
+        }
+        headers
+            .entry(k.clone())
+            .and_modify(|e| {
+                e.push(',');
+                e.push_str(v);
+            })
+            .or_insert_with(|| v.clone());
+    }
+    let parts = &request.parts;
+    // rawPath and rawQueryString are taken from the request-target as is, keeping
+    // percent-encoding, `+` and trailing slashes
+    let raw_query_string = parts.uri.query().unwrap_or("");
+    let query_string_parameters = if !raw_query_string.is_empty() {
+        let mut params: indexmap::IndexMap<String, String> = indexmap::IndexMap::new();
+        for (k, v) in request.query_pairs() {
+            params
+                .entry(k)
+                .and_modify(|e| {
+                    e.push(',');
+                    e.push_str(&v);
+                })
+                .or_insert(v);
+        }
+        Some(params)
+    } else {
+        None
+    };
+    // Unlike REST API, rawPath and requestContext.http.path keep the stage prefix
+    let resource = options.resource;
+    let route_key = match resource {
+        Some(r) => format!(
+            "{} {}",
+            r.method.as_ref().map_or("ANY", |m| m.as_str()),
+            r.template
+        ),
+        None => "$default".to_owned(),
+    };
+    let (body, is_base64_encoded) = request.encode_body(options);
+    let (domain_name, domain_prefix) =
+        options.domain_name_and_prefix(request, "execute-api.us-east-1.amazonaws.com");
+    ApiGatewayV2LambdaProxyIntegrationV2 {
+        version: "2.0",
+        route_key: route_key.clone(),
+        raw_path: parts.uri.path(),
+        raw_query_string,
+        cookies: if cookies.is_empty() {
+            None
+        } else {
+            Some(cookies)
+        },
+        headers,
+        query_string_parameters,
+        path_parameters: resource
+            .filter(|r| !r.path_parameters.is_empty())
+            .map(|r| r.path_parameters.clone()),
+        stage_variables: options.stage_variables.cloned(),
+        request_context: ApiGatewayV2LambdaProxyIntegrationV2RequestContext {
+            account_id: options.account_id,
+            api_id: options.api_id,
+            authentication: options.client_cert.map(|client_cert| {
+                ApiGatewayV2LambdaProxyIntegrationV2RequestContextAuthentication { client_cert }
+            }),
+            domain_name,
+            domain_prefix,
+            http: ApiGatewayV2LambdaProxyIntegrationV2RequestContextHttp {
+                method: format!("{}", parts.method),
+                path: parts.uri.path(),
+                protocol: format!("{:?}", parts.version),
+                source_ip: options.source_ip,
+                user_agent: request
+                    .header(&hyper::header::USER_AGENT)
+                    .unwrap_or_default(),
+            },
+            request_id: &options.request_id.extended,
+            route_key,
+            stage: options.stage,
+            time: options.request_time(),
+            time_epoch: options.time.timestamp_millis(),
+        },
+        body,
+        is_base64_encoded,
+    }
+}
+
+// https://docs.aws.amazon.com/apigateway/latest/developerguide/http-api-develop-integrations-lambda.html#http-api-develop-integrations-lambda.response
+// Function URLs infer responses in the same way
+pub fn parse_response(body: &[u8]) -> Result<super::LambdaResponse, anyhow::Error> {
+    let value: serde_json::Value = serde_json::from_slice(body)?;
+    if value.get("statusCode").is_some() {
+        Ok(serde_json::from_value(value)?)
+    } else if let serde_json::Value::String(body) = value {
+        // Strings returned by the function are bodies without their quotes
+        Ok(super::LambdaResponse::json(200, body))
+    } else {
+        // Any other JSON returned by the function is the body of a 200 JSON response
+        Ok(super::LambdaResponse::json(
+            200,
+            String::from_utf8_lossy(body).into_owned(),
+        ))
+    }
+}
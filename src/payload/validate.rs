@@ -0,0 +1,122 @@
+// Checks a function result against the response format, returning what is malformed
+pub fn response(format: super::PayloadFormat, body: &[u8]) -> Vec<String> {
+    let value: serde_json::Value = match serde_json::from_slice(body) {
+        Ok(value) => value,
+        Err(e) => return vec![format!("response is not JSON: {}", e)],
+    };
+    let allowed_fields: &[&str] = match format {
+        super::PayloadFormat::V1 => &[
+            "statusCode",
+            "headers",
+            "multiValueHeaders",
+            "body",
+            "isBase64Encoded",
+        ],
+        super::PayloadFormat::V2 | super::PayloadFormat::FunctionUrl => {
+            // Results without statusCode are inferred as bodies of 200 responses
+            if value.get("statusCode").is_none() {
+                return Vec::new();
+            }
+            &[
+                "statusCode",
+                "headers",
+                "cookies",
+                "body",
+                "isBase64Encoded",
+            ]
+        }
+        super::PayloadFormat::Alb => &[
+            "statusCode",
+            "statusDescription",
+            "headers",
+            "multiValueHeaders",
+            "body",
+            "isBase64Encoded",
+        ],
+        // Not proxy integrations
+        super::PayloadFormat::EdgeViewerRequest
+        | super::PayloadFormat::EdgeOriginRequest
+        | super::PayloadFormat::AppSync => return Vec::new(),
+    };
+    let object = match value.as_object() {
+        Some(object) => object,
+        None => return vec![format!("response must be an object but got {}", value)],
+    };
+
+    let mut errors = Vec::new();
+    for key in object.keys() {
+        if !allowed_fields.contains(&key.as_str()) {
+            errors.push(format!("unknown field {:?}", key));
+        }
+    }
+    match object.get("statusCode") {
+        Some(serde_json::Value::Number(n))
+            if n.as_u64().is_some_and(|n| (100..=599).contains(&n)) => {}
+        Some(v) => errors.push(format!(
+            "statusCode must be an integer from 100 to 599 but got {}",
+            v
+        )),
+        None => errors.push("statusCode is missing".to_owned()),
+    }
+    if let Some(v) = object.get("statusDescription") {
+        if !v.is_string() {
+            errors.push(format!("statusDescription must be a string but got {}", v));
+        }
+    }
+    if let Some(v) = object.get("headers") {
+        match v.as_object() {
+            Some(headers) => {
+                for (k, v) in headers {
+                    if !v.is_string() {
+                        errors.push(format!("headers.{} must be a string but got {}", k, v));
+                    }
+                }
+            }
+            None => errors.push(format!("headers must be an object but got {}", v)),
+        }
+    }
+    if let Some(v) = object.get("multiValueHeaders") {
+        match v.as_object() {
+            Some(headers) => {
+                for (k, v) in headers {
+                    if !is_string_array(v) {
+                        errors.push(format!(
+                            "multiValueHeaders.{} must be an array of strings but got {}",
+                            k, v
+                        ));
+                    }
+                }
+            }
+            None => errors.push(format!("multiValueHeaders must be an object but got {}", v)),
+        }
+    }
+    if let Some(v) = object.get("cookies") {
+        if !is_string_array(v) {
+            errors.push(format!("cookies must be an array of strings but got {}", v));
+        }
+    }
+    let is_base64_encoded = match object.get("isBase64Encoded") {
+        Some(serde_json::Value::Bool(b)) => *b,
+        Some(v) => {
+            errors.push(format!("isBase64Encoded must be a boolean but got {}", v));
+            false
+        }
+        None => false,
+    };
+    match object.get("body") {
+        Some(serde_json::Value::String(body)) => {
+            if is_base64_encoded && base64::decode(body).is_err() {
+                errors.push("body must be base64 when isBase64Encoded is true".to_owned());
+            }
+        }
+        Some(serde_json::Value::Null) | None => {}
+        Some(v) => errors.push(format!("body must be a string but got {}", v)),
+    }
+    errors
+}
+
+fn is_string_array(value: &serde_json::Value) -> bool {
+    value
+        .as_array()
+        .is_some_and(|values| values.iter().all(|v| v.is_string()))
+}
@@ -0,0 +1,95 @@
+// Custom events are rendered from a JSON template. Strings consisting of a single `${expr}`
+// are replaced with the value of `expr` as is, and `${expr}` embedded in longer strings is
+// replaced with its text. `expr` is a path like `body.commits[0].id` into this context:
+//
+// - method, path, sourceIp, requestId, time, timeEpoch
+// - headers and query: objects keeping the last value of each name
+// - body: the request body parsed as JSON, or as a string when it isn't JSON
+// - rawBody: the request body as a string
+pub fn build(
+    request: &super::Request,
+    options: &super::EventOptions<'_>,
+    template: &serde_json::Value,
+) -> serde_json::Value {
+    let raw_body = String::from_utf8_lossy(&request.body).into_owned();
+    let body = serde_json::from_slice(&request.body)
+        .unwrap_or_else(|_| serde_json::Value::String(raw_body.clone()));
+    let headers: serde_json::Map<String, serde_json::Value> = request
+        .headers
+        .iter()
+        .map(|(k, v)| (k.clone(), serde_json::Value::String(v.clone())))
+        .collect();
+    let query: serde_json::Map<String, serde_json::Value> = request
+        .query_pairs()
+        .into_iter()
+        .map(|(k, v)| (k, serde_json::Value::String(v)))
+        .collect();
+    let context = serde_json::json!({
+        "method": request.parts.method.as_str(),
+        "path": request.parts.uri.path(),
+        "sourceIp": options.source_ip,
+        "requestId": options.request_id.uuid,
+        "time": options.time.to_rfc3339(),
+        "timeEpoch": options.time.timestamp_millis(),
+        "headers": headers,
+        "query": query,
+        "body": body,
+        "rawBody": raw_body,
+    });
+    render(template, &context)
+}
+
+fn render(template: &serde_json::Value, context: &serde_json::Value) -> serde_json::Value {
+    match template {
+        serde_json::Value::String(s) => render_string(s, context),
+        serde_json::Value::Array(values) => values.iter().map(|v| render(v, context)).collect(),
+        serde_json::Value::Object(fields) => serde_json::Value::Object(
+            fields
+                .iter()
+                .map(|(k, v)| (k.clone(), render(v, context)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn render_string(s: &str, context: &serde_json::Value) -> serde_json::Value {
+    if let Some(expr) = s.strip_prefix("${").and_then(|e| e.strip_suffix('}')) {
+        if !expr.contains("${") {
+            return lookup(context, expr).cloned().unwrap_or_default();
+        }
+    }
+    let mut rendered = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        rendered.push_str(&rest[..start]);
+        match lookup(context, &rest[start + 2..end]) {
+            Some(serde_json::Value::String(v)) => rendered.push_str(v),
+            Some(serde_json::Value::Null) | None => {}
+            Some(v) => rendered.push_str(&v.to_string()),
+        }
+        rest = &rest[end + 1..];
+    }
+    rendered.push_str(rest);
+    serde_json::Value::String(rendered)
+}
+
+// Look up a path like `a.b[0]`
+fn lookup<'a>(context: &'a serde_json::Value, expr: &str) -> Option<&'a serde_json::Value> {
+    let mut value = context;
+    for segment in expr.trim().split('.') {
+        let mut parts = segment.split('[');
+        let key = parts.next().unwrap_or_default();
+        if !key.is_empty() {
+            value = value.get(key)?;
+        }
+        for index in parts {
+            value = value.get(index.strip_suffix(']')?.parse::<usize>().ok()?)?;
+        }
+    }
+    Some(value)
+}
@@ -0,0 +1,57 @@
+/// Identifiers generated for each API request
+#[derive(Debug)]
+pub struct RequestId {
+    // UUID used by REST API requestId
+    pub uuid: String,
+    // Short ID like `JKJaXmPLvHcESHA=` used by HTTP API requestId and REST API extendedRequestId
+    pub extended: String,
+}
+
+/// Generator of request IDs, which is reproducible when seeded
+#[derive(Debug)]
+pub struct Generator {
+    seed: Option<u64>,
+    counter: std::sync::atomic::AtomicU64,
+}
+
+impl Generator {
+    pub fn new(seed: Option<u64>) -> Self {
+        Self {
+            seed,
+            counter: std::sync::atomic::AtomicU64::new(0),
+        }
+    }
+
+    pub fn uuid(&self) -> uuid::Uuid {
+        match self.seed {
+            Some(seed) => {
+                let n = self
+                    .counter
+                    .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                let mut bytes = [0; 16];
+                bytes[..8].copy_from_slice(&splitmix64(seed ^ n.wrapping_mul(2)).to_be_bytes());
+                bytes[8..].copy_from_slice(
+                    &splitmix64(seed ^ n.wrapping_mul(2).wrapping_add(1)).to_be_bytes(),
+                );
+                uuid::Builder::from_random_bytes(bytes).into_uuid()
+            }
+            None => uuid::Uuid::new_v4(),
+        }
+    }
+
+    pub fn generate(&self) -> RequestId {
+        let uuid = self.uuid();
+        RequestId {
+            uuid: uuid.to_string(),
+            extended: base64::encode(&uuid.as_bytes()[..11]),
+        }
+    }
+}
+
+// https://prng.di.unimi.it/splitmix64.c
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,203 @@
+/// API Gateway resource path like `/users/{id}` or `/api/{proxy+}`
+///
+/// Resources can be limited to a method like `GET /users/{id}`, and match every method otherwise.
+#[derive(Debug, Clone)]
+pub struct Resource {
+    method: Option<hyper::Method>,
+    template: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Literal(String),
+    Parameter(String),
+    // Matches with all the remaining path segments
+    Greedy(String),
+}
+
+/// Resource matched with the request path
+#[derive(Debug)]
+pub struct Match {
+    pub method: Option<hyper::Method>,
+    pub template: String,
+    pub path_parameters: std::collections::HashMap<String, String>,
+}
+
+impl std::str::FromStr for Resource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (method, s) = match s.split_once(' ') {
+            Some((method, path)) if !method.starts_with('/') => {
+                let method = method.to_ascii_uppercase();
+                if method == "ANY" {
+                    (None, path.trim_start())
+                } else {
+                    let method = method
+                        .parse()
+                        .map_err(|e| anyhow::anyhow!("invalid method of resource {}: {}", s, e))?;
+                    (Some(method), path.trim_start())
+                }
+            }
+            _ => (None, s),
+        };
+        if !s.starts_with('/') {
+            return Err(anyhow::anyhow!("resource must start with /: {}", s));
+        }
+        let mut segments = Vec::new();
+        for segment in s[1..].split('/') {
+            if let Some(Segment::Greedy(_)) = segments.last() {
+                return Err(anyhow::anyhow!(
+                    "greedy path parameter must be the last path segment: {}",
+                    s
+                ));
+            }
+            if segment.starts_with('{') && segment.ends_with("+}") && segment.len() > 3 {
+                segments.push(Segment::Greedy(segment[1..segment.len() - 2].to_owned()));
+            } else if segment.starts_with('{') && segment.ends_with('}') && segment.len() > 2 {
+                segments.push(Segment::Parameter(segment[1..segment.len() - 1].to_owned()));
+            } else if segment.contains('{') || segment.contains('}') {
+                return Err(anyhow::anyhow!(
+                    "path parameter must be a whole path segment: {}",
+                    s
+                ));
+            } else {
+                segments.push(Segment::Literal(segment.to_owned()));
+            }
+        }
+        Ok(Self {
+            method,
+            template: s.to_owned(),
+            segments,
+        })
+    }
+}
+
+impl std::fmt::Display for Resource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.method {
+            Some(method) => write!(f, "{} {}", method, self.template),
+            None => f.write_str(&self.template),
+        }
+    }
+}
+
+impl Resource {
+    /// Resource with unnamed path parameters like `GET /users/{}`, which is the same for
+    /// resources matching the same requests
+    pub fn shape(&self) -> String {
+        let path: String = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(literal) => format!("/{}", literal),
+                Segment::Parameter(_) => "/{}".to_owned(),
+                Segment::Greedy(_) => "/{+}".to_owned(),
+            })
+            .collect();
+        match &self.method {
+            Some(method) => format!("{} {}", method, path),
+            None => path,
+        }
+    }
+
+    pub fn allows(&self, method: &hyper::Method) -> bool {
+        self.method.as_ref().is_none_or(|m| m == method)
+    }
+
+    fn matches(&self, method: &hyper::Method, path: &str) -> Option<Match> {
+        if !self.allows(method) {
+            return None;
+        }
+        self.matches_path(path)
+    }
+
+    fn matches_path(&self, path: &str) -> Option<Match> {
+        let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        let greedy = matches!(self.segments.last(), Some(Segment::Greedy(_)));
+        if parts.len() < self.segments.len() || (!greedy && parts.len() != self.segments.len()) {
+            return None;
+        }
+        let mut path_parameters = std::collections::HashMap::new();
+        for (i, segment) in self.segments.iter().enumerate() {
+            let part = parts[i];
+            match segment {
+                Segment::Literal(literal) => {
+                    if literal != part {
+                        return None;
+                    }
+                }
+                Segment::Parameter(name) => {
+                    if part.is_empty() {
+                        return None;
+                    }
+                    let value = percent_encoding::percent_decode_str(part).decode_utf8_lossy();
+                    path_parameters.insert(name.clone(), value.into_owned());
+                }
+                Segment::Greedy(name) => {
+                    let rest = parts[i..].join("/");
+                    if rest.is_empty() {
+                        return None;
+                    }
+                    let value = percent_encoding::percent_decode_str(&rest).decode_utf8_lossy();
+                    path_parameters.insert(name.clone(), value.into_owned());
+                }
+            }
+        }
+        Some(Match {
+            method: self.method.clone(),
+            template: self.template.clone(),
+            path_parameters,
+        })
+    }
+
+    fn specificity(&self) -> (usize, bool, bool) {
+        let literals = self
+            .segments
+            .iter()
+            .filter(|s| matches!(s, Segment::Literal(_)))
+            .count();
+        let greedy = matches!(self.segments.last(), Some(Segment::Greedy(_)));
+        (literals, !greedy, self.method.is_some())
+    }
+}
+
+/// Find the most specific resource matching with the method and the path
+///
+/// Resources with more literal segments win, then resources without greedy path parameters win,
+/// then resources of the method win over ones of any method, and earlier resources win ties.
+pub fn find(resources: &[Resource], method: &hyper::Method, path: &str) -> Option<Match> {
+    find_by(resources.iter().map(|r| (r, ())), method, path).map(|(_, m)| m)
+}
+
+/// Like `find`, returning the value paired with the most specific resource as well
+pub fn find_value<'a, T>(
+    entries: &'a [(Resource, T)],
+    method: &hyper::Method,
+    path: &str,
+) -> Option<(&'a T, Match)> {
+    find_by(entries.iter().map(|(r, v)| (r, v)), method, path)
+}
+
+/// Like `find`, among resources paired with arbitrary values
+pub fn find_by<'a, I, T>(entries: I, method: &hyper::Method, path: &str) -> Option<(T, Match)>
+where
+    I: IntoIterator<Item = (&'a Resource, T)>,
+{
+    let mut best: Option<((usize, bool, bool), T, Match)> = None;
+    for (resource, value) in entries {
+        if let Some(m) = resource.matches(method, path) {
+            let score = resource.specificity();
+            if best.as_ref().map(|(s, _, _)| score > *s).unwrap_or(true) {
+                best = Some((score, value, m));
+            }
+        }
+    }
+    best.map(|(_, v, m)| (v, m))
+}
+
+/// Whether any resource matches with the path regardless of methods
+pub fn matches_path(resources: &[Resource], path: &str) -> bool {
+    resources.iter().any(|r| r.matches_path(path).is_some())
+}
@@ -0,0 +1,25 @@
+/// CIDR block or a single address like `10.0.0.0/8` and `192.168.1.10`
+pub fn parse_cidr(s: &str) -> Result<ipnet::IpNet, anyhow::Error> {
+    match s.parse::<std::net::IpAddr>() {
+        Ok(ip) => Ok(ip.into()),
+        Err(_) => s
+            .parse()
+            .map_err(|_| anyhow::anyhow!("expected CIDR block like 10.0.0.0/8: {}", s)),
+    }
+}
+
+/// Whether resource policies of `aws:SourceIp` allow requests from the source IP, where denies
+/// win and only allowed addresses are accepted if any are allowed
+pub fn allows(allow: &[ipnet::IpNet], deny: &[ipnet::IpNet], source_ip: &str) -> bool {
+    if allow.is_empty() && deny.is_empty() {
+        return true;
+    }
+    // Source IPs of X-Forwarded-For may not be addresses, and IPv4 clients of dual-stack sockets
+    // are IPv4-mapped IPv6 addresses
+    let ip = match source_ip.parse::<std::net::IpAddr>() {
+        Ok(ip) => ip.to_canonical(),
+        Err(_) => return false,
+    };
+    let contains = |nets: &[ipnet::IpNet]| nets.iter().any(|net| net.contains(&ip));
+    !contains(deny) && (allow.is_empty() || contains(allow))
+}
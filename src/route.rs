@@ -0,0 +1,1121 @@
+/// Route of requests to one of multiple RIE, like `/users/*=http://localhost:9001`
+///
+/// Routes may start with a method like `POST /users` and a host pattern like `*.localhost/api/*`
+/// matched with the Host header. Paths are resource paths where a trailing `*` matches the rest,
+/// or regular expressions after `~`. Targets are URLs, canned responses of `mock:` or directories
+/// of `file:`, and options like `priority=1` can follow them after spaces.
+#[derive(Debug, Clone)]
+pub struct Route {
+    // Pattern before the target as it was given, like POST /users
+    pattern: String,
+    method: Option<hyper::Method>,
+    host: Option<String>,
+    matcher: Matcher,
+    // Routes of lower priorities are matched first, and routes without priorities last
+    priority: Option<i64>,
+    strip_prefix: Option<String>,
+    rewrite: Option<(regex::Regex, String)>,
+    header_rules: Vec<HeaderRule>,
+    query_rules: Vec<QueryRule>,
+    required_query_parameters: Vec<String>,
+    required_headers: Vec<String>,
+    // Models of JSON bodies and query string parameters validated like request validators
+    pub body_schema: Option<crate::schema::Schema>,
+    pub query_schema: Option<crate::schema::Schema>,
+    pub payload_format: Option<crate::payload::PayloadFormat>,
+    pub stage: Option<String>,
+    pub stage_variables: Vec<(String, String)>,
+    pub function: Option<String>,
+    // Version or alias of the function
+    qualifier: Option<String>,
+    // URL receiving the percentage of invocations instead of the target
+    canary: Option<(String, f64)>,
+    // URLs receiving copies of invocations, whose responses are discarded
+    mirrors: Vec<String>,
+    pub name: Option<String>,
+    // URL switched with the target by the admin API, which clones of the route share
+    green: Option<String>,
+    green_active: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    // Throttling of the route instead of the stage
+    pub throttle: Option<crate::throttle::Shared>,
+    pub integration: Option<crate::integration::Integration>,
+    pub target: Target,
+}
+
+#[derive(Debug, Clone)]
+pub enum Target {
+    Url(String),
+    Mock(crate::mock::Mock),
+    Static(crate::static_files::StaticFiles),
+}
+
+#[derive(Debug, Clone)]
+enum HeaderRule {
+    Set(String, String),
+    Add(String, String),
+    Remove(String),
+}
+
+// Names and values are percent-encoded as they appear in query strings
+#[derive(Debug, Clone)]
+enum QueryRule {
+    Set(String, String),
+    Add(String, String),
+    Remove(String),
+    Rename(String, String),
+}
+
+// Characters kept as they are in names and values of query string parameters
+const QUERY_COMPONENT: &percent_encoding::AsciiSet = &percent_encoding::NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Resource(crate::resource::Resource),
+    Regex(regex::Regex),
+    // Matches with every path including /, which /{proxy+} doesn't
+    CatchAll,
+}
+
+impl std::str::FromStr for Route {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pattern, rest) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("route must be in PATTERN=URL form: {}", s))?;
+        let given_pattern = pattern;
+        let (mut target, options) = if let Some(mock) = rest.strip_prefix("mock:") {
+            let (mock, options) = crate::mock::Mock::parse(mock)?;
+            (Target::Mock(mock), options)
+        } else if let Some(dir) = rest.strip_prefix("file:") {
+            let (dir, options) = dir.split_once(char::is_whitespace).unwrap_or((dir, ""));
+            (
+                Target::Static(crate::static_files::StaticFiles::new(dir)),
+                options,
+            )
+        } else {
+            let (url, options) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            (Target::Url(parse_url(url, s)?), options)
+        };
+        let mut priority = None;
+        let mut strip_prefix = None;
+        let mut rewrite = None;
+        let mut header_rules = Vec::new();
+        let mut query_rules = Vec::new();
+        let mut required_query_parameters = Vec::new();
+        let mut required_headers = Vec::new();
+        let mut body_schema = None;
+        let mut query_schema = None;
+        let mut payload_format = None;
+        let mut stage = None;
+        let mut stage_variables = Vec::new();
+        let mut function = None;
+        let mut qualifier = None;
+        let mut canary_url = None;
+        let mut canary_weight = None;
+        let mut mirrors = Vec::new();
+        let mut name = None;
+        let mut green = None;
+        let mut throttle_rate = None;
+        let mut throttle_burst = None;
+        let mut request_template = None;
+        let mut response_template = None;
+        let mut error_statuses = Vec::new();
+        // Options can be quoted like set-header="Authorization: Bearer x"
+        for option in split_words(options)? {
+            let option = option.as_str();
+            match option.split_once('=') {
+                Some(("priority", value)) => {
+                    priority =
+                        Some(value.parse().map_err(|e| {
+                            anyhow::anyhow!("invalid priority of route {}: {}", s, e)
+                        })?);
+                }
+                // Paths are rewritten before events are built, like rewrite=^/v1/(.*)=>/$1
+                Some(("strip-prefix", prefix)) => {
+                    strip_prefix = Some(prefix.trim_end_matches('/').to_owned());
+                }
+                Some(("rewrite", value)) => {
+                    let (regex, replacement) = value.rsplit_once("=>").ok_or_else(|| {
+                        anyhow::anyhow!("rewrite must be in REGEX=>REPLACEMENT form: {}", s)
+                    })?;
+                    let regex = regex::Regex::new(regex)
+                        .map_err(|e| anyhow::anyhow!("invalid rewrite of route {}: {}", s, e))?;
+                    rewrite = Some((regex, replacement.to_owned()));
+                }
+                // Request headers are changed like set-header=NAME:VALUE
+                Some(("set-header", header)) => {
+                    let (name, value) = parse_header(header)?;
+                    header_rules.push(HeaderRule::Set(name, value));
+                }
+                Some(("add-header", header)) => {
+                    let (name, value) = parse_header(header)?;
+                    header_rules.push(HeaderRule::Add(name, value));
+                }
+                Some(("remove-header", name)) => {
+                    header_rules.push(HeaderRule::Remove(name.to_ascii_lowercase()));
+                }
+                // Query string parameters are changed like request parameter mappings, e.g.
+                // rename-query=OLD=>NEW
+                Some(("set-query", parameter)) => {
+                    let (name, value) = parse_query_parameter(parameter)?;
+                    query_rules.push(QueryRule::Set(name, value));
+                }
+                Some(("add-query", parameter)) => {
+                    let (name, value) = parse_query_parameter(parameter)?;
+                    query_rules.push(QueryRule::Add(name, value));
+                }
+                Some(("remove-query", name)) => {
+                    query_rules.push(QueryRule::Remove(encode_query_component(name)));
+                }
+                Some(("rename-query", names)) => {
+                    let (old, new) = names.split_once("=>").ok_or_else(|| {
+                        anyhow::anyhow!("rename-query must be in OLD=>NEW form: {}", s)
+                    })?;
+                    query_rules.push(QueryRule::Rename(
+                        encode_query_component(old),
+                        encode_query_component(new),
+                    ));
+                }
+                // Requests are validated like request validators
+                Some(("required-query", name)) => required_query_parameters.push(name.to_owned()),
+                Some(("required-header", name)) => {
+                    required_headers.push(name.to_ascii_lowercase());
+                }
+                Some(("body-schema", schema)) => {
+                    body_schema = Some(crate::schema::Schema::read(schema)?)
+                }
+                Some(("query-schema", schema)) => {
+                    query_schema = Some(crate::schema::Schema::read(schema)?)
+                }
+                // Options of events sent to the function of the route
+                Some(("payload-format", format)) => payload_format = Some(format.parse()?),
+                Some(("stage", name)) => stage = Some(name.to_owned()),
+                Some(("stage-variable", variable)) => {
+                    stage_variables.push(crate::parse_key_value(variable)?);
+                }
+                // Templates of URLs like http://localhost:{port} are filled with the function and the
+                // qualifier, which also selects the RIE of the alias given by --function-alias
+                Some(("function", name)) => function = Some(name.to_owned()),
+                Some(("qualifier", name)) => qualifier = Some(name.to_owned()),
+                // Like canary deployments, canary=URL with canary-weight=PERCENT sends the percentage
+                // of invocations to another URL, and mirror=URL sends copies discarding responses
+                Some(("canary", url)) => canary_url = Some(parse_url(url, s)?),
+                Some(("mirror", url)) => mirrors.push(parse_url(url, s)?),
+                // Routes with names switch their targets to green URLs by the admin API
+                Some(("name", value)) => name = Some(value.to_owned()),
+                Some(("green", url)) => green = Some(parse_url(url, s)?),
+                Some(("canary-weight", weight)) => {
+                    let weight: f64 = weight
+                        .parse()
+                        .map_err(|e| anyhow::anyhow!("invalid canary-weight of {}: {}", s, e))?;
+                    if !(0.0..=100.0).contains(&weight) {
+                        return Err(anyhow::anyhow!("canary-weight must be a percentage: {}", s));
+                    }
+                    canary_weight = Some(weight);
+                }
+                // The route has its own token bucket instead of the stage's
+                Some(("throttle-rate", rate)) => {
+                    throttle_rate = Some(rate.parse().map_err(|e| {
+                        anyhow::anyhow!("invalid throttle-rate of route {}: {}", s, e)
+                    })?);
+                }
+                Some(("throttle-burst", burst)) => {
+                    throttle_burst = Some(burst.parse().map_err(|e| {
+                        anyhow::anyhow!("invalid throttle-burst of route {}: {}", s, e)
+                    })?);
+                }
+                // Like Lambda integrations without proxy, Velocity templates map events and results,
+                // and error-status=REGEX=>STATUS responds the status to matching errors
+                Some(("request-template", path)) => {
+                    request_template = Some(crate::vtl::Template::read_file(path)?);
+                }
+                Some(("response-template", path)) => {
+                    response_template = Some(crate::vtl::Template::read_file(path)?);
+                }
+                Some(("error-status", value)) => {
+                    let (pattern, status) = value.rsplit_once("=>").ok_or_else(|| {
+                        anyhow::anyhow!("error-status must be in REGEX=>STATUS form: {}", s)
+                    })?;
+                    let status: u16 = status
+                        .parse()
+                        .map_err(|e| anyhow::anyhow!("invalid error-status of {}: {}", s, e))?;
+                    error_statuses.push((pattern.to_owned(), status));
+                }
+                // Static files fall back to the file like index.html of single page applications
+                Some(("spa-fallback", file)) => match &mut target {
+                    Target::Static(files) => files.spa_fallback = Some(file.to_owned()),
+                    _ => {
+                        return Err(anyhow::anyhow!(
+                            "spa-fallback is only for file: routes: {}",
+                            s
+                        ))
+                    }
+                },
+                _ => return Err(anyhow::anyhow!("unknown option of route {}: {}", s, option)),
+            }
+        }
+
+        if !mirrors.is_empty() && !matches!(target, Target::Url(_)) {
+            return Err(anyhow::anyhow!("mirror is only for routes of URLs: {}", s));
+        }
+        if green.is_some() && (name.is_none() || !matches!(target, Target::Url(_))) {
+            return Err(anyhow::anyhow!(
+                "green is only for routes of URLs with names: {}",
+                s
+            ));
+        }
+        let canary = match (canary_url, canary_weight, &target) {
+            (Some(url), Some(weight), Target::Url(_)) => Some((url, weight)),
+            (None, None, _) => None,
+            (Some(_), Some(_), _) => {
+                return Err(anyhow::anyhow!("canary is only for routes of URLs: {}", s))
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "canary and canary-weight must be given together: {}",
+                    s
+                ))
+            }
+        };
+        let throttle = match (throttle_rate, throttle_burst) {
+            (Some(rate_limit), Some(burst_limit)) => {
+                Some(crate::throttle::shared(crate::throttle::Limits {
+                    rate_limit,
+                    burst_limit,
+                }))
+            }
+            (None, None) => None,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "throttle-rate and throttle-burst must be given together: {}",
+                    s
+                ))
+            }
+        };
+        let integration = if request_template.is_none()
+            && response_template.is_none()
+            && error_statuses.is_empty()
+        {
+            None
+        } else {
+            if !matches!(target, Target::Url(_)) {
+                return Err(anyhow::anyhow!(
+                    "mapping templates are only for routes of URLs: {}",
+                    s
+                ));
+            }
+            // Templates map events of REST APIs
+            match payload_format {
+                None | Some(crate::payload::PayloadFormat::V1) => {
+                    payload_format = Some(crate::payload::PayloadFormat::V1)
+                }
+                Some(_) => {
+                    return Err(anyhow::anyhow!(
+                        "mapping templates are only for payload-format=1.0: {}",
+                        s
+                    ))
+                }
+            }
+            let mut integration = crate::integration::Integration::new(request_template);
+            for (pattern, status) in error_statuses {
+                integration.add_response(Some(&pattern), status, None, &[])?;
+            }
+            integration.add_response(None, 200, response_template, &[])?;
+            Some(integration)
+        };
+
+        let (method, pattern) = match pattern.split_once(' ') {
+            Some((method, pattern)) => (Some(method), pattern.trim_start()),
+            None => (None, pattern),
+        };
+        let (host, path) = if pattern.starts_with('/') || pattern.starts_with('~') || pattern == "*"
+        {
+            (None, pattern)
+        } else {
+            match pattern.find(['/', '~']) {
+                Some(i) => (Some(&pattern[..i]), &pattern[i..]),
+                None => (Some(pattern), "/*"),
+            }
+        };
+        let matcher = if let Some(regex) = path.strip_prefix('~') {
+            Matcher::Regex(
+                regex::Regex::new(regex)
+                    .map_err(|e| anyhow::anyhow!("invalid regex of route {}: {}", s, e))?,
+            )
+        } else if path == "/*" || path == "*" {
+            Matcher::CatchAll
+        } else {
+            let path = match path.strip_suffix("/*") {
+                Some(prefix) => {
+                    if let Target::Static(files) = &mut target {
+                        files.prefix = prefix.to_owned();
+                    }
+                    format!("{}/{{proxy+}}", prefix)
+                }
+                None => path.to_owned(),
+            };
+            // Resources of the method win over resources of any method
+            let resource = match method {
+                Some(method) => format!("{} {}", method, path),
+                None => path,
+            };
+            Matcher::Resource(resource.parse()?)
+        };
+        let method = match method.map(|m| m.to_ascii_uppercase()) {
+            Some(m) if m != "ANY" => Some(
+                m.parse()
+                    .map_err(|e| anyhow::anyhow!("invalid method of route {}: {}", s, e))?,
+            ),
+            _ => None,
+        };
+        Ok(Self {
+            pattern: given_pattern.to_owned(),
+            method,
+            host: host.map(|host| host.to_ascii_lowercase()),
+            matcher,
+            priority,
+            strip_prefix,
+            rewrite,
+            header_rules,
+            query_rules,
+            required_query_parameters,
+            required_headers,
+            body_schema,
+            query_schema,
+            payload_format,
+            stage,
+            stage_variables,
+            function,
+            qualifier,
+            canary,
+            mirrors,
+            name,
+            green,
+            green_active: std::sync::Arc::default(),
+            throttle,
+            integration,
+            target,
+        })
+    }
+}
+
+impl Route {
+    /// Fill placeholders of the target URL with the function of the route, whose URL of the
+    /// qualifier like `users:prod` replaces the target if any
+    pub fn expand_target(
+        &mut self,
+        ports: &[(String, String)],
+        aliases: &[(String, String)],
+    ) -> Result<(), anyhow::Error> {
+        if let (Some(function), Some(qualifier), Target::Url(url)) =
+            (&self.function, &self.qualifier, &mut self.target)
+        {
+            let key = format!("{}:{}", function, qualifier);
+            if let Some((_, alias_url)) = aliases.iter().find(|(name, _)| *name == key) {
+                *url = alias_url.trim_end_matches('/').to_owned();
+            }
+        }
+        let canary = self.canary.as_mut().map(|(url, _)| url);
+        if let Target::Url(url) = &mut self.target {
+            for url in std::iter::once(url)
+                .chain(canary)
+                .chain(self.green.as_mut())
+                .chain(self.mirrors.iter_mut())
+            {
+                if url.contains('{') {
+                    *url = expand_url(
+                        url,
+                        self.function.as_deref(),
+                        self.qualifier.as_deref(),
+                        ports,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Target URL, or the green URL while it's switched to
+    pub fn active_url(&self) -> Option<&str> {
+        match (&self.target, &self.green) {
+            (Target::Url(_), Some(green)) if self.is_green_active() => Some(green),
+            (Target::Url(url), _) => Some(url),
+            _ => None,
+        }
+    }
+
+    /// URL of the invocation, which is the canary for its percentage of invocations
+    pub fn target_url(&self) -> Option<&str> {
+        let url = self.active_url()?;
+        if let Some((canary_url, weight)) = &self.canary {
+            let random = uuid::Uuid::new_v4();
+            let bytes = random.as_bytes();
+            let n = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            if f64::from(n) / f64::from(u32::MAX) * 100.0 < *weight {
+                log::info!("Send the invocation to the canary {}", canary_url);
+                return Some(canary_url);
+            }
+        }
+        Some(url)
+    }
+
+    pub fn is_green_active(&self) -> bool {
+        self.green_active.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Switch the target to the green URL or back, or toggle it without the side
+    pub fn switch(&self, green: Option<bool>) -> Result<bool, anyhow::Error> {
+        if self.green.is_none() {
+            anyhow::bail!("route has no green target");
+        }
+        let ordering = std::sync::atomic::Ordering::SeqCst;
+        let active = match green {
+            Some(green) => {
+                self.green_active.store(green, ordering);
+                green
+            }
+            None => !self.green_active.fetch_xor(true, ordering),
+        };
+        Ok(active)
+    }
+
+    /// Summary of the route like the one the admin API lists
+    pub fn describe(&self) -> serde_json::Value {
+        let target = match &self.target {
+            Target::Url(_) => self.active_url().unwrap_or_default().to_owned(),
+            Target::Mock(_) => "mock".to_owned(),
+            Target::Static(files) => format!("file:{}", files.root().display()),
+        };
+        let mut description = serde_json::json!({
+            "pattern": self.pattern,
+            "target": target,
+        });
+        let fields = [
+            ("name", self.name.clone().map(serde_json::Value::from)),
+            (
+                "function",
+                self.function.clone().map(serde_json::Value::from),
+            ),
+            (
+                "qualifier",
+                self.qualifier.clone().map(serde_json::Value::from),
+            ),
+            ("priority", self.priority.map(serde_json::Value::from)),
+            (
+                "payloadFormat",
+                self.payload_format.map(|f| f.to_string().into()),
+            ),
+            ("stage", self.stage.clone().map(serde_json::Value::from)),
+            (
+                "green",
+                self.green
+                    .as_ref()
+                    .map(|url| serde_json::json!({ "url": url, "active": self.is_green_active() })),
+            ),
+            (
+                "canary",
+                self.canary
+                    .as_ref()
+                    .map(|(url, weight)| serde_json::json!({ "url": url, "weight": weight })),
+            ),
+            (
+                "throttle",
+                self.throttle.as_ref().map(|bucket| {
+                    let limits = bucket.lock().unwrap().limits();
+                    serde_json::json!({
+                        "rateLimit": limits.rate_limit,
+                        "burstLimit": limits.burst_limit,
+                    })
+                }),
+            ),
+            (
+                "integration",
+                self.integration.as_ref().map(|_| "aws".into()),
+            ),
+            (
+                "mirrors",
+                Some(self.mirrors.clone().into()).filter(|_| !self.mirrors.is_empty()),
+            ),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                description[key] = value;
+            }
+        }
+        description
+    }
+
+    pub fn mirror_urls(&self) -> &[String] {
+        &self.mirrors
+    }
+
+    /// URLs the route sends invocations to, including the canary, green and mirror URLs
+    pub fn urls(&self) -> Vec<&str> {
+        match &self.target {
+            Target::Url(url) => std::iter::once(url)
+                .chain(self.canary.as_ref().map(|(url, _)| url))
+                .chain(self.green.as_ref())
+                .chain(self.mirrors.iter())
+                .map(|url| url.as_str())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Requests the route matches, which are the same for routes of the same key
+    fn match_key(&self) -> String {
+        let matcher = match &self.matcher {
+            Matcher::Resource(resource) => resource.shape(),
+            Matcher::Regex(regex) => format!("~{}", regex.as_str()),
+            Matcher::CatchAll => "*".to_owned(),
+        };
+        format!(
+            "{:?} {:?} {:?} {}",
+            self.priority,
+            self.host.as_ref().map(|host| host.to_ascii_lowercase()),
+            self.method,
+            matcher
+        )
+    }
+
+    // How specifically the host pattern matches with the host, if it does
+    fn host_rank(&self, host: Option<&str>) -> Option<u8> {
+        match (&self.host, host) {
+            (None, _) => Some(0),
+            (Some(pattern), Some(host)) if !pattern.contains('*') => {
+                if pattern.eq_ignore_ascii_case(host) {
+                    Some(2)
+                } else {
+                    None
+                }
+            }
+            (Some(pattern), Some(host)) if crate::header_filter::glob_match(pattern, host) => {
+                Some(1)
+            }
+            _ => None,
+        }
+    }
+
+    /// Path rewritten by the prefix to strip and then the regex substitution, if any applies
+    pub fn rewrite_path(&self, path: &str) -> Option<String> {
+        let mut rewritten = None;
+        if let Some(prefix) = &self.strip_prefix {
+            if let Some(rest) = path.strip_prefix(prefix.as_str()) {
+                if rest.is_empty() {
+                    rewritten = Some("/".to_owned());
+                } else if rest.starts_with('/') {
+                    rewritten = Some(rest.to_owned());
+                }
+            }
+        }
+        if let Some((regex, replacement)) = &self.rewrite {
+            let current = rewritten.as_deref().unwrap_or(path);
+            if regex.is_match(current) {
+                rewritten = Some(regex.replace(current, replacement.as_str()).into_owned());
+            }
+        }
+        rewritten
+    }
+
+    /// Required query string parameters and headers missing in the request
+    pub fn missing_parameters(&self, request: &crate::payload::Request) -> Vec<String> {
+        let query = request.parts.uri.query().unwrap_or_default();
+        let mut missing: Vec<String> = self
+            .required_query_parameters
+            .iter()
+            .filter(|name| {
+                !query.split('&').any(|p| {
+                    let n = p.split('=').next().unwrap_or_default();
+                    percent_encoding::percent_decode_str(n).decode_utf8_lossy() == name.as_str()
+                })
+            })
+            .cloned()
+            .collect();
+        missing.extend(
+            self.required_headers
+                .iter()
+                .filter(|name| !request.headers.iter().any(|(k, _)| k == *name))
+                .cloned(),
+        );
+        missing
+    }
+
+    /// Errors of the body and query string parameters of the request against the schemas of the
+    /// route, and whether they are of the body
+    pub fn validation_errors(&self, request: &crate::payload::Request) -> (Vec<String>, bool) {
+        if let Some(schema) = &self.query_schema {
+            // Parameters are validated as functions see them
+            let errors = schema.validate_parameters(&request.query_pairs());
+            if !errors.is_empty() {
+                return (errors, false);
+            }
+        }
+        if let Some(schema) = &self.body_schema {
+            let errors = match serde_json::from_slice(&request.body) {
+                Ok(body) => schema.validate(&body),
+                Err(e) => vec![format!("invalid JSON: {}", e)],
+            };
+            return (errors, true);
+        }
+        (Vec::new(), false)
+    }
+
+    /// Change headers of the request by the header rules in order
+    pub fn apply_header_rules(&self, request: &mut crate::payload::Request) {
+        for rule in &self.header_rules {
+            match rule {
+                HeaderRule::Set(name, value) => {
+                    request.remove_header(name);
+                    request.headers.push((name.clone(), value.clone()));
+                }
+                HeaderRule::Add(name, value) => {
+                    request.headers.push((name.clone(), value.clone()));
+                }
+                HeaderRule::Remove(name) => {
+                    request.remove_header(name);
+                }
+            }
+        }
+    }
+
+    /// Change query string parameters of the request by the query rules in order
+    pub fn apply_query_rules(
+        &self,
+        request: &mut crate::payload::Request,
+    ) -> Result<(), anyhow::Error> {
+        if self.query_rules.is_empty() {
+            return Ok(());
+        }
+        let mut parameters: Vec<(String, Option<String>)> = request
+            .parts
+            .uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|p| !p.is_empty())
+            .map(|p| match p.split_once('=') {
+                Some((name, value)) => (name.to_owned(), Some(value.to_owned())),
+                None => (p.to_owned(), None),
+            })
+            .collect();
+        for rule in &self.query_rules {
+            match rule {
+                QueryRule::Set(name, value) => {
+                    parameters.retain(|(n, _)| !same_query_component(n, name));
+                    parameters.push((name.clone(), Some(value.clone())));
+                }
+                QueryRule::Add(name, value) => {
+                    parameters.push((name.clone(), Some(value.clone())));
+                }
+                QueryRule::Remove(name) => {
+                    parameters.retain(|(n, _)| !same_query_component(n, name))
+                }
+                QueryRule::Rename(old, new) => {
+                    for (name, _) in parameters
+                        .iter_mut()
+                        .filter(|(n, _)| same_query_component(n, old))
+                    {
+                        *name = new.clone();
+                    }
+                }
+            }
+        }
+        let query: Vec<String> = parameters
+            .into_iter()
+            .map(|(name, value)| match value {
+                Some(value) => format!("{}={}", name, value),
+                None => name,
+            })
+            .collect();
+        request.set_query(&query.join("&"))
+    }
+
+    fn allows(&self, method: &hyper::Method) -> bool {
+        self.method.as_ref().is_none_or(|m| m == method)
+    }
+}
+
+/// Target URL with `{function}`, `{qualifier}` and `{port}` replaced by the function name, its
+/// version or alias and its port
+pub fn expand_url(
+    template: &str,
+    function: Option<&str>,
+    qualifier: Option<&str>,
+    ports: &[(String, String)],
+) -> Result<String, anyhow::Error> {
+    let mut url = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        url.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow::anyhow!("unterminated placeholder in {}", template))?;
+        let placeholder = &rest[start + 1..start + end];
+        let function = function.ok_or_else(|| {
+            anyhow::anyhow!("{} needs the function of the route to fill it", template)
+        })?;
+        match placeholder {
+            "function" => url.push_str(function),
+            "qualifier" => url.push_str(qualifier.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{} needs the qualifier of {} to fill it",
+                    template,
+                    function
+                )
+            })?),
+            "port" => {
+                let port = ports
+                    .iter()
+                    .find(|(name, _)| name == function)
+                    .map(|(_, port)| port)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("no --function-port of {} for {}", function, template)
+                    })?;
+                port.parse::<u16>()
+                    .map_err(|e| anyhow::anyhow!("invalid port of {}: {}", function, e))?;
+                url.push_str(port);
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "unknown placeholder {{{}}} in {}",
+                    placeholder,
+                    template
+                ))
+            }
+        }
+        rest = &rest[start + end + 1..];
+    }
+    url.push_str(rest);
+    Ok(url)
+}
+
+fn parse_url(url: &str, route: &str) -> Result<String, anyhow::Error> {
+    if !url.starts_with("http://") && !url.starts_with("https://") && !url.starts_with("srv://") {
+        return Err(anyhow::anyhow!("invalid target URL of route: {}", route));
+    }
+    Ok(url.trim_end_matches('/').to_owned())
+}
+
+// Header in NAME:VALUE form, whose name is lowercased like ones of requests
+fn parse_header(s: &str) -> Result<(String, String), anyhow::Error> {
+    match s.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_ascii_lowercase(), value.trim().to_owned()))
+        }
+        _ => Err(anyhow::anyhow!("header must be in NAME:VALUE form: {}", s)),
+    }
+}
+
+fn parse_query_parameter(s: &str) -> Result<(String, String), anyhow::Error> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() => {
+            Ok((encode_query_component(name), encode_query_component(value)))
+        }
+        _ => Err(anyhow::anyhow!(
+            "query parameter must be in NAME=VALUE form: {}",
+            s
+        )),
+    }
+}
+
+fn encode_query_component(s: &str) -> String {
+    percent_encoding::utf8_percent_encode(s, QUERY_COMPONENT).to_string()
+}
+
+// Whether percent-encoded components are the same after decoding, like a%5Fb and a_b
+fn same_query_component(a: &str, b: &str) -> bool {
+    percent_encoding::percent_decode_str(a).eq(percent_encoding::percent_decode_str(b))
+}
+
+// Split options by whitespace, where single or double quotes keep whitespace in words
+fn split_words(s: &str) -> Result<Vec<String>, anyhow::Error> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in s.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => word.get_or_insert_with(String::new).push(c),
+            None if c == '\'' || c == '"' => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            None if c.is_whitespace() => words.extend(word.take()),
+            None => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(anyhow::anyhow!(
+            "unterminated quote in route options: {}",
+            s
+        ));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Find the route matching with the request
+///
+/// Routes are matched in the order of priorities, then routes of exact hosts win over routes of
+/// wildcard hosts, which win over routes without hosts. Among them, the most specific resource
+/// path wins like HTTP APIs, then the first matching regular expression, then catch-all routes.
+pub fn find<'a>(
+    routes: &'a [Route],
+    method: &hyper::Method,
+    host: Option<&str>,
+    path: &str,
+) -> Option<&'a Route> {
+    let mut priorities: Vec<Option<i64>> = routes.iter().map(|route| route.priority).collect();
+    priorities.sort_by_key(|priority| (priority.is_none(), *priority));
+    priorities.dedup();
+    for priority in priorities {
+        for rank in (0..=2).rev() {
+            let candidates: Vec<&Route> = routes
+                .iter()
+                .filter(|route| route.priority == priority && route.host_rank(host) == Some(rank))
+                .collect();
+            let resources = candidates.iter().filter_map(|route| match &route.matcher {
+                Matcher::Resource(resource) => Some((resource, *route)),
+                _ => None,
+            });
+            if let Some((route, _)) = crate::resource::find_by(resources, method, path) {
+                return Some(route);
+            }
+            let matched = candidates
+                .iter()
+                .find(|route| match &route.matcher {
+                    Matcher::Regex(regex) => route.allows(method) && regex.is_match(path),
+                    _ => false,
+                })
+                .or_else(|| {
+                    candidates.iter().find(|route| {
+                        matches!(route.matcher, Matcher::CatchAll) && route.allows(method)
+                    })
+                });
+            if let Some(route) = matched {
+                return Some(route);
+            }
+        }
+    }
+    None
+}
+
+/// Pairs of indices of routes matching the same requests, where the later route is never matched
+pub fn conflicts(routes: &[Route]) -> Vec<(usize, usize)> {
+    let keys: Vec<String> = routes.iter().map(|route| route.match_key()).collect();
+    (0..keys.len())
+        .filter_map(|j| {
+            keys[..j]
+                .iter()
+                .position(|key| *key == keys[j])
+                .map(|i| (i, j))
+        })
+        .collect()
+}
+
+/// Routes of options with the ones discovered while running, like routes of Docker containers
+#[derive(Debug)]
+pub struct Table {
+    state: std::sync::RwLock<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    configured: Vec<Route>,
+    // Route strings of each source, compared to find changes
+    sources: std::collections::BTreeMap<&'static str, Vec<String>>,
+    routes: std::sync::Arc<Vec<Route>>,
+}
+
+impl Table {
+    pub fn new(routes: Vec<Route>) -> Self {
+        Self {
+            state: std::sync::RwLock::new(State {
+                routes: std::sync::Arc::new(routes.clone()),
+                configured: routes,
+                sources: std::collections::BTreeMap::new(),
+            }),
+        }
+    }
+
+    /// Current routes, which requests keep until they complete
+    pub fn snapshot(&self) -> std::sync::Arc<Vec<Route>> {
+        self.state.read().unwrap().routes.clone()
+    }
+
+    /// Replace the routes of the source, returning whether they changed
+    pub fn update(&self, source: &'static str, routes: Vec<String>) -> Result<bool, anyhow::Error> {
+        let mut state = self.state.write().unwrap();
+        if state
+            .sources
+            .get(source)
+            .map_or(routes.is_empty(), |r| *r == routes)
+        {
+            return Ok(false);
+        }
+        let mut sources = state.sources.clone();
+        sources.insert(source, routes);
+        let routes = merge(&state.configured, &sources)?;
+        state.sources = sources;
+        state.routes = routes;
+        Ok(true)
+    }
+
+    /// Change the target of configured routes of the name to the URL, returning their number
+    ///
+    /// Routes to mocks or static files can't be changed.
+    pub fn set_target_url(&self, name: &str, url: &str) -> Result<usize, anyhow::Error> {
+        let url = parse_url(url, url)?;
+        let mut state = self.state.write().unwrap();
+        let mut configured = state.configured.clone();
+        let mut changed = 0;
+        for route in &mut configured {
+            if route.name.as_deref() == Some(name) {
+                if !matches!(route.target, Target::Url(_)) {
+                    anyhow::bail!(
+                        "route {} targets a mock or static files rather than a URL",
+                        name
+                    );
+                }
+                // The green URL is changed while it's switched to
+                let active = route.is_green_active();
+                match &mut route.green {
+                    Some(green) if active => *green = url.clone(),
+                    _ => route.target = Target::Url(url.clone()),
+                }
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            state.routes = merge(&configured, &state.sources)?;
+            state.configured = configured;
+        }
+        Ok(changed)
+    }
+}
+
+fn merge(
+    configured: &[Route],
+    sources: &std::collections::BTreeMap<&'static str, Vec<String>>,
+) -> Result<std::sync::Arc<Vec<Route>>, anyhow::Error> {
+    let mut routes = configured.to_vec();
+    for route in sources.values().flatten() {
+        routes.push(route.parse()?);
+    }
+    Ok(std::sync::Arc::new(routes))
+}
+
+#[cfg(test)]
+mod tests {
+    fn routes(routes: &[&str]) -> Vec<super::Route> {
+        routes.iter().map(|route| route.parse().unwrap()).collect()
+    }
+
+    // Target URL of the route found for the request
+    fn find<'a>(
+        routes: &'a [super::Route],
+        method: hyper::Method,
+        host: Option<&str>,
+        path: &str,
+    ) -> Option<&'a str> {
+        super::find(routes, &method, host, path).map(|route| match &route.target {
+            super::Target::Url(url) => url.as_str(),
+            _ => unreachable!(),
+        })
+    }
+
+    #[test]
+    fn most_specific_resource_wins() {
+        let routes = routes(&[
+            "/users/*=http://localhost:9001",
+            "/users/{id}=http://localhost:9002",
+            "/users/me=http://localhost:9003",
+            "POST /users/{id}=http://localhost:9004",
+        ]);
+        let get = |path| find(&routes, hyper::Method::GET, None, path);
+        assert_eq!(get("/users/me"), Some("http://localhost:9003"));
+        assert_eq!(get("/users/1"), Some("http://localhost:9002"));
+        assert_eq!(get("/users/1/orders"), Some("http://localhost:9001"));
+        assert_eq!(get("/orders"), None);
+        assert_eq!(
+            find(&routes, hyper::Method::POST, None, "/users/1"),
+            Some("http://localhost:9004")
+        );
+    }
+
+    #[test]
+    fn exact_hosts_win_over_wildcard_hosts_and_no_hosts() {
+        let routes = routes(&[
+            "/api/*=http://localhost:9001",
+            "*.localhost/api/*=http://localhost:9002",
+            "users.localhost/api/*=http://localhost:9003",
+        ]);
+        let get = |host| find(&routes, hyper::Method::GET, host, "/api/users");
+        assert_eq!(get(Some("users.localhost")), Some("http://localhost:9003"));
+        assert_eq!(get(Some("orders.localhost")), Some("http://localhost:9002"));
+        assert_eq!(get(Some("example.com")), Some("http://localhost:9001"));
+        assert_eq!(get(None), Some("http://localhost:9001"));
+    }
+
+    #[test]
+    fn regexes_and_catch_all_come_after_resources() {
+        let routes = routes(&[
+            "/*=http://localhost:9001",
+            "~^/v[0-9]+/=http://localhost:9002",
+            "~^/v1/=http://localhost:9003",
+            "/v1/users=http://localhost:9004",
+        ]);
+        let get = |path| find(&routes, hyper::Method::GET, None, path);
+        assert_eq!(get("/v1/users"), Some("http://localhost:9004"));
+        assert_eq!(get("/v1/orders"), Some("http://localhost:9002"));
+        assert_eq!(get("/health"), Some("http://localhost:9001"));
+    }
+
+    #[test]
+    fn priorities_win_over_specificity() {
+        let routes = routes(&[
+            "/users/me=http://localhost:9001",
+            "/users/*=http://localhost:9002 priority=2",
+            "/*=http://localhost:9003 priority=1",
+        ]);
+        assert_eq!(
+            find(&routes, hyper::Method::GET, None, "/users/me"),
+            Some("http://localhost:9003")
+        );
+    }
+
+    #[test]
+    fn routes_of_same_matches_conflict() {
+        let routes = routes(&[
+            "/users/{id}=http://localhost:9001",
+            "GET /users/{id}=http://localhost:9002",
+            "/users/{id}=http://localhost:9003",
+        ]);
+        assert_eq!(super::conflicts(&routes), vec![(0, 2)]);
+    }
+
+    #[test]
+    fn query_rules_match_decoded_names() {
+        let route: super::Route =
+            "/u=http://localhost:9001 remove-query=a_b rename-query=c_d=>e set-query=f=1"
+                .parse()
+                .unwrap();
+        let (parts, ()) = hyper::Request::builder()
+            .uri("/u?a%5Fb=1&a_b=2&c%5fd=3&f=0&g=4")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let mut request = crate::payload::Request {
+            parts,
+            headers: Vec::new(),
+            body: bytes::Bytes::new(),
+        };
+        route.apply_query_rules(&mut request).unwrap();
+        assert_eq!(request.parts.uri.query(), Some("e=3&g=4&f=1"));
+    }
+}
@@ -0,0 +1,507 @@
+// Nesting of $ref followed at most, which stops recursive schemas of invalid documents
+const MAX_DEPTH: usize = 32;
+
+/// JSON Schema validating request bodies or parameters like models of REST APIs
+///
+/// Keywords of draft 4 used by API Gateway models are supported, with `nullable` of OpenAPI and
+/// numeric `exclusiveMinimum` and `exclusiveMaximum` of later drafts. `$ref` can refer to local
+/// definitions like `#/definitions/Pet`, and `format` isn't validated. Errors are worded like
+/// `$context.error.validationErrorString` of API Gateway.
+#[derive(Debug, Clone)]
+pub struct Schema(serde_json::Value);
+
+impl Schema {
+    pub fn new(schema: serde_json::Value) -> Self {
+        Self(schema)
+    }
+
+    /// Schema of an inline JSON object or a JSON file
+    pub fn read(s: &str) -> Result<Self, anyhow::Error> {
+        let schema = if s.starts_with('{') {
+            serde_json::from_str(s).map_err(|e| anyhow::anyhow!("invalid schema {}: {}", s, e))?
+        } else {
+            crate::json::read_file(s)?
+        };
+        if !schema.is_object() {
+            anyhow::bail!("schema must be an object: {}", s);
+        }
+        Ok(Self(schema))
+    }
+
+    /// Errors of the value, which is valid when there are none
+    pub fn validate(&self, value: &serde_json::Value) -> Vec<String> {
+        let mut errors = Vec::new();
+        self.check(&self.0, value, "", 0, &mut errors);
+        errors
+    }
+
+    /// Errors of query string parameters validated as an object of their first values, which
+    /// are numbers or booleans when the properties of the schema are
+    pub fn validate_parameters(&self, parameters: &[(String, String)]) -> Vec<String> {
+        let mut object = serde_json::Map::new();
+        for (name, value) in parameters {
+            if object.contains_key(name) {
+                continue;
+            }
+            let property = self.resolve(&self.0["properties"][name], 0);
+            let typed = match property["type"].as_str() {
+                Some("integer" | "number" | "boolean") => serde_json::from_str(value)
+                    .ok()
+                    .filter(|v: &serde_json::Value| v.is_number() || v.is_boolean()),
+                _ => None,
+            };
+            object.insert(name.clone(), typed.unwrap_or_else(|| value.clone().into()));
+        }
+        self.validate(&object.into())
+    }
+
+    // Schema referred by $ref of the schema, or the schema itself
+    fn resolve<'a>(&'a self, schema: &'a serde_json::Value, depth: usize) -> &'a serde_json::Value {
+        match schema["$ref"].as_str() {
+            Some(reference) if depth < MAX_DEPTH => {
+                let pointer = reference.strip_prefix('#').unwrap_or(reference);
+                match self.0.pointer(pointer) {
+                    Some(schema) => self.resolve(schema, depth + 1),
+                    None => &serde_json::Value::Null,
+                }
+            }
+            _ => schema,
+        }
+    }
+
+    fn check(
+        &self,
+        schema: &serde_json::Value,
+        value: &serde_json::Value,
+        path: &str,
+        depth: usize,
+        errors: &mut Vec<String>,
+    ) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        if let Some(reference) = schema["$ref"].as_str() {
+            match self.resolve(schema, depth) {
+                serde_json::Value::Null => {
+                    push(errors, path, format!("unresolvable $ref {}", reference))
+                }
+                resolved => self.check(resolved, value, path, depth + 1, errors),
+            }
+            return;
+        }
+        let schema = match schema.as_object() {
+            Some(schema) => schema,
+            // true and false of later drafts
+            None => {
+                if schema == &serde_json::Value::Bool(false) {
+                    push(
+                        errors,
+                        path,
+                        "instance matched a schema which it should not have".to_owned(),
+                    );
+                }
+                return;
+            }
+        };
+        if value.is_null() && schema.get("nullable") == Some(&serde_json::Value::Bool(true)) {
+            return;
+        }
+
+        if let Some(types) = schema.get("type") {
+            let types: Vec<&str> = match types {
+                serde_json::Value::Array(types) => {
+                    types.iter().filter_map(|t| t.as_str()).collect()
+                }
+                types => types.as_str().into_iter().collect(),
+            };
+            if !types.iter().any(|t| has_type(value, t)) {
+                push(
+                    errors,
+                    path,
+                    format!(
+                    "instance type ({}) does not match any allowed primitive type (allowed: {})",
+                    type_name(value),
+                    serde_json::to_string(&types).unwrap_or_default()
+                ),
+                );
+                return;
+            }
+        }
+        if let Some(serde_json::Value::Array(values)) = schema.get("enum") {
+            if !values.iter().any(|v| json_eq(v, value)) {
+                push(
+                    errors,
+                    path,
+                    format!(
+                        "instance value ({}) not found in enum (possible values: {})",
+                        value,
+                        serde_json::Value::Array(values.clone())
+                    ),
+                );
+            }
+        }
+        if let Some(constant) = schema.get("const") {
+            if !json_eq(constant, value) {
+                push(
+                    errors,
+                    path,
+                    format!(
+                        "instance value ({}) is not the constant {}",
+                        value, constant
+                    ),
+                );
+            }
+        }
+
+        match value {
+            serde_json::Value::String(s) => {
+                let length = s.chars().count() as u64;
+                if let Some(min) = schema.get("minLength").and_then(|m| m.as_u64()) {
+                    if length < min {
+                        push(
+                            errors,
+                            path,
+                            format!(
+                                "string {} is too short (length: {}, required minimum: {})",
+                                value, length, min
+                            ),
+                        );
+                    }
+                }
+                if let Some(max) = schema.get("maxLength").and_then(|m| m.as_u64()) {
+                    if length > max {
+                        push(
+                            errors,
+                            path,
+                            format!(
+                                "string {} is too long (length: {}, maximum allowed: {})",
+                                value, length, max
+                            ),
+                        );
+                    }
+                }
+                if let Some(pattern) = schema.get("pattern").and_then(|p| p.as_str()) {
+                    match regex::Regex::new(pattern) {
+                        Ok(regex) if regex.is_match(s) => {}
+                        Ok(_) => push(
+                            errors,
+                            path,
+                            format!(
+                                "ECMA 262 regex \"{}\" does not match input string {}",
+                                pattern, value
+                            ),
+                        ),
+                        Err(_) => push(
+                            errors,
+                            path,
+                            format!("invalid regex \"{}\" of the schema", pattern),
+                        ),
+                    }
+                }
+            }
+            serde_json::Value::Number(number) => {
+                let n = number.as_f64().unwrap_or_default();
+                let bound = |name: &str| schema.get(name).and_then(|b| b.as_f64());
+                let exclusive = |name: &str| schema.get(name) == Some(&true.into());
+                if let Some(min) = bound("minimum") {
+                    if n < min || (exclusive("exclusiveMinimum") && n == min) {
+                        push(errors, path, format!(
+                            "numeric instance is lower than the required minimum (minimum: {}, found: {})",
+                            min, number
+                        ));
+                    }
+                }
+                if let Some(min) = bound("exclusiveMinimum") {
+                    if n <= min {
+                        push(
+                            errors,
+                            path,
+                            format!(
+                            "numeric instance is not strictly greater than the required minimum {}",
+                            min
+                        ),
+                        );
+                    }
+                }
+                if let Some(max) = bound("maximum") {
+                    if n > max || (exclusive("exclusiveMaximum") && n == max) {
+                        push(errors, path, format!(
+                            "numeric instance is greater than the required maximum (maximum: {}, found: {})",
+                            max, number
+                        ));
+                    }
+                }
+                if let Some(max) = bound("exclusiveMaximum") {
+                    if n >= max {
+                        push(
+                            errors,
+                            path,
+                            format!(
+                            "numeric instance is not strictly lower than the required maximum {}",
+                            max
+                        ),
+                        );
+                    }
+                }
+                if let Some(divisor) = bound("multipleOf").filter(|d| *d > 0.0) {
+                    let quotient = n / divisor;
+                    if (quotient - quotient.round()).abs() > 1e-9 {
+                        push(
+                            errors,
+                            path,
+                            format!(
+                                "remainder of division is not zero ({} / {})",
+                                number, divisor
+                            ),
+                        );
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => {
+                let count = items.len() as u64;
+                if let Some(min) = schema.get("minItems").and_then(|m| m.as_u64()) {
+                    if count < min {
+                        push(errors, path, format!(
+                            "array is too short: must have at least {} elements but instance has {} elements",
+                            min, count
+                        ));
+                    }
+                }
+                if let Some(max) = schema.get("maxItems").and_then(|m| m.as_u64()) {
+                    if count > max {
+                        push(errors, path, format!(
+                            "array is too long: must have at most {} elements but instance has {} elements",
+                            max, count
+                        ));
+                    }
+                }
+                if schema.get("uniqueItems") == Some(&true.into())
+                    && items
+                        .iter()
+                        .enumerate()
+                        .any(|(i, a)| items[..i].iter().any(|b| json_eq(a, b)))
+                {
+                    push(
+                        errors,
+                        path,
+                        "array must not contain duplicate elements".to_owned(),
+                    );
+                }
+                match schema.get("items") {
+                    // Tuples of draft 4
+                    Some(serde_json::Value::Array(schemas)) => {
+                        for (i, (item, schema)) in items.iter().zip(schemas).enumerate() {
+                            self.check(
+                                schema,
+                                item,
+                                &pointer(path, &i.to_string()),
+                                depth + 1,
+                                errors,
+                            );
+                        }
+                    }
+                    Some(schema) => {
+                        for (i, item) in items.iter().enumerate() {
+                            self.check(
+                                schema,
+                                item,
+                                &pointer(path, &i.to_string()),
+                                depth + 1,
+                                errors,
+                            );
+                        }
+                    }
+                    None => {}
+                }
+            }
+            serde_json::Value::Object(object) => {
+                if let Some(serde_json::Value::Array(required)) = schema.get("required") {
+                    let missing: Vec<&str> = required
+                        .iter()
+                        .filter_map(|name| name.as_str())
+                        .filter(|name| !object.contains_key(*name))
+                        .collect();
+                    if !missing.is_empty() {
+                        push(
+                            errors,
+                            path,
+                            format!(
+                                "object has missing required properties ({})",
+                                serde_json::to_string(&missing).unwrap_or_default()
+                            ),
+                        );
+                    }
+                }
+                let count = object.len() as u64;
+                if let Some(min) = schema.get("minProperties").and_then(|m| m.as_u64()) {
+                    if count < min {
+                        push(errors, path, format!(
+                            "object has too few properties (found {} but schema requires at least {})",
+                            count, min
+                        ));
+                    }
+                }
+                if let Some(max) = schema.get("maxProperties").and_then(|m| m.as_u64()) {
+                    if count > max {
+                        push(errors, path, format!(
+                            "object has too many properties (found {} but schema requires at most {})",
+                            count, max
+                        ));
+                    }
+                }
+                let properties = schema.get("properties").and_then(|p| p.as_object());
+                let patterns: Vec<(regex::Regex, &serde_json::Value)> = schema
+                    .get("patternProperties")
+                    .and_then(|p| p.as_object())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|(pattern, schema)| {
+                        Some((regex::Regex::new(pattern).ok()?, schema))
+                    })
+                    .collect();
+                let mut additional = Vec::new();
+                for (name, value) in object {
+                    let path = pointer(path, name);
+                    let mut matched = false;
+                    if let Some(schema) = properties.and_then(|p| p.get(name)) {
+                        matched = true;
+                        self.check(schema, value, &path, depth + 1, errors);
+                    }
+                    for (regex, schema) in &patterns {
+                        if regex.is_match(name) {
+                            matched = true;
+                            self.check(schema, value, &path, depth + 1, errors);
+                        }
+                    }
+                    if !matched {
+                        match schema.get("additionalProperties") {
+                            Some(serde_json::Value::Bool(false)) => additional.push(name.as_str()),
+                            Some(schema @ serde_json::Value::Object(_)) => {
+                                self.check(schema, value, &path, depth + 1, errors)
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                if !additional.is_empty() {
+                    push(
+                        errors,
+                        path,
+                        format!(
+                        "object instance has properties which are not allowed by the schema: {}",
+                        serde_json::to_string(&additional).unwrap_or_default()
+                    ),
+                    );
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(serde_json::Value::Array(schemas)) = schema.get("allOf") {
+            for schema in schemas {
+                self.check(schema, value, path, depth + 1, errors);
+            }
+        }
+        let matches = |schemas: &[serde_json::Value]| {
+            schemas
+                .iter()
+                .filter(|schema| {
+                    let mut errors = Vec::new();
+                    self.check(schema, value, path, depth + 1, &mut errors);
+                    errors.is_empty()
+                })
+                .count()
+        };
+        if let Some(serde_json::Value::Array(schemas)) = schema.get("anyOf") {
+            if matches(schemas) == 0 {
+                push(
+                    errors,
+                    path,
+                    format!(
+                        "instance failed to match at least one required schema among {}",
+                        schemas.len()
+                    ),
+                );
+            }
+        }
+        if let Some(serde_json::Value::Array(schemas)) = schema.get("oneOf") {
+            let matched = matches(schemas);
+            if matched != 1 {
+                push(
+                    errors,
+                    path,
+                    format!(
+                        "instance failed to match exactly one schema (matched {} out of {})",
+                        matched,
+                        schemas.len()
+                    ),
+                );
+            }
+        }
+        if let Some(not) = schema.get("not") {
+            if matches(std::slice::from_ref(not)) == 1 {
+                push(
+                    errors,
+                    path,
+                    "instance matched a schema which it should not have".to_owned(),
+                );
+            }
+        }
+    }
+}
+
+fn push(errors: &mut Vec<String>, path: &str, message: String) {
+    errors.push(if path.is_empty() {
+        message
+    } else {
+        format!("{}: {}", path, message)
+    });
+}
+
+// JSON Pointer of the property or the index, escaped like RFC 6901
+fn pointer(path: &str, key: &str) -> String {
+    format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"))
+}
+
+fn has_type(value: &serde_json::Value, type_: &str) -> bool {
+    match (type_, value) {
+        ("null", serde_json::Value::Null)
+        | ("boolean", serde_json::Value::Bool(_))
+        | ("number", serde_json::Value::Number(_))
+        | ("string", serde_json::Value::String(_))
+        | ("array", serde_json::Value::Array(_))
+        | ("object", serde_json::Value::Object(_)) => true,
+        ("integer", serde_json::Value::Number(n)) => {
+            n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        _ => false,
+    }
+}
+
+fn type_name(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        serde_json::Value::Number(_) => "number",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "object",
+    }
+}
+
+// Equality where 1 and 1.0 are the same number
+fn json_eq(a: &serde_json::Value, b: &serde_json::Value) -> bool {
+    match (a, b) {
+        (serde_json::Value::Number(a), serde_json::Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (serde_json::Value::Array(a), serde_json::Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| json_eq(a, b))
+        }
+        (serde_json::Value::Object(a), serde_json::Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(k, a)| b.get(k).is_some_and(|b| json_eq(a, b)))
+        }
+        (a, b) => a == b,
+    }
+}
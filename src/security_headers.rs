@@ -0,0 +1,40 @@
+// Security headers commonly set in front of public web applications
+const DEFAULT_HEADERS: &[(&str, &str)] = &[
+    (
+        "strict-transport-security",
+        "max-age=31536000; includeSubDomains",
+    ),
+    ("x-content-type-options", "nosniff"),
+    ("x-frame-options", "DENY"),
+    ("content-security-policy", "default-src 'self'"),
+    ("referrer-policy", "strict-origin-when-cross-origin"),
+];
+
+/// Add security headers missing in the response
+///
+/// `overrides` replace the default values or add extra headers, and empty values remove them.
+pub fn inject(
+    headers: &mut hyper::HeaderMap,
+    overrides: &[(String, String)],
+) -> Result<(), anyhow::Error> {
+    let mut security_headers: Vec<(String, String)> = DEFAULT_HEADERS
+        .iter()
+        .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+        .collect();
+    for (k, v) in overrides {
+        let k = k.to_ascii_lowercase();
+        match security_headers.iter_mut().find(|(name, _)| *name == k) {
+            Some(header) => header.1 = v.clone(),
+            None => security_headers.push((k, v.clone())),
+        }
+    }
+    for (k, v) in security_headers {
+        // Headers set by the function win
+        if !v.is_empty() {
+            headers
+                .entry(hyper::header::HeaderName::from_bytes(k.as_bytes())?)
+                .or_insert(hyper::header::HeaderValue::from_str(&v)?);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,277 @@
+const SRV_TYPE: u16 = 33;
+const A_TYPE: u16 = 1;
+const AAAA_TYPE: u16 = 28;
+const IN_CLASS: u16 = 1;
+const QUERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Resolver of targets like `srv://_rie._tcp.users.service.consul`, sent to the host and port of
+/// one of the SRV records by plain HTTP
+///
+/// Records are resolved again after the refresh interval so that targets can move while running.
+/// Records of the lowest priority are chosen by their weights, and hosts are the addresses in the
+/// additional section if the DNS server gives them like Consul.
+#[derive(Debug)]
+pub struct Resolver {
+    server: Option<std::net::SocketAddr>,
+    refresh_interval: std::time::Duration,
+    records: std::sync::Mutex<std::collections::HashMap<String, Resolved>>,
+}
+
+#[derive(Debug, Clone)]
+struct Resolved {
+    at: std::time::Instant,
+    records: Vec<Record>,
+}
+
+#[derive(Debug, Clone)]
+struct Record {
+    priority: u16,
+    weight: u16,
+    port: u16,
+    host: String,
+}
+
+impl Resolver {
+    /// Resolver sending queries to the server, or to the first nameserver of /etc/resolv.conf
+    pub fn new(
+        server: Option<std::net::SocketAddr>,
+        refresh_interval: std::time::Duration,
+    ) -> Self {
+        Self {
+            server,
+            refresh_interval,
+            records: std::sync::Mutex::new(std::collections::HashMap::new()),
+        }
+    }
+
+    /// Root URL of the target, where srv:// URLs are replaced with http:// URLs of a record
+    pub async fn resolve(&self, url: &str) -> Result<String, anyhow::Error> {
+        let rest = match url.strip_prefix("srv://") {
+            Some(rest) => rest,
+            None => return Ok(url.to_owned()),
+        };
+        let (name, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, ""),
+        };
+        let cached = self
+            .records
+            .lock()
+            .unwrap()
+            .get(name)
+            .filter(|resolved| resolved.at.elapsed() < self.refresh_interval)
+            .cloned();
+        let resolved = match cached {
+            Some(resolved) => resolved,
+            None => {
+                let records = self.query(name).await?;
+                if records.is_empty() {
+                    anyhow::bail!("no SRV records of {}", name);
+                }
+                log::info!(
+                    "Resolved SRV records of {}: {}",
+                    name,
+                    records
+                        .iter()
+                        .map(|r| format!("{}:{}", r.host, r.port))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                let resolved = Resolved {
+                    at: std::time::Instant::now(),
+                    records,
+                };
+                self.records
+                    .lock()
+                    .unwrap()
+                    .insert(name.to_owned(), resolved.clone());
+                resolved
+            }
+        };
+        let record = choose(&resolved.records);
+        let host = if record.host.contains(':') {
+            format!("[{}]", record.host)
+        } else {
+            record.host.clone()
+        };
+        Ok(format!("http://{}:{}{}", host, record.port, path))
+    }
+
+    async fn query(&self, name: &str) -> Result<Vec<Record>, anyhow::Error> {
+        let server = match self.server {
+            Some(server) => server,
+            None => system_nameserver()?,
+        };
+        let random = uuid::Uuid::new_v4();
+        let id = u16::from_be_bytes([random.as_bytes()[0], random.as_bytes()[1]]);
+        // Recursion desired, one question
+        let mut query = Vec::with_capacity(name.len() + 18);
+        query.extend_from_slice(&id.to_be_bytes());
+        query.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        for label in name.trim_end_matches('.').split('.') {
+            if label.is_empty() || label.len() > 63 {
+                anyhow::bail!("invalid DNS name: {}", name);
+            }
+            query.push(label.len() as u8);
+            query.extend_from_slice(label.as_bytes());
+        }
+        query.push(0);
+        query.extend_from_slice(&SRV_TYPE.to_be_bytes());
+        query.extend_from_slice(&IN_CLASS.to_be_bytes());
+
+        let bind: std::net::SocketAddr = if server.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = tokio::net::UdpSocket::bind(bind).await?;
+        socket.connect(server).await?;
+        socket.send(&query).await?;
+        let mut buf = vec![0; 4096];
+        let len = tokio::time::timeout(QUERY_TIMEOUT, async {
+            loop {
+                let len = socket.recv(&mut buf).await?;
+                // Ignore stray responses of other queries
+                if len >= 2 && buf[..2] == id.to_be_bytes() {
+                    return Ok::<_, std::io::Error>(len);
+                }
+            }
+        })
+        .await
+        .map_err(|_| anyhow::anyhow!("DNS server {} didn't respond for {}", server, name))??;
+        parse_response(&buf[..len])
+            .map_err(|e| anyhow::anyhow!("failed to resolve SRV records of {}: {}", name, e))
+    }
+}
+
+// Records of the lowest priority are chosen randomly in proportion to their weights
+fn choose(records: &[Record]) -> &Record {
+    let priority = records.iter().map(|r| r.priority).min().unwrap_or_default();
+    let candidates: Vec<&Record> = records.iter().filter(|r| r.priority == priority).collect();
+    let total: u32 = candidates.iter().map(|r| u32::from(r.weight)).sum();
+    if total == 0 {
+        return candidates[0];
+    }
+    let random = uuid::Uuid::new_v4();
+    let bytes = random.as_bytes();
+    let mut n = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) % total;
+    for record in &candidates {
+        if n < u32::from(record.weight) {
+            return record;
+        }
+        n -= u32::from(record.weight);
+    }
+    candidates[0]
+}
+
+fn parse_response(message: &[u8]) -> Result<Vec<Record>, anyhow::Error> {
+    if message.len() < 12 {
+        anyhow::bail!("truncated DNS response");
+    }
+    let flags = u16::from_be_bytes([message[2], message[3]]);
+    if flags & 0x0200 != 0 {
+        anyhow::bail!("DNS response is truncated, which needs TCP queries");
+    }
+    match flags & 0x000f {
+        0 => {}
+        3 => return Ok(Vec::new()),
+        rcode => anyhow::bail!("DNS server responded RCODE {}", rcode),
+    }
+    let count = |i: usize| usize::from(u16::from_be_bytes([message[i], message[i + 1]]));
+    let (questions, answers) = (count(4), count(6));
+    let others = count(8) + count(10);
+
+    let mut offset = 12;
+    for _ in 0..questions {
+        offset = read_name(message, offset)?.1 + 4;
+    }
+    let mut records = Vec::new();
+    let mut addresses = std::collections::HashMap::new();
+    for i in 0..answers + others {
+        let (name, next) = read_name(message, offset)?;
+        let header = message
+            .get(next..next + 10)
+            .ok_or_else(|| anyhow::anyhow!("truncated DNS record"))?;
+        let type_ = u16::from_be_bytes([header[0], header[1]]);
+        let length = usize::from(u16::from_be_bytes([header[8], header[9]]));
+        let data_offset = next + 10;
+        let data = message
+            .get(data_offset..data_offset + length)
+            .ok_or_else(|| anyhow::anyhow!("truncated DNS record"))?;
+        match type_ {
+            SRV_TYPE if i < answers && data.len() > 6 => records.push(Record {
+                priority: u16::from_be_bytes([data[0], data[1]]),
+                weight: u16::from_be_bytes([data[2], data[3]]),
+                port: u16::from_be_bytes([data[4], data[5]]),
+                host: read_name(message, data_offset + 6)?.0,
+            }),
+            A_TYPE if data.len() == 4 => {
+                let ip = std::net::Ipv4Addr::new(data[0], data[1], data[2], data[3]);
+                addresses.entry(name).or_insert_with(|| ip.to_string());
+            }
+            AAAA_TYPE if data.len() == 16 => {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(data);
+                let ip = std::net::Ipv6Addr::from(octets);
+                addresses.entry(name).or_insert_with(|| ip.to_string());
+            }
+            _ => {}
+        }
+        offset = data_offset + length;
+    }
+    for record in &mut records {
+        if let Some(address) = addresses.get(&record.host) {
+            record.host = address.clone();
+        }
+    }
+    Ok(records)
+}
+
+// Name at the offset following compression pointers, and the offset after it
+fn read_name(message: &[u8], mut offset: usize) -> Result<(String, usize), anyhow::Error> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    for _ in 0..128 {
+        let len = *message
+            .get(offset)
+            .ok_or_else(|| anyhow::anyhow!("truncated DNS name"))?;
+        if len & 0xc0 == 0xc0 {
+            let low = *message
+                .get(offset + 1)
+                .ok_or_else(|| anyhow::anyhow!("truncated DNS name"))?;
+            end.get_or_insert(offset + 2);
+            offset = usize::from(u16::from_be_bytes([len & 0x3f, low]));
+        } else if len == 0 {
+            return Ok((labels.join("."), end.unwrap_or(offset + 1)));
+        } else {
+            let label = message
+                .get(offset + 1..offset + 1 + usize::from(len))
+                .ok_or_else(|| anyhow::anyhow!("truncated DNS name"))?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            offset += 1 + usize::from(len);
+        }
+    }
+    Err(anyhow::anyhow!("too many labels or pointers in DNS name"))
+}
+
+fn system_nameserver() -> Result<std::net::SocketAddr, anyhow::Error> {
+    let content = std::fs::read_to_string("/etc/resolv.conf")
+        .map_err(|e| anyhow::anyhow!("failed to open /etc/resolv.conf: {}", e))?;
+    content
+        .lines()
+        .find_map(|line| {
+            let ip: std::net::IpAddr = line.strip_prefix("nameserver")?.trim().parse().ok()?;
+            Some((ip, 53).into())
+        })
+        .ok_or_else(|| anyhow::anyhow!("no nameserver in /etc/resolv.conf"))
+}
+
+/// DNS server like `127.0.0.1:8600` of Consul, where the port defaults to 53
+pub fn parse_server(s: &str) -> Result<std::net::SocketAddr, anyhow::Error> {
+    match s.parse::<std::net::IpAddr>() {
+        Ok(ip) => Ok((ip, 53).into()),
+        Err(_) => s
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid DNS server {}: {}", s, e)),
+    }
+}
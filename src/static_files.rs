@@ -0,0 +1,156 @@
+// Content types by file extensions, falling back to application/octet-stream
+const CONTENT_TYPES: &[(&str, &str)] = &[
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("css", "text/css; charset=utf-8"),
+    ("js", "text/javascript; charset=utf-8"),
+    ("mjs", "text/javascript; charset=utf-8"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("webmanifest", "application/manifest+json"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("csv", "text/csv; charset=utf-8"),
+    ("xml", "application/xml"),
+    ("svg", "image/svg+xml"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("ico", "image/x-icon"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("wasm", "application/wasm"),
+    ("pdf", "application/pdf"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("mp3", "audio/mpeg"),
+];
+
+/// Directory served by routes to `file:`
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    root: std::path::PathBuf,
+    // Stripped from request paths, like /assets of /assets/*
+    pub prefix: String,
+    // Served instead of missing files, like index.html of single page applications
+    pub spa_fallback: Option<String>,
+}
+
+impl StaticFiles {
+    pub fn root(&self) -> &std::path::Path {
+        &self.root
+    }
+
+    pub fn new(root: &str) -> Self {
+        Self {
+            root: std::path::PathBuf::from(root),
+            prefix: String::new(),
+            spa_fallback: None,
+        }
+    }
+
+    pub async fn serve(
+        &self,
+        method: &hyper::Method,
+        path: &str,
+    ) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+        if method != hyper::Method::GET && method != hyper::Method::HEAD {
+            return Ok(hyper::Response::builder()
+                .status(hyper::StatusCode::METHOD_NOT_ALLOWED)
+                .header(hyper::header::ALLOW, "GET, HEAD")
+                .body(hyper::Body::empty())?);
+        }
+        // The prefix is a whole segment, so /static doesn't serve /staticfoo
+        let path = path
+            .strip_prefix(self.prefix.as_str())
+            .filter(|path| path.is_empty() || path.starts_with('/'));
+        let file = match path {
+            Some(path) => {
+                let path = percent_encoding::percent_decode_str(path).decode_utf8_lossy();
+                match self.resolve(&path) {
+                    Some(file) => Some(file),
+                    None => match &self.spa_fallback {
+                        Some(fallback) => self.resolve(fallback),
+                        None => None,
+                    },
+                }
+            }
+            None => None,
+        };
+        let file = match file {
+            Some(file) => file,
+            None => {
+                return Ok(hyper::Response::builder()
+                    .status(hyper::StatusCode::NOT_FOUND)
+                    .header(hyper::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+                    .body(hyper::Body::from("Not Found\n"))?);
+            }
+        };
+        log::info!("Serving {}", file.display());
+        let content_type = file
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| {
+                CONTENT_TYPES
+                    .iter()
+                    .find(|(e, _)| e.eq_ignore_ascii_case(ext))
+            })
+            .map(|(_, content_type)| *content_type)
+            .unwrap_or("application/octet-stream");
+        Ok(hyper::Response::builder()
+            .header(hyper::header::CONTENT_TYPE, content_type)
+            .body(hyper::Body::from(tokio::fs::read(&file).await?))?)
+    }
+
+    // Existing file of the path under the root, serving index.html of directories
+    fn resolve(&self, path: &str) -> Option<std::path::PathBuf> {
+        let mut file = self.root.clone();
+        for segment in path.split('/') {
+            match segment {
+                "" | "." => {}
+                // Files outside of the root are never served
+                ".." => return None,
+                segment => file.push(segment),
+            }
+        }
+        if file.is_dir() {
+            file.push("index.html");
+        }
+        if file.is_file() {
+            Some(file)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[tokio::test]
+    async fn prefix_is_whole_segment() {
+        let root = std::env::temp_dir().join(format!("static-files-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("static")).unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+        std::fs::write(root.join("staticfoo"), "foo").unwrap();
+        let mut files = super::StaticFiles::new(root.to_str().unwrap());
+        files.prefix = "/static".to_owned();
+        let status = |path: &'static str| {
+            let files = files.clone();
+            async move {
+                files
+                    .serve(&hyper::Method::GET, path)
+                    .await
+                    .unwrap()
+                    .status()
+            }
+        };
+        assert_eq!(status("/static/a.txt").await, hyper::StatusCode::OK);
+        assert_eq!(status("/staticfoo").await, hyper::StatusCode::NOT_FOUND);
+        assert_eq!(status("/other/a.txt").await, hyper::StatusCode::NOT_FOUND);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+/// Limits of throttling like API Gateway, where requests are refilled at `rateLimit` per second
+/// up to `burstLimit`
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Limits {
+    pub rate_limit: f64,
+    pub burst_limit: u32,
+}
+
+/// Token bucket of the limits, which starts full
+#[derive(Debug)]
+pub struct Bucket {
+    limits: Limits,
+    tokens: f64,
+    updated_at: std::time::Instant,
+}
+
+impl Bucket {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            tokens: limits.burst_limit.into(),
+            updated_at: std::time::Instant::now(),
+        }
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    // Whether the bucket has been refilled to the burst limit
+    fn is_full(&self) -> bool {
+        let elapsed = self.updated_at.elapsed().as_secs_f64();
+        self.tokens + elapsed * self.limits.rate_limit >= self.limits.burst_limit.into()
+    }
+
+    /// Take a token for a request, returning false when the request is throttled
+    pub fn take(&mut self) -> bool {
+        let now = std::time::Instant::now();
+        let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.limits.rate_limit).min(self.limits.burst_limit.into());
+        self.updated_at = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// Bucket shared by gateways and clones of routes
+pub type Shared = std::sync::Arc<std::sync::Mutex<Bucket>>;
+
+pub fn shared(limits: Limits) -> Shared {
+    std::sync::Arc::new(std::sync::Mutex::new(Bucket::new(limits)))
+}
+
+// Buckets of clients are dropped when they are full, once this many clients are tracked
+const MAX_CLIENTS: usize = 10000;
+
+/// Token buckets of each client address, independent of throttling of the stage and routes
+#[derive(Debug)]
+pub struct PerClient {
+    limits: Limits,
+    buckets: std::sync::Mutex<std::collections::HashMap<std::net::IpAddr, Bucket>>,
+}
+
+impl PerClient {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            buckets: std::sync::Mutex::new(std::collections::HashMap::new()),
+        }
+    }
+
+    /// Take a token of the client, returning seconds to wait until the next token when the
+    /// request is rejected
+    pub fn take(&self, ip: std::net::IpAddr) -> Result<(), u64> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_CLIENTS {
+            buckets.retain(|_, bucket| !bucket.is_full());
+        }
+        let limits = self.limits;
+        if buckets
+            .entry(ip)
+            .or_insert_with(|| Bucket::new(limits))
+            .take()
+        {
+            Ok(())
+        } else {
+            Err((1.0 / limits.rate_limit).ceil() as u64)
+        }
+    }
+}
@@ -0,0 +1,178 @@
+pub fn server_config(
+    cert_path: &std::path::Path,
+    key_path: &std::path::Path,
+    client_ca_path: Option<&std::path::Path>,
+) -> Result<tokio_rustls::rustls::ServerConfig, anyhow::Error> {
+    let certs = read_pem(cert_path)?
+        .into_iter()
+        .filter_map(|item| match item {
+            rustls_pemfile::Item::X509Certificate(der) => {
+                Some(tokio_rustls::rustls::Certificate(der))
+            }
+            _ => None,
+        })
+        .collect();
+    let key = read_pem(key_path)?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(der)
+            | rustls_pemfile::Item::PKCS8Key(der)
+            | rustls_pemfile::Item::ECKey(der) => Some(tokio_rustls::rustls::PrivateKey(der)),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow::anyhow!("no private key found in {}", key_path.display()))?;
+    let builder = tokio_rustls::rustls::ServerConfig::builder().with_safe_defaults();
+    let builder = if let Some(path) = client_ca_path {
+        // Clients must present a certificate signed by the CA like mutual TLS of API Gateway
+        let mut roots = tokio_rustls::rustls::RootCertStore::empty();
+        for item in read_pem(path)? {
+            if let rustls_pemfile::Item::X509Certificate(der) = item {
+                roots.add(&tokio_rustls::rustls::Certificate(der))?;
+            }
+        }
+        builder.with_client_cert_verifier(
+            tokio_rustls::rustls::server::AllowAnyAuthenticatedClient::new(roots).boxed(),
+        )
+    } else {
+        builder.with_no_client_auth()
+    };
+    let mut config = builder.with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+fn read_pem(path: &std::path::Path) -> Result<Vec<rustls_pemfile::Item>, anyhow::Error> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| anyhow::anyhow!("failed to open {}: {}", path.display(), e))?;
+    Ok(rustls_pemfile::read_all(&mut std::io::BufReader::new(
+        file,
+    ))?)
+}
+
+/// Accepted connection, either plain TCP or TLS
+pub enum Connection {
+    Plain(hyper::server::conn::AddrStream),
+    Tls(Box<tokio_rustls::server::TlsStream<hyper::server::conn::AddrStream>>),
+}
+
+impl Connection {
+    pub fn remote_addr(&self) -> std::net::SocketAddr {
+        match self {
+            Self::Plain(stream) => stream.remote_addr(),
+            Self::Tls(stream) => stream.get_ref().0.remote_addr(),
+        }
+    }
+
+    pub fn client_cert(&self) -> Option<ClientCert> {
+        match self {
+            Self::Plain(_) => None,
+            Self::Tls(stream) => {
+                let certs = stream.get_ref().1.peer_certificates()?;
+                match ClientCert::from_der(&certs.first()?.0) {
+                    Ok(cert) => Some(cert),
+                    Err(e) => {
+                        log::warn!("Failed to parse client certificate: {}", e);
+                        None
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl tokio::io::AsyncRead for Connection {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => std::pin::Pin::new(stream).poll_read(cx, buf),
+            Self::Tls(stream) => std::pin::Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl tokio::io::AsyncWrite for Connection {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            Self::Plain(stream) => std::pin::Pin::new(stream).poll_write(cx, buf),
+            Self::Tls(stream) => std::pin::Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => std::pin::Pin::new(stream).poll_flush(cx),
+            Self::Tls(stream) => std::pin::Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => std::pin::Pin::new(stream).poll_shutdown(cx),
+            Self::Tls(stream) => std::pin::Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+// https://docs.aws.amazon.com/apigateway/latest/developerguide/rest-api-mutual-tls.html
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientCert {
+    client_cert_pem: String,
+    #[serde(rename = "subjectDN")]
+    subject_dn: String,
+    #[serde(rename = "issuerDN")]
+    issuer_dn: String,
+    serial_number: String,
+    validity: ClientCertValidity,
+}
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ClientCertValidity {
+    not_before: String,
+    not_after: String,
+}
+
+impl ClientCert {
+    fn from_der(der: &[u8]) -> Result<Self, anyhow::Error> {
+        let (_, cert) = x509_parser::parse_x509_certificate(der)
+            .map_err(|e| anyhow::anyhow!("invalid X.509 certificate: {}", e))?;
+        let encoded = base64::encode(der);
+        let mut pem = "-----BEGIN CERTIFICATE-----\n".to_owned();
+        for line in encoded.as_bytes().chunks(64) {
+            pem.push_str(std::str::from_utf8(line)?);
+            pem.push('\n');
+        }
+        pem.push_str("-----END CERTIFICATE-----\n");
+        let validity = cert.validity();
+        Ok(Self {
+            client_cert_pem: pem,
+            subject_dn: cert.subject().to_string(),
+            issuer_dn: cert.issuer().to_string(),
+            serial_number: cert.raw_serial_as_string(),
+            validity: ClientCertValidity {
+                not_before: format_time(validity.not_before.timestamp()),
+                not_after: format_time(validity.not_after.timestamp()),
+            },
+        })
+    }
+}
+
+// API Gateway formats validity like OpenSSL, e.g. "May 28 12:30:02 2019 GMT"
+fn format_time(timestamp: i64) -> String {
+    chrono::DateTime::<chrono::Utc>::from_timestamp(timestamp, 0)
+        .map(|t| t.format("%b %e %H:%M:%S %Y GMT").to_string())
+        .unwrap_or_default()
+}
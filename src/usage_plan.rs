@@ -0,0 +1,116 @@
+/// API keys with their usage plans, like `{"KEY": {"id": "a1b2c3d4e5", "throttle": {"rateLimit":
+/// 5, "burstLimit": 10}, "quota": {"limit": 1000, "period": "DAY"}}}`
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct ApiKeys(std::collections::HashMap<String, ApiKey>);
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ApiKey {
+    // apiKeyId of requestContext, defaulting to the key
+    id: Option<String>,
+    throttle: Option<crate::throttle::Limits>,
+    quota: Option<Quota>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Quota {
+    limit: u64,
+    period: Period,
+}
+
+// Quotas are reset at the start of each period in UTC
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+impl ApiKeys {
+    pub fn read_file(path: &str) -> Result<Self, anyhow::Error> {
+        serde_json::from_value(crate::json::read_file(path)?)
+            .map_err(|e| anyhow::anyhow!("invalid API keys in {}: {}", path, e))
+    }
+}
+
+/// Reason of requests rejected by usage plans
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    // 403 of missing or unknown keys
+    InvalidKey,
+    // 429 of throttle limits
+    Throttled,
+    // 429 of quotas
+    QuotaExceeded,
+}
+
+/// Usage of API keys enforcing throttle limits and quotas of each key
+#[derive(Debug)]
+pub struct Meter {
+    keys: std::collections::HashMap<String, ApiKey>,
+    usage: std::sync::Mutex<std::collections::HashMap<String, Usage>>,
+}
+
+#[derive(Debug)]
+struct Usage {
+    bucket: Option<crate::throttle::Bucket>,
+    // Period of the quota like 2024-01-31, and requests counted in it
+    period: String,
+    count: u64,
+}
+
+impl Meter {
+    /// Meter of the keys of the file and keys without limits
+    pub fn new(mut keys: ApiKeys, unlimited_keys: &[String]) -> Self {
+        for key in unlimited_keys {
+            keys.0.entry(key.clone()).or_default();
+        }
+        Self {
+            keys: keys.0,
+            usage: std::sync::Mutex::new(std::collections::HashMap::new()),
+        }
+    }
+
+    /// Count the request of the key, returning the ID of the key unless the request is rejected
+    pub fn check(
+        &self,
+        key: Option<&str>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<&str, Rejection> {
+        let (key, api_key) = key
+            .and_then(|key| self.keys.get_key_value(key))
+            .ok_or(Rejection::InvalidKey)?;
+        let period = api_key.quota.as_ref().map(|quota| {
+            let format = match quota.period {
+                Period::Day => "%Y-%m-%d",
+                Period::Week => "%G-W%V",
+                Period::Month => "%Y-%m",
+            };
+            now.format(format).to_string()
+        });
+        let mut usage = self.usage.lock().unwrap();
+        let usage = usage.entry(key.clone()).or_insert_with(|| Usage {
+            bucket: api_key.throttle.map(crate::throttle::Bucket::new),
+            period: String::new(),
+            count: 0,
+        });
+        if let Some(bucket) = &mut usage.bucket {
+            if !bucket.take() {
+                return Err(Rejection::Throttled);
+            }
+        }
+        if let (Some(quota), Some(period)) = (&api_key.quota, period) {
+            if usage.period != period {
+                usage.period = period;
+                usage.count = 0;
+            }
+            if usage.count >= quota.limit {
+                return Err(Rejection::QuotaExceeded);
+            }
+            usage.count += 1;
+        }
+        Ok(api_key.id.as_deref().unwrap_or(key))
+    }
+}
@@ -0,0 +1,1311 @@
+// Nesting of templates and expressions at most, which stops runaway recursion
+const MAX_DEPTH: usize = 64;
+
+/// Velocity template of mapping templates of REST APIs
+///
+/// References like `$input.path('$.name').size()`, `#set`, `#if`/`#elseif`/`#else`, `#foreach`
+/// with `$foreach.hasNext`, and comments are supported. Lines of only directives are dropped
+/// from the output, and undefined references are written as they are unless they are quiet like
+/// `$!name`.
+#[derive(Debug, Clone)]
+pub struct Template(Vec<Node>);
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Reference(Reference),
+    Set(Reference, Expr),
+    If(Vec<(Expr, Vec<Node>)>, Vec<Node>),
+    Foreach(String, Expr, Vec<Node>),
+}
+
+#[derive(Debug, Clone)]
+struct Reference {
+    // Source written for undefined references
+    source: String,
+    quiet: bool,
+    name: String,
+    accessors: Vec<Accessor>,
+}
+
+#[derive(Debug, Clone)]
+enum Accessor {
+    Property(String),
+    Method(String, Vec<Expr>),
+    Index(Expr),
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(serde_json::Value),
+    // Double-quoted strings interpolating references
+    Interpolated(Vec<Node>),
+    Reference(Reference),
+    List(Vec<Expr>),
+    Map(Vec<(Expr, Expr)>),
+    Range(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+}
+
+// Directives ending blocks
+enum Terminator {
+    End,
+    Else,
+    ElseIf(Expr),
+}
+
+impl std::str::FromStr for Template {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            chars: s.chars().collect(),
+            pos: 0,
+            depth: 0,
+        };
+        match parser.block()? {
+            (nodes, None) => Ok(Self(nodes)),
+            (_, Some(_)) => Err(parser.error("#end, #else or #elseif without a block")),
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> anyhow::Error {
+        let line = self.chars[..self.pos.min(self.chars.len())]
+            .iter()
+            .filter(|c| **c == '\n')
+            .count()
+            + 1;
+        anyhow::anyhow!("invalid template at line {}: {}", line, message)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.peek_at(i) == Some(c))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), anyhow::Error> {
+        self.skip_whitespace();
+        if self.peek() != Some(c) {
+            return Err(self.error(&format!("expected '{}'", c)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn identifier(&mut self) -> Option<String> {
+        if !self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        {
+            return None;
+        }
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            self.pos += 1;
+        }
+        Some(self.chars[start..self.pos].iter().collect())
+    }
+
+    // Nodes until the end of the template or a directive ending the block
+    fn block(&mut self) -> Result<(Vec<Node>, Option<Terminator>), anyhow::Error> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("too deeply nested"));
+        }
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '\\' if matches!(self.peek_at(1), Some('$' | '#')) => {
+                    text.extend(self.peek_at(1));
+                    self.pos += 2;
+                }
+                '#' if self.peek_at(1) == Some('#') => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                }
+                '#' if self.peek_at(1) == Some('*') => {
+                    while self.pos < self.chars.len() && !self.starts_with("*#") {
+                        self.pos += 1;
+                    }
+                    self.pos += 2;
+                }
+                '#' if self.starts_with("#[[") => {
+                    self.pos += 3;
+                    while self.pos < self.chars.len() && !self.starts_with("]]#") {
+                        text.extend(self.peek());
+                        self.pos += 1;
+                    }
+                    self.pos += 3;
+                }
+                '#' => {
+                    let start = self.pos;
+                    self.pos += 1;
+                    let braced = self.peek() == Some('{');
+                    if braced {
+                        self.pos += 1;
+                    }
+                    let name = self.identifier();
+                    if braced && name.is_some() && self.peek() == Some('}') {
+                        self.pos += 1;
+                    }
+                    let name = match name.as_deref() {
+                        Some(name @ ("set" | "if" | "elseif" | "else" | "end" | "foreach")) => {
+                            name.to_owned()
+                        }
+                        _ => {
+                            self.pos = start + 1;
+                            text.push('#');
+                            continue;
+                        }
+                    };
+                    // Lines of only directives are dropped with their indentation
+                    let line = self.chars[..start]
+                        .iter()
+                        .rev()
+                        .take_while(|c| **c != '\n')
+                        .collect::<String>();
+                    let at_line_start = line.chars().all(|c| c == ' ' || c == '\t')
+                        && text.ends_with(line.as_str());
+                    let indent = if at_line_start {
+                        text.split_off(text.len() - line.len())
+                    } else {
+                        String::new()
+                    };
+                    if !text.is_empty() {
+                        nodes.push(Node::Text(std::mem::take(&mut text)));
+                    }
+                    let argument = match name.as_str() {
+                        "set" | "if" | "elseif" | "foreach" => {
+                            self.expect('(')?;
+                            Some(name.clone())
+                        }
+                        _ => None,
+                    };
+                    let mut directive = match argument.as_deref() {
+                        Some("set") => {
+                            self.skip_whitespace();
+                            let target = self
+                                .reference()?
+                                .ok_or_else(|| self.error("#set requires a reference"))?;
+                            self.expect('=')?;
+                            let value = self.expr()?;
+                            self.expect(')')?;
+                            Some(Directive::Set(target, value))
+                        }
+                        Some("if") | Some("elseif") => {
+                            let condition = self.expr()?;
+                            self.expect(')')?;
+                            Some(if name == "if" {
+                                Directive::If(condition)
+                            } else {
+                                Directive::ElseIf(condition)
+                            })
+                        }
+                        Some(_) => {
+                            self.skip_whitespace();
+                            if self.peek() != Some('$') {
+                                return Err(self.error("#foreach requires a variable"));
+                            }
+                            self.pos += 1;
+                            let variable = self
+                                .identifier()
+                                .ok_or_else(|| self.error("#foreach requires a variable"))?;
+                            self.skip_whitespace();
+                            if self.identifier().as_deref() != Some("in") {
+                                return Err(self.error("expected 'in' of #foreach"));
+                            }
+                            let items = self.expr()?;
+                            self.expect(')')?;
+                            Some(Directive::Foreach(variable, items))
+                        }
+                        None if name == "else" => Some(Directive::Else),
+                        None => Some(Directive::End),
+                    };
+                    let rest_of_line = self.chars[self.pos..]
+                        .iter()
+                        .position(|c| *c == '\n')
+                        .map(|i| self.pos + i);
+                    let only_directive = at_line_start
+                        && self.chars[self.pos..rest_of_line.unwrap_or(self.chars.len())]
+                            .iter()
+                            .all(|c| *c == ' ' || *c == '\t' || *c == '\r');
+                    if only_directive {
+                        self.pos = rest_of_line.map(|i| i + 1).unwrap_or(self.chars.len());
+                    } else if !indent.is_empty() {
+                        nodes.push(Node::Text(indent));
+                    }
+                    match directive.take() {
+                        Some(Directive::Set(target, value)) => nodes.push(Node::Set(target, value)),
+                        Some(Directive::If(condition)) => {
+                            let mut branches = Vec::new();
+                            let mut condition = Some(condition);
+                            let otherwise = loop {
+                                let (body, terminator) = self.block()?;
+                                match (condition.take(), terminator) {
+                                    (Some(c), Some(Terminator::End)) => {
+                                        branches.push((c, body));
+                                        break Vec::new();
+                                    }
+                                    (Some(c), Some(Terminator::ElseIf(next))) => {
+                                        branches.push((c, body));
+                                        condition = Some(next);
+                                    }
+                                    (Some(c), Some(Terminator::Else)) => branches.push((c, body)),
+                                    (None, Some(Terminator::End)) => break body,
+                                    (None, Some(_)) => {
+                                        return Err(self.error("#else followed by another branch"))
+                                    }
+                                    (_, None) => return Err(self.error("#if without #end")),
+                                }
+                            };
+                            nodes.push(Node::If(branches, otherwise));
+                        }
+                        Some(Directive::Foreach(variable, items)) => match self.block()? {
+                            (body, Some(Terminator::End)) => {
+                                nodes.push(Node::Foreach(variable, items, body))
+                            }
+                            _ => return Err(self.error("#foreach without #end")),
+                        },
+                        Some(Directive::ElseIf(condition)) => {
+                            self.depth -= 1;
+                            return Ok((nodes, Some(Terminator::ElseIf(condition))));
+                        }
+                        Some(Directive::Else) => {
+                            self.depth -= 1;
+                            return Ok((nodes, Some(Terminator::Else)));
+                        }
+                        Some(Directive::End) | None => {
+                            self.depth -= 1;
+                            return Ok((nodes, Some(Terminator::End)));
+                        }
+                    }
+                }
+                '$' => match self.reference()? {
+                    Some(reference) => {
+                        if !text.is_empty() {
+                            nodes.push(Node::Text(std::mem::take(&mut text)));
+                        }
+                        nodes.push(Node::Reference(reference));
+                    }
+                    None => {
+                        text.push('$');
+                        self.pos += 1;
+                    }
+                },
+                c => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+        self.depth -= 1;
+        Ok((nodes, None))
+    }
+
+    // Reference at the position like $!{name.method('a')[0]}, or None leaving the position
+    fn reference(&mut self) -> Result<Option<Reference>, anyhow::Error> {
+        let start = self.pos;
+        if self.peek() != Some('$') {
+            return Ok(None);
+        }
+        self.pos += 1;
+        let quiet = self.peek() == Some('!');
+        if quiet {
+            self.pos += 1;
+        }
+        let braced = self.peek() == Some('{');
+        if braced {
+            self.pos += 1;
+        }
+        let name = match self.identifier() {
+            Some(name) => name,
+            None => {
+                self.pos = start;
+                return Ok(None);
+            }
+        };
+        let mut accessors = Vec::new();
+        loop {
+            match self.peek() {
+                Some('.') if self.peek_at(1).is_some_and(|c| c.is_ascii_alphabetic()) => {
+                    self.pos += 1;
+                    let name = self.identifier().unwrap_or_default();
+                    if self.peek() == Some('(') {
+                        self.pos += 1;
+                        let mut arguments = Vec::new();
+                        self.skip_whitespace();
+                        if self.peek() == Some(')') {
+                            self.pos += 1;
+                        } else {
+                            loop {
+                                arguments.push(self.expr()?);
+                                self.skip_whitespace();
+                                match self.peek() {
+                                    Some(',') => self.pos += 1,
+                                    Some(')') => {
+                                        self.pos += 1;
+                                        break;
+                                    }
+                                    _ => return Err(self.error("expected ',' or ')'")),
+                                }
+                            }
+                        }
+                        accessors.push(Accessor::Method(name, arguments));
+                    } else {
+                        accessors.push(Accessor::Property(name));
+                    }
+                }
+                Some('[') => {
+                    self.pos += 1;
+                    let index = self.expr()?;
+                    self.expect(']')?;
+                    accessors.push(Accessor::Index(index));
+                }
+                _ => break,
+            }
+        }
+        if braced {
+            if self.peek() != Some('}') {
+                return Err(self.error("expected '}' of the reference"));
+            }
+            self.pos += 1;
+        }
+        Ok(Some(Reference {
+            source: self.chars[start..self.pos].iter().collect(),
+            quiet,
+            name,
+            accessors,
+        }))
+    }
+
+    fn expr(&mut self) -> Result<Expr, anyhow::Error> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("too deeply nested"));
+        }
+        let expr = self.binary(0);
+        self.depth -= 1;
+        expr
+    }
+
+    // Operators of each precedence from the lowest, with their word forms
+    fn binary(&mut self, level: usize) -> Result<Expr, anyhow::Error> {
+        const LEVELS: &[&[(&str, &str)]] = &[
+            &[("||", "||"), ("or", "||")],
+            &[("&&", "&&"), ("and", "&&")],
+            &[("==", "=="), ("!=", "!="), ("eq", "=="), ("ne", "!=")],
+            &[
+                ("<=", "<="),
+                (">=", ">="),
+                ("<", "<"),
+                (">", ">"),
+                ("le", "<="),
+                ("ge", ">="),
+                ("lt", "<"),
+                ("gt", ">"),
+            ],
+            &[("+", "+"), ("-", "-")],
+            &[("*", "*"), ("/", "/"), ("%", "%")],
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        'operators: loop {
+            self.skip_whitespace();
+            for (token, operator) in LEVELS[level] {
+                let is_word = token.chars().all(|c| c.is_ascii_alphabetic());
+                let ends = !is_word
+                    || !self
+                        .peek_at(token.len())
+                        .is_some_and(|c| c.is_ascii_alphanumeric());
+                if self.starts_with(token) && ends && !(*token == "=" && self.starts_with("==")) {
+                    self.pos += token.len();
+                    let right = self.binary(level + 1)?;
+                    left = Expr::Binary(operator, Box::new(left), Box::new(right));
+                    continue 'operators;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, anyhow::Error> {
+        self.skip_whitespace();
+        if self.peek() == Some('!') && self.peek_at(1) != Some('=') {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.starts_with("not") && !self.peek_at(3).is_some_and(|c| c.is_ascii_alphanumeric()) {
+            self.pos += 3;
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.peek() == Some('-') && !self.peek_at(1).is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, anyhow::Error> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let expr = self.expr()?;
+                self.expect(')')?;
+                Ok(expr)
+            }
+            Some('$') => match self.reference()? {
+                Some(reference) => Ok(Expr::Reference(reference)),
+                None => Err(self.error("expected a reference")),
+            },
+            Some('\'') => {
+                self.pos += 1;
+                Ok(Expr::Literal(self.string('\'')?.into()))
+            }
+            Some('"') => {
+                self.pos += 1;
+                let content = self.string('"')?;
+                let mut parser = Parser {
+                    chars: content.chars().collect(),
+                    pos: 0,
+                    depth: self.depth,
+                };
+                Ok(Expr::Interpolated(parser.block()?.0))
+            }
+            Some('[') => {
+                self.pos += 1;
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(Expr::List(Vec::new()));
+                }
+                let first = self.expr()?;
+                self.skip_whitespace();
+                if self.starts_with("..") {
+                    self.pos += 2;
+                    let last = self.expr()?;
+                    self.expect(']')?;
+                    return Ok(Expr::Range(Box::new(first), Box::new(last)));
+                }
+                let mut items = vec![first];
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => {
+                            self.pos += 1;
+                            items.push(self.expr()?);
+                        }
+                        Some(']') => {
+                            self.pos += 1;
+                            return Ok(Expr::List(items));
+                        }
+                        _ => return Err(self.error("expected ',' or ']'")),
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut entries = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(Expr::Map(entries));
+                }
+                loop {
+                    let key = self.expr()?;
+                    self.expect(':')?;
+                    entries.push((key, self.expr()?));
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some('}') => {
+                            self.pos += 1;
+                            return Ok(Expr::Map(entries));
+                        }
+                        _ => return Err(self.error("expected ',' or '}'")),
+                    }
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' => {
+                let start = self.pos;
+                self.pos += 1;
+                while self.peek().is_some_and(|c| c.is_ascii_digit())
+                    || (self.peek() == Some('.')
+                        && self.peek_at(1).is_some_and(|c| c.is_ascii_digit()))
+                {
+                    self.pos += 1;
+                }
+                let number: String = self.chars[start..self.pos].iter().collect();
+                serde_json::from_str(&number)
+                    .map(Expr::Literal)
+                    .map_err(|_| self.error(&format!("invalid number {}", number)))
+            }
+            _ => match self.identifier().as_deref() {
+                Some("true") => Ok(Expr::Literal(true.into())),
+                Some("false") => Ok(Expr::Literal(false.into())),
+                Some("null") => Ok(Expr::Literal(serde_json::Value::Null)),
+                _ => Err(self.error("expected an expression")),
+            },
+        }
+    }
+
+    // Content of a string literal after the opening quote, where doubled quotes are quotes
+    fn string(&mut self, quote: char) -> Result<String, anyhow::Error> {
+        let mut s = String::new();
+        loop {
+            match self.peek() {
+                Some(c) if c == quote && self.peek_at(1) == Some(quote) => {
+                    s.push(quote);
+                    self.pos += 2;
+                }
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(s);
+                }
+                Some(c) => {
+                    s.push(c);
+                    self.pos += 1;
+                }
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+}
+
+enum Directive {
+    Set(Reference, Expr),
+    If(Expr),
+    ElseIf(Expr),
+    Else,
+    End,
+    Foreach(String, Expr),
+}
+
+/// Variables of the mapping template and `$input` of the body
+pub struct Context {
+    pub variables: std::collections::HashMap<String, serde_json::Value>,
+    // Raw body of $input.body
+    pub body: String,
+    // Parameters of $input.params() like {"path": {...}, "querystring": {...}, "header": {...}}
+    pub params: serde_json::Value,
+}
+
+impl Template {
+    pub fn read_file(path: &str) -> Result<Self, anyhow::Error> {
+        std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("failed to read {}: {}", path, e))?
+            .parse()
+            .map_err(|e| anyhow::anyhow!("{}: {:#}", path, e))
+    }
+
+    pub fn render(&self, context: Context) -> Result<String, anyhow::Error> {
+        let json = serde_json::from_str(&context.body).ok();
+        let mut renderer = Renderer {
+            context,
+            json,
+            depth: 0,
+        };
+        let mut output = String::new();
+        renderer.nodes(&self.0, &mut output)?;
+        Ok(output)
+    }
+}
+
+struct Renderer {
+    context: Context,
+    // Body parsed as JSON for $input.json and $input.path
+    json: Option<serde_json::Value>,
+    depth: usize,
+}
+
+impl Renderer {
+    fn nodes(&mut self, nodes: &[Node], output: &mut String) -> Result<(), anyhow::Error> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            anyhow::bail!("template is too deeply nested");
+        }
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Reference(reference) => match self.reference(reference)? {
+                    Some(value) => output.push_str(&to_text(&value)),
+                    None if reference.quiet => {}
+                    None => output.push_str(&reference.source),
+                },
+                Node::Set(target, value) => {
+                    if let Some(value) = self.expr(value)? {
+                        self.set(target, value)?;
+                    }
+                }
+                Node::If(branches, otherwise) => {
+                    let mut body = otherwise;
+                    for (condition, branch) in branches {
+                        if is_truthy(self.expr(condition)?.as_ref()) {
+                            body = branch;
+                            break;
+                        }
+                    }
+                    self.nodes(body, output)?;
+                }
+                Node::Foreach(variable, items, body) => {
+                    let items = match self.expr(items)? {
+                        Some(serde_json::Value::Array(items)) => items,
+                        // Maps are iterated by their values
+                        Some(serde_json::Value::Object(map)) => {
+                            map.into_iter().map(|(_, v)| v).collect()
+                        }
+                        _ => Vec::new(),
+                    };
+                    let saved = (
+                        self.context.variables.remove(variable),
+                        self.context.variables.remove("foreach"),
+                        self.context.variables.remove("velocityCount"),
+                    );
+                    let count = items.len();
+                    for (i, item) in items.into_iter().enumerate() {
+                        self.context.variables.insert(variable.clone(), item);
+                        self.context.variables.insert(
+                            "foreach".to_owned(),
+                            serde_json::json!({
+                                "index": i,
+                                "count": i + 1,
+                                "hasNext": i + 1 < count,
+                                "first": i == 0,
+                                "last": i + 1 == count,
+                            }),
+                        );
+                        self.context
+                            .variables
+                            .insert("velocityCount".to_owned(), (i + 1).into());
+                        self.nodes(body, output)?;
+                    }
+                    for (name, value) in [
+                        (variable.as_str(), saved.0),
+                        ("foreach", saved.1),
+                        ("velocityCount", saved.2),
+                    ] {
+                        match value {
+                            Some(value) => self.context.variables.insert(name.to_owned(), value),
+                            None => self.context.variables.remove(name),
+                        };
+                    }
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    // Assign the value to the variable, or to the property or the index of its value
+    fn set(&mut self, target: &Reference, value: serde_json::Value) -> Result<(), anyhow::Error> {
+        let mut keys = Vec::new();
+        for accessor in &target.accessors {
+            keys.push(match accessor {
+                Accessor::Property(name) => serde_json::Value::from(name.as_str()),
+                Accessor::Index(index) => self.expr(index)?.unwrap_or_default(),
+                Accessor::Method(..) => anyhow::bail!("can't assign to {}", target.source),
+            });
+        }
+        let mut slot = self
+            .context
+            .variables
+            .entry(target.name.clone())
+            .or_insert(serde_json::Value::Null);
+        for key in keys {
+            slot = match (slot, key) {
+                (serde_json::Value::Array(items), serde_json::Value::Number(i)) => {
+                    match i.as_u64().map(|i| i as usize) {
+                        Some(i) if i < items.len() => &mut items[i],
+                        _ => return Ok(()),
+                    }
+                }
+                (slot, key) => {
+                    if !slot.is_object() {
+                        *slot = serde_json::Value::Object(serde_json::Map::new());
+                    }
+                    slot.as_object_mut()
+                        .unwrap()
+                        .entry(to_text(&key))
+                        .or_insert(serde_json::Value::Null)
+                }
+            };
+        }
+        *slot = value;
+        Ok(())
+    }
+
+    fn reference(
+        &mut self,
+        reference: &Reference,
+    ) -> Result<Option<serde_json::Value>, anyhow::Error> {
+        let mut accessors = reference.accessors.iter();
+        let mut value = match reference.name.as_str() {
+            "input" => match accessors.next() {
+                Some(Accessor::Property(name)) if name == "body" => {
+                    Some(self.context.body.clone().into())
+                }
+                Some(Accessor::Method(name, arguments)) => {
+                    let arguments = self.arguments(arguments)?;
+                    self.input(name, &arguments)
+                }
+                _ => None,
+            },
+            "util" => match accessors.next() {
+                Some(Accessor::Method(name, arguments)) => {
+                    let arguments = self.arguments(arguments)?;
+                    util(name, &arguments)?
+                }
+                _ => None,
+            },
+            name => self.context.variables.get(name).cloned(),
+        };
+        for accessor in accessors {
+            let current = match value {
+                Some(current) if !current.is_null() => current,
+                _ => return Ok(None),
+            };
+            value = match accessor {
+                Accessor::Property(name) => match current {
+                    serde_json::Value::Object(mut map) => map.remove(name),
+                    _ => None,
+                },
+                Accessor::Index(index) => {
+                    let index = self.expr(index)?;
+                    match (current, index) {
+                        (serde_json::Value::Array(items), Some(serde_json::Value::Number(i))) => {
+                            i.as_u64().and_then(|i| items.into_iter().nth(i as usize))
+                        }
+                        (serde_json::Value::Object(mut map), Some(key)) => {
+                            map.remove(&to_text(&key))
+                        }
+                        _ => None,
+                    }
+                }
+                Accessor::Method(name, arguments) => {
+                    let arguments = self.arguments(arguments)?;
+                    method(current, name, &arguments)?
+                }
+            };
+        }
+        Ok(value)
+    }
+
+    fn arguments(&mut self, arguments: &[Expr]) -> Result<Vec<serde_json::Value>, anyhow::Error> {
+        arguments
+            .iter()
+            .map(|argument| Ok(self.expr(argument)?.unwrap_or_default()))
+            .collect()
+    }
+
+    // Methods of $input
+    fn input(&self, name: &str, arguments: &[serde_json::Value]) -> Option<serde_json::Value> {
+        let path = arguments.first().and_then(|p| p.as_str());
+        match (name, path) {
+            ("json", Some(path)) => Some(
+                serde_json::to_string(&json_path(self.json.as_ref(), path).unwrap_or_default())
+                    .unwrap_or_default()
+                    .into(),
+            ),
+            ("path", Some(path)) => json_path(self.json.as_ref(), path),
+            ("params", None) => Some(self.context.params.clone()),
+            // Path parameters win over query string parameters, which win over headers
+            ("params", Some(name)) => Some(
+                ["path", "querystring", "header"]
+                    .iter()
+                    .find_map(|location| self.context.params[location].get(name).cloned())
+                    .unwrap_or_else(|| "".into()),
+            ),
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<Option<serde_json::Value>, anyhow::Error> {
+        Ok(match expr {
+            Expr::Literal(value) => Some(value.clone()),
+            Expr::Interpolated(nodes) => {
+                let mut output = String::new();
+                self.nodes(nodes, &mut output)?;
+                Some(output.into())
+            }
+            Expr::Reference(reference) => self.reference(reference)?,
+            Expr::List(items) => Some(self.arguments(items)?.into()),
+            Expr::Map(entries) => {
+                let mut map = serde_json::Map::new();
+                for (key, value) in entries {
+                    let key = self.expr(key)?.unwrap_or_default();
+                    map.insert(to_text(&key), self.expr(value)?.unwrap_or_default());
+                }
+                Some(map.into())
+            }
+            Expr::Range(first, last) => {
+                let first = self.expr(first)?.and_then(|v| v.as_i64());
+                let last = self.expr(last)?.and_then(|v| v.as_i64());
+                match (first, last) {
+                    (Some(first), Some(last)) if first <= last => {
+                        Some((first..=last).collect::<Vec<_>>().into())
+                    }
+                    (Some(first), Some(last)) => {
+                        Some((last..=first).rev().collect::<Vec<_>>().into())
+                    }
+                    _ => None,
+                }
+            }
+            Expr::Not(expr) => Some((!is_truthy(self.expr(expr)?.as_ref())).into()),
+            Expr::Negate(expr) => match self.expr(expr)? {
+                Some(serde_json::Value::Number(n)) => match n.as_i64() {
+                    Some(n) => Some((-n).into()),
+                    None => n.as_f64().map(|n| (-n).into()),
+                },
+                _ => None,
+            },
+            Expr::Binary(operator, left, right) => {
+                let left = self.expr(left)?;
+                // Conditions are short-circuited
+                match *operator {
+                    "&&" if !is_truthy(left.as_ref()) => return Ok(Some(false.into())),
+                    "||" if is_truthy(left.as_ref()) => return Ok(Some(true.into())),
+                    _ => {}
+                }
+                let right = self.expr(right)?;
+                binary(operator, left, right)
+            }
+        })
+    }
+}
+
+fn binary(
+    operator: &str,
+    left: Option<serde_json::Value>,
+    right: Option<serde_json::Value>,
+) -> Option<serde_json::Value> {
+    match operator {
+        "&&" | "||" => return Some(is_truthy(right.as_ref()).into()),
+        "==" => return Some(equals(left.as_ref(), right.as_ref()).into()),
+        "!=" => return Some((!equals(left.as_ref(), right.as_ref())).into()),
+        _ => {}
+    }
+    if let (Some(serde_json::Value::String(a)), Some(serde_json::Value::String(b))) =
+        (&left, &right)
+    {
+        let ordering = a.cmp(b);
+        return match operator {
+            "<" => Some(ordering.is_lt().into()),
+            ">" => Some(ordering.is_gt().into()),
+            "<=" => Some(ordering.is_le().into()),
+            ">=" => Some(ordering.is_ge().into()),
+            _ => None,
+        };
+    }
+    let (a, b) = match (left?, right?) {
+        (serde_json::Value::Number(a), serde_json::Value::Number(b)) => (a, b),
+        _ => return None,
+    };
+    if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+        return match operator {
+            "+" => a.checked_add(b).map(Into::into),
+            "-" => a.checked_sub(b).map(Into::into),
+            "*" => a.checked_mul(b).map(Into::into),
+            // Integers are divided like Java
+            "/" => a.checked_div(b).map(Into::into),
+            "%" => a.checked_rem(b).map(Into::into),
+            "<" => Some((a < b).into()),
+            ">" => Some((a > b).into()),
+            "<=" => Some((a <= b).into()),
+            ">=" => Some((a >= b).into()),
+            _ => None,
+        };
+    }
+    let (a, b) = (a.as_f64()?, b.as_f64()?);
+    match operator {
+        "+" => serde_json::Number::from_f64(a + b).map(Into::into),
+        "-" => serde_json::Number::from_f64(a - b).map(Into::into),
+        "*" => serde_json::Number::from_f64(a * b).map(Into::into),
+        "/" => serde_json::Number::from_f64(a / b).map(Into::into),
+        "%" => serde_json::Number::from_f64(a % b).map(Into::into),
+        "<" => Some((a < b).into()),
+        ">" => Some((a > b).into()),
+        "<=" => Some((a <= b).into()),
+        ">=" => Some((a >= b).into()),
+        _ => None,
+    }
+}
+
+// Values of different types are compared by their strings like Velocity
+fn equals(a: Option<&serde_json::Value>, b: Option<&serde_json::Value>) -> bool {
+    match (a, b) {
+        (None | Some(serde_json::Value::Null), None | Some(serde_json::Value::Null)) => true,
+        (Some(serde_json::Value::Number(a)), Some(serde_json::Value::Number(b))) => {
+            a.as_f64() == b.as_f64()
+        }
+        (Some(a), Some(b)) if std::mem::discriminant(a) == std::mem::discriminant(b) => a == b,
+        (Some(a), Some(b)) => to_text(a) == to_text(b),
+        _ => false,
+    }
+}
+
+fn is_truthy(value: Option<&serde_json::Value>) -> bool {
+    match value {
+        None | Some(serde_json::Value::Null) => false,
+        Some(serde_json::Value::Bool(b)) => *b,
+        Some(_) => true,
+    }
+}
+
+/// Text of the value written by templates like Java, e.g. `[a, b]` and `{key=value}`
+fn to_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(items) => format!(
+            "[{}]",
+            items.iter().map(to_text).collect::<Vec<_>>().join(", ")
+        ),
+        serde_json::Value::Object(map) => format!(
+            "{{{}}}",
+            map.iter()
+                .map(|(k, v)| format!("{}={}", k, to_text(v)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        value => value.to_string(),
+    }
+}
+
+// Value of the JSONPath expression like $.items[0].name or $['key'], where [*] collects values
+pub fn json_path(root: Option<&serde_json::Value>, path: &str) -> Option<serde_json::Value> {
+    let rest = path.trim().strip_prefix('$')?;
+    let mut values = vec![root?.clone()];
+    let mut spread = false;
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        let selector = match c {
+            '.' => {
+                let mut name = String::new();
+                while let Some(c) = chars.peek().filter(|c| **c != '.' && **c != '[') {
+                    name.push(*c);
+                    chars.next();
+                }
+                name
+            }
+            '[' => {
+                let mut selector = String::new();
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    selector.push(c);
+                }
+                selector.trim().trim_matches(['\'', '"']).to_owned()
+            }
+            _ => return None,
+        };
+        values = values
+            .into_iter()
+            .flat_map(|value| match (value, selector.as_str()) {
+                (serde_json::Value::Array(items), "*") => items,
+                (serde_json::Value::Object(map), "*") => map.into_iter().map(|(_, v)| v).collect(),
+                (serde_json::Value::Array(items), index) => index
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| items.into_iter().nth(i))
+                    .into_iter()
+                    .collect(),
+                (serde_json::Value::Object(mut map), name) => {
+                    map.remove(name).into_iter().collect()
+                }
+                _ => Vec::new(),
+            })
+            .collect();
+        spread |= selector == "*";
+    }
+    if spread {
+        Some(values.into())
+    } else {
+        values.into_iter().next()
+    }
+}
+
+// Methods of $util
+fn util(
+    name: &str,
+    arguments: &[serde_json::Value],
+) -> Result<Option<serde_json::Value>, anyhow::Error> {
+    let s = arguments.first().map(to_text).unwrap_or_default();
+    Ok(Some(match name {
+        "escapeJavaScript" => escape_javascript(&s).into(),
+        "parseJson" => serde_json::from_str(&s)
+            .map_err(|e| anyhow::anyhow!("invalid JSON of $util.parseJson: {}", e))?,
+        // Like URLEncoder of Java
+        "urlEncode" => percent_encoding::utf8_percent_encode(&s, FORM)
+            .to_string()
+            .replace("%20", "+")
+            .into(),
+        "urlDecode" => percent_encoding::percent_decode_str(&s.replace('+', " "))
+            .decode_utf8_lossy()
+            .into_owned()
+            .into(),
+        "base64Encode" => base64::encode(s).into(),
+        "base64Decode" => String::from_utf8_lossy(
+            &base64::decode(&s)
+                .map_err(|e| anyhow::anyhow!("invalid base64 of $util.base64Decode: {}", e))?,
+        )
+        .into_owned()
+        .into(),
+        _ => return Ok(None),
+    }))
+}
+
+// Characters kept by URLEncoder of Java
+const FORM: &percent_encoding::AsciiSet = &percent_encoding::NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'*');
+
+// Like StringEscapeUtils.escapeJavaScript of Java, which escapes single quotes and slashes too
+fn escape_javascript(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\'' => escaped.push_str("\\'"),
+            '\\' => escaped.push_str("\\\\"),
+            '/' => escaped.push_str("\\/"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{8}' => escaped.push_str("\\b"),
+            '\u{c}' => escaped.push_str("\\f"),
+            c if (c as u32) < 0x20 || (c as u32) > 0x7e => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    escaped.push_str(&format!("\\u{:04X}", unit));
+                }
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Methods of Java strings, lists and maps
+fn method(
+    value: serde_json::Value,
+    name: &str,
+    arguments: &[serde_json::Value],
+) -> Result<Option<serde_json::Value>, anyhow::Error> {
+    let argument = |i: usize| arguments.get(i).map(to_text).unwrap_or_default();
+    let index = |i: usize| arguments.get(i).and_then(|v| v.as_i64());
+    let regex = |i: usize| {
+        regex::Regex::new(&argument(i))
+            .map_err(|e| anyhow::anyhow!("invalid regex of {}: {}", name, e))
+    };
+    Ok(match (value, name) {
+        (value, "toString") => Some(to_text(&value).into()),
+        (value, "equals") => Some(equals(Some(&value), arguments.first()).into()),
+        (serde_json::Value::String(s), name) => match name {
+            "length" | "size" => Some(s.chars().count().into()),
+            "isEmpty" => Some(s.is_empty().into()),
+            "toUpperCase" => Some(s.to_uppercase().into()),
+            "toLowerCase" => Some(s.to_lowercase().into()),
+            "trim" => Some(s.trim().into()),
+            "contains" => Some(s.contains(&argument(0)).into()),
+            "startsWith" => Some(s.starts_with(&argument(0)).into()),
+            "endsWith" => Some(s.ends_with(&argument(0)).into()),
+            "equalsIgnoreCase" => Some(s.eq_ignore_ascii_case(&argument(0)).into()),
+            "indexOf" => {
+                let i = s.find(&argument(0)).map(|i| s[..i].chars().count() as i64);
+                Some(i.unwrap_or(-1).into())
+            }
+            "replace" => Some(s.replace(&argument(0), &argument(1)).into()),
+            "replaceAll" => Some(
+                regex(0)?
+                    .replace_all(&s, argument(1).as_str())
+                    .into_owned()
+                    .into(),
+            ),
+            "matches" => Some(
+                regex::Regex::new(&format!("^(?:{})$", argument(0)))?
+                    .is_match(&s)
+                    .into(),
+            ),
+            "split" => Some(
+                regex(0)?
+                    .split(&s)
+                    .map(serde_json::Value::from)
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+            "substring" => {
+                let chars: Vec<char> = s.chars().collect();
+                let start = index(0).unwrap_or(0).clamp(0, chars.len() as i64) as usize;
+                let end = index(1)
+                    .unwrap_or(chars.len() as i64)
+                    .clamp(start as i64, chars.len() as i64) as usize;
+                Some(chars[start..end].iter().collect::<String>().into())
+            }
+            _ => None,
+        },
+        (serde_json::Value::Array(items), name) => match name {
+            "size" => Some(items.len().into()),
+            "isEmpty" => Some(items.is_empty().into()),
+            "get" => index(0).and_then(|i| items.get(i as usize).cloned()),
+            "contains" => Some(
+                items
+                    .iter()
+                    .any(|item| equals(Some(item), arguments.first()))
+                    .into(),
+            ),
+            _ => None,
+        },
+        (serde_json::Value::Object(map), name) => match name {
+            "size" => Some(map.len().into()),
+            "isEmpty" => Some(map.is_empty().into()),
+            "get" => map.get(&argument(0)).cloned(),
+            "containsKey" => Some(map.contains_key(&argument(0)).into()),
+            "keySet" => Some(map.keys().cloned().collect::<Vec<_>>().into()),
+            "values" => Some(map.values().cloned().collect::<Vec<_>>().into()),
+            "entrySet" => Some(
+                map.into_iter()
+                    .map(|(key, value)| serde_json::json!({ "key": key, "value": value }))
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    fn render(template: &str) -> String {
+        let mut variables = std::collections::HashMap::new();
+        variables.insert(
+            "context".to_owned(),
+            serde_json::json!({"requestId": "id", "identity": {"sourceIp": "127.0.0.1"}}),
+        );
+        variables.insert(
+            "stageVariables".to_owned(),
+            serde_json::json!({"table": "pets"}),
+        );
+        let context = super::Context {
+            variables,
+            body: r#"{"name": "Tom", "tags": ["a", "b", "c"], "owner": {"age": 3}}"#.to_owned(),
+            params: serde_json::json!({
+                "path": {"id": "1"},
+                "querystring": {"q": "x y"},
+                "header": {"Accept": "application/json"},
+            }),
+        };
+        template
+            .parse::<super::Template>()
+            .unwrap()
+            .render(context)
+            .unwrap()
+    }
+
+    #[test]
+    fn input_of_body_and_params() {
+        assert_eq!(
+            render(r#"{"name": "$input.path('$.name')", "owner": $input.json('$.owner')}"#),
+            r#"{"name": "Tom", "owner": {"age":3}}"#
+        );
+        assert_eq!(render("$input.path('$.tags').size()"), "3");
+        assert_eq!(
+            render("$input.params('id') $input.params('q') $input.params().header.Accept"),
+            "1 x y application/json"
+        );
+        assert_eq!(
+            render("$context.requestId $context.identity.sourceIp $stageVariables.table"),
+            "id 127.0.0.1 pets"
+        );
+    }
+
+    #[test]
+    fn directives() {
+        let template = "#set($tags = $input.path('$.tags'))\n[#foreach($tag in $tags)\"$tag\"#if($foreach.hasNext),#end#end]\n#if($tags.size() > 5)\nmany\n#elseif($tags.size() > 2)\nsome\n#else\nfew\n#end\n";
+        assert_eq!(render(template), "[\"a\",\"b\",\"c\"]\nsome\n");
+    }
+
+    #[test]
+    fn undefined_references() {
+        assert_eq!(render("$missing.name [$!missing]"), "$missing.name []");
+    }
+
+    #[test]
+    fn util_functions() {
+        assert_eq!(
+            render(r#"$util.escapeJavaScript("it's ""q""")"#),
+            r#"it\'s \"q\""#
+        );
+        assert_eq!(
+            render("$util.urlEncode('a b&c') $util.urlDecode('a%20b')"),
+            "a+b%26c a b"
+        );
+        assert_eq!(
+            render("$util.base64Encode('Tom') $util.base64Decode('VG9t')"),
+            "VG9t Tom"
+        );
+        assert_eq!(
+            render("#set($o = $util.parseJson('{\"a\": [1, 2]}'))$o.a[1]"),
+            "2"
+        );
+    }
+
+    #[test]
+    fn comments_are_dropped() {
+        assert_eq!(render("## line\na#* block *#b"), "ab");
+    }
+
+    #[test]
+    fn recursion_is_limited() {
+        let template = format!("{}x{}", "#if(true)".repeat(100), "#end".repeat(100));
+        let context = super::Context {
+            variables: std::collections::HashMap::new(),
+            body: String::new(),
+            params: serde_json::json!({}),
+        };
+        let rendered = template
+            .parse::<super::Template>()
+            .and_then(|template| template.render(context));
+        let error = rendered.unwrap_err().to_string();
+        assert!(error.contains("too deeply nested"), "{}", error);
+    }
+}
@@ -0,0 +1,130 @@
+/// Rules filtering requests in front of the API like AWS WAF, like `[{"name": "NoScanners",
+/// "field": "header:user-agent", "regex": "(?i)sqlmap|nikto"}, {"name": "SmallBodies", "field":
+/// "body", "maxSize": 8192}]`
+///
+/// Rules are evaluated in order, and the first matching rule of `BLOCK` (default) or `ALLOW`
+/// decides, while `COUNT` only logs matches. Rules match when the regex matches with the field or
+/// the field is larger than `maxSize` bytes, and `negate` inverts them.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Rules(Vec<Rule>);
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Rule {
+    name: String,
+    field: Field,
+    #[serde(default, deserialize_with = "regex")]
+    regex: Option<regex::Regex>,
+    max_size: Option<usize>,
+    #[serde(default)]
+    negate: bool,
+    #[serde(default)]
+    action: Action,
+}
+
+// Part of requests inspected by rules, like method, path, query, body or header:NAME
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(try_from = "String")]
+enum Field {
+    Method,
+    Path,
+    // Raw query string without ?
+    Query,
+    Body,
+    Header(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+enum Action {
+    #[default]
+    Block,
+    Allow,
+    Count,
+}
+
+impl std::convert::TryFrom<String> for Field {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "method" => Ok(Self::Method),
+            "path" => Ok(Self::Path),
+            "query" => Ok(Self::Query),
+            "body" => Ok(Self::Body),
+            field => match field.strip_prefix("header:") {
+                Some(name) => Ok(Self::Header(name.to_ascii_lowercase())),
+                None => Err(format!(
+                    "field must be method, path, query, body or header:NAME: {}",
+                    field
+                )),
+            },
+        }
+    }
+}
+
+fn regex<'de, D>(deserializer: D) -> Result<Option<regex::Regex>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s = <String as serde::Deserialize>::deserialize(deserializer)?;
+    regex::Regex::new(&s)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+impl Rules {
+    pub fn read_file(path: &str) -> Result<Self, anyhow::Error> {
+        let rules: Self = serde_json::from_value(crate::json::read_file(path)?)
+            .map_err(|e| anyhow::anyhow!("invalid WAF rules in {}: {}", path, e))?;
+        if let Some(rule) = rules
+            .0
+            .iter()
+            .find(|rule| rule.regex.is_none() && rule.max_size.is_none())
+        {
+            anyhow::bail!(
+                "WAF rule {} in {} has neither regex nor maxSize",
+                rule.name,
+                path
+            );
+        }
+        Ok(rules)
+    }
+
+    /// Name of the rule blocking the request, if any
+    pub fn blocked_by(&self, request: &crate::payload::Request) -> Option<&str> {
+        for rule in &self.0 {
+            let value = match &rule.field {
+                Field::Method => std::borrow::Cow::Borrowed(request.parts.method.as_str()),
+                Field::Path => std::borrow::Cow::Borrowed(request.parts.uri.path()),
+                Field::Query => std::borrow::Cow::Borrowed(request.parts.uri.query().unwrap_or("")),
+                Field::Body => String::from_utf8_lossy(&request.body),
+                // Repeated headers are inspected as their values joined
+                Field::Header(name) => std::borrow::Cow::Owned(
+                    request
+                        .headers
+                        .iter()
+                        .filter(|(k, _)| k == name)
+                        .map(|(_, v)| v.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ),
+            };
+            let size = match rule.field {
+                Field::Body => request.body.len(),
+                _ => value.len(),
+            };
+            let matches = rule.regex.as_ref().is_some_and(|r| r.is_match(&value))
+                || rule.max_size.is_some_and(|max_size| size > max_size);
+            if matches == rule.negate {
+                continue;
+            }
+            match rule.action {
+                Action::Block => return Some(&rule.name),
+                Action::Allow => return None,
+                Action::Count => log::info!("Request matched the WAF rule {}", rule.name),
+            }
+        }
+        None
+    }
+}